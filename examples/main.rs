@@ -1,10 +1,8 @@
-use serde_json;
-
 use r2pipe::R2Pipe;
 use r2pipe::R2PipeSpawnOptions;
 
 fn test_trim() {
-    let mut ns = R2Pipe::spawn("/bin/ls".to_owned(), None).unwrap();
+    let mut ns = R2Pipe::spawn("/bin/ls", None).unwrap();
     println!("(({}))", ns.cmd("\n\n?e hello world\n\n").unwrap());
     println!("(({}))", ns.cmd("\n\n?e hello world\n\n").unwrap());
     println!("(({}))", ns.cmd("\n\n?e hello world\n\n").unwrap());
@@ -22,7 +20,7 @@ fn main() {
     };
     let mut r2p = match R2Pipe::in_session() {
         Some(_) => R2Pipe::open(),
-        None => R2Pipe::spawn("/bin/ls".to_owned(), Some(opts)),
+        None => R2Pipe::spawn("/bin/ls", Some(opts)),
     }
     .unwrap();
 
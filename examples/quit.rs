@@ -1,9 +1,9 @@
 use r2pipe::R2Pipe;
 
 fn main() {
-    let mut r2p = R2Pipe::spawn("/bin/ls".to_owned(), None).unwrap();
+    let mut r2p = R2Pipe::spawn("/bin/ls", None).unwrap();
     println!("{}", r2p.cmd("?e Hello").unwrap());
-    if let Err(_) = r2p.cmd("q") {
+    if r2p.cmd("q").is_err() {
         // !killall r2") {
         println!("Quit happens!");
     } else {
@@ -1,5 +1,3 @@
-use serde_json;
-
 use r2pipe::R2Pipe;
 
 fn main() {
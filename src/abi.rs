@@ -0,0 +1,50 @@
+//! Calling conventions (`afc*`): which registers carry the arguments and
+//! the return value of a function.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, CallingConvention};
+
+impl R2Pipe {
+    /// The calling conventions known for the current architecture and OS
+    /// (`afcl`).
+    pub fn calling_conventions(&mut self) -> Result<Vec<String>, Error> {
+        let res = self.cmd("afcl")?;
+        Ok(res
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// The calling convention of the function at `addr` (`afc`, `afcrj`).
+    pub fn function_cc<A: Into<Addr>>(&mut self, addr: A) -> Result<CallingConvention, Error> {
+        let addr = addr.into();
+        let name = self.cmd(&format!("afc @ {}", addr))?.trim().to_string();
+        if name.is_empty() {
+            return Err(Error::Other(format!("No function at {}", addr)));
+        }
+        let mut cc: CallingConvention = self.cmd_deserialize(&format!("afcrj @ {}", addr))?;
+        cc.name = name;
+        Ok(cc)
+    }
+
+    /// Sets the calling convention of the function at `addr` (`afc`), one
+    /// of `calling_conventions()`.
+    pub fn set_function_cc<A: Into<Addr>>(&mut self, addr: A, name: &str) -> Result<(), Error> {
+        let addr = addr.into();
+        if !self.calling_conventions()?.iter().any(|cc| cc == name) {
+            return Err(Error::Unsupported(format!(
+                "No calling convention named {} here, see `afcl`",
+                name
+            )));
+        }
+        self.cmd_ok(&format!("afc {} @ {}", name, addr))?;
+        let applied = self.cmd(&format!("afc @ {}", addr))?;
+        if applied.trim() != name {
+            return Err(Error::Other(format!("No function at {}", addr)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! Converting between file offsets (physical addresses, `paddr`) and
+//! virtual addresses (`vaddr`).
+//!
+//! Both are plain `Addr`s, and mixing them up goes unnoticed until the
+//! bytes read are wrong. `AddressSpace` is the layout of the binary,
+//! fetched once, so converting many addresses takes no round trip.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, IoMap, Section};
+
+/// A range of the file loaded at a virtual address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    pub paddr: Addr,
+    pub vaddr: Addr,
+    /// Bytes of the file loaded, the rest of `vsize` is zeroed memory.
+    pub size: u64,
+    pub vsize: u64,
+}
+
+impl Mapping {
+    fn from_section(s: &Section) -> Option<Mapping> {
+        // Sections which aren't loaded (symbols, debug info) have no
+        // permissions.
+        if !s.perm.contains(['r', 'w', 'x']) || s.vsize == 0 {
+            return None;
+        }
+        Some(Mapping {
+            paddr: s.paddr,
+            vaddr: s.vaddr,
+            size: s.size.min(s.vsize),
+            vsize: s.vsize,
+        })
+    }
+
+    fn from_map(m: &IoMap) -> Mapping {
+        let size = m.to.0.saturating_sub(m.from.0).saturating_add(1);
+        Mapping {
+            paddr: Addr(m.delta),
+            vaddr: m.from,
+            size,
+            vsize: size,
+        }
+    }
+
+    fn vaddr_of(&self, paddr: Addr) -> Option<Addr> {
+        let off = paddr.0.checked_sub(self.paddr.0)?;
+        (off < self.size).then(|| Addr(self.vaddr.0 + off))
+    }
+
+    fn paddr_of(&self, vaddr: Addr) -> Option<Addr> {
+        let off = vaddr.0.checked_sub(self.vaddr.0)?;
+        (off < self.size).then(|| Addr(self.paddr.0 + off))
+    }
+}
+
+/// How the file is laid out in memory, from its segments, sections or,
+/// for files r2 has no layout of, the IO maps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressSpace {
+    /// Looked up in order, segments before sections.
+    pub mappings: Vec<Mapping>,
+}
+
+impl AddressSpace {
+    /// The virtual address `paddr` is loaded at, `None` if it isn't.
+    ///
+    /// A file offset can be loaded more than once; the first mapping
+    /// wins.
+    pub fn to_vaddr(&self, paddr: Addr) -> Option<Addr> {
+        self.mappings.iter().find_map(|m| m.vaddr_of(paddr))
+    }
+
+    /// The file offset loaded at `vaddr`, `None` if nothing of the file
+    /// is, e.g. in `.bss`.
+    pub fn to_paddr(&self, vaddr: Addr) -> Option<Addr> {
+        self.mappings.iter().find_map(|m| m.paddr_of(vaddr))
+    }
+}
+
+impl R2Pipe {
+    /// The layout of the binary (`iSSj`, `iSj`, and `omj` when neither
+    /// lists anything loaded).
+    pub fn address_space(&mut self) -> Result<AddressSpace, Error> {
+        let sections = match self.sections() {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        let mut mappings: Vec<Mapping> = self
+            .segments()?
+            .iter()
+            .chain(sections.iter())
+            .filter_map(Mapping::from_section)
+            .collect();
+        if mappings.is_empty() {
+            mappings = self.io_maps()?.iter().map(Mapping::from_map).collect();
+        }
+        Ok(AddressSpace { mappings })
+    }
+
+    /// The virtual address the file offset `paddr` is loaded at.
+    ///
+    /// Uses `address_space()`, then asks r2 (`?P`) for what it doesn't
+    /// cover. Convert through an `AddressSpace` to do many at once.
+    pub fn to_vaddr<A: Into<Addr>>(&mut self, paddr: A) -> Result<Option<Addr>, Error> {
+        let paddr = paddr.into();
+        match self.address_space()?.to_vaddr(paddr) {
+            Some(vaddr) => Ok(Some(vaddr)),
+            None => self.convert("?P", paddr),
+        }
+    }
+
+    /// The file offset loaded at the virtual address `vaddr`, as
+    /// `to_vaddr()` the other way round (`?p`).
+    pub fn to_paddr<A: Into<Addr>>(&mut self, vaddr: A) -> Result<Option<Addr>, Error> {
+        let vaddr = vaddr.into();
+        match self.address_space()?.to_paddr(vaddr) {
+            Some(paddr) => Ok(Some(paddr)),
+            None => self.convert("?p", vaddr),
+        }
+    }
+
+    fn convert(&mut self, cmd: &str, addr: Addr) -> Result<Option<Addr>, Error> {
+        let res = self.cmd(&format!("{} {}", cmd, addr))?;
+        // r2 prints -1 (or nothing) for addresses it can't convert.
+        Ok(res.trim().parse::<Addr>().ok().filter(|a| a.0 != u64::MAX))
+    }
+}
@@ -0,0 +1,285 @@
+//! Running r2's code analysis as a whole or pass by pass.
+
+use crate::error::Error;
+use crate::info::R2Version;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{parse_r2_number, Addr, FunctionInfo};
+use crate::tasks::{TaskEntry, TaskId};
+
+use std::ops::Range;
+
+/// Depth of the whole-binary analysis, from cheapest to most expensive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AnalysisLevel {
+    /// Functions, symbols and entrypoints (`aa`).
+    Basic,
+    /// Basic, plus calls, references, emulation and signatures (`aaa`).
+    Auto,
+    /// Auto, plus experimental passes (`aaaa`).
+    Deep,
+    /// Deep, refined again to find more functions (`aaaaa`).
+    Experimental,
+}
+
+impl AnalysisLevel {
+    /// The r2 command running this analysis level.
+    pub fn command(self) -> &'static str {
+        match self {
+            AnalysisLevel::Basic => "aa",
+            AnalysisLevel::Auto => "aaa",
+            AnalysisLevel::Deep => "aaaa",
+            AnalysisLevel::Experimental => "aaaaa",
+        }
+    }
+
+    /// The individual passes approximating this level, in execution order.
+    ///
+    /// Used by `R2Pipe::analyze_with_progress()` to report between passes.
+    pub fn passes(self) -> &'static [&'static str] {
+        match self {
+            AnalysisLevel::Basic => &["aa"],
+            AnalysisLevel::Auto => &["aa", "aar", "aac", "aan"],
+            AnalysisLevel::Deep => &["aa", "aar", "aac", "aan", "aap", "aaef"],
+            AnalysisLevel::Experimental => &["aa", "aar", "aac", "aan", "aap", "aaef", "aaf"],
+        }
+    }
+}
+
+/// Progress of an analysis started with `R2Pipe::analyze_with_progress()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisProgress {
+    /// Command of the pass that just completed.
+    pub phase: &'static str,
+    /// Number of passes completed so far.
+    pub step: usize,
+    /// Total number of passes for the requested level.
+    pub total: usize,
+    /// Number of functions known after this pass (`aflc`).
+    pub functions: u64,
+}
+
+impl AnalysisProgress {
+    /// Completed fraction of the analysis, between 0 and 100.
+    pub fn percentage(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+        self.step as f64 * 100.0 / self.total as f64
+    }
+}
+
+/// Where the analysis is at, see `R2Pipe::analysis_status()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisStatus {
+    pub version: R2Version,
+    /// The analysis commands still running as r2 tasks.
+    pub running: Vec<TaskId>,
+    /// Number of functions known so far (`aflc`).
+    pub functions: u64,
+}
+
+impl AnalysisStatus {
+    pub fn is_done(&self) -> bool {
+        self.running.is_empty()
+    }
+}
+
+/// The first version whose task listing (`&j`) can be read.
+const TASKS_JSON: R2Version = R2Version::new(5, 0, 0);
+
+impl R2Pipe {
+    /// Analyzes the whole binary at the given level.
+    ///
+    /// Analysis commands may leave work running in r2 tasks on recent
+    /// versions, this waits for them too, so that what they print isn't
+    /// read as the answer to the next commands.
+    pub fn analyze(&mut self, level: AnalysisLevel) -> Result<(), Error> {
+        let version = self.analysis_version()?;
+        self.run_analysis(level.command(), version)
+    }
+
+    /// The version of r2, the analysis tasks running (`&j`) and the
+    /// functions found so far.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.spawn_task("aaa")?;
+    /// while !r2.analysis_status()?.is_done() {
+    ///     std::thread::sleep(std::time::Duration::from_millis(200));
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn analysis_status(&mut self) -> Result<AnalysisStatus, Error> {
+        let version = self.r2_version()?;
+        let running = self
+            .analysis_tasks(version)?
+            .into_iter()
+            .filter(|t| t.state != "done" && t.cmd.trim_start().starts_with("aa"))
+            .map(|t| t.id)
+            .collect();
+        Ok(AnalysisStatus {
+            version,
+            running,
+            functions: parse_r2_number(&self.cmd("aflc")?).unwrap_or(0),
+        })
+    }
+
+    /// Analyzes the whole binary pass by pass, calling `progress` after each.
+    ///
+    /// Instead of a single opaque command, the passes listed by
+    /// `AnalysisLevel::passes()` are run one at a time, so long analyses can
+    /// report which phase they are in and how many functions were found.
+    pub fn analyze_with_progress<F>(
+        &mut self,
+        level: AnalysisLevel,
+        mut progress: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&AnalysisProgress),
+    {
+        let version = self.analysis_version()?;
+        let passes = level.passes();
+        for (n, pass) in passes.iter().enumerate() {
+            self.run_analysis(pass, version)?;
+            let functions = parse_r2_number(&self.cmd("aflc")?).unwrap_or(0);
+            progress(&AnalysisProgress {
+                phase: pass,
+                step: n + 1,
+                total: passes.len(),
+                functions,
+            });
+        }
+        Ok(())
+    }
+
+    /// Analyzes function calls from all the functions (`aac`), waiting for
+    /// the tasks it leaves running as `analyze()` does.
+    pub fn analyze_calls(&mut self) -> Result<(), Error> {
+        let version = self.analysis_version()?;
+        self.run_analysis("aac", version)
+    }
+
+    /// Analyzes references to code and data in executable sections (`aar`).
+    pub fn analyze_refs(&mut self) -> Result<(), Error> {
+        let version = self.analysis_version()?;
+        self.run_analysis("aar", version)
+    }
+
+    /// Finds functions by searching for known function preludes (`aap`).
+    pub fn analyze_preludes(&mut self) -> Result<(), Error> {
+        let version = self.analysis_version()?;
+        self.run_analysis("aap", version)
+    }
+
+    /// Analyzes again the functions overlapping `ranges`, e.g. after
+    /// patching them, rather than the whole binary.
+    ///
+    /// Each function is removed (`af-`) and analyzed again from its entry
+    /// (`af`), keeping its name. Returns the functions as analyzed now.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.cmd("aaa")?;
+    /// r2.write_bytes(0x4011d6u64, &[0x90, 0x90])?;
+    /// let changed = r2.reanalyze_dirty(&[r2pipe::Addr(0x4011d6)..r2pipe::Addr(0x4011d8)])?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn reanalyze_dirty(&mut self, ranges: &[Range<Addr>]) -> Result<Vec<FunctionInfo>, Error> {
+        let functions: Vec<FunctionInfo> = match self.cmd_deserialize("aflj") {
+            Err(ref e) if e.is_empty_response() => return Ok(Vec::new()),
+            res => res?,
+        };
+        let dirty = functions.into_iter().filter(|f| {
+            ranges.iter().any(|r| {
+                let len = r.end.0.saturating_sub(r.start.0);
+                overlaps((f.offset.0, f.size), (r.start.0, len))
+            })
+        });
+        let mut redone = Vec::new();
+        for f in dirty {
+            self.cmd_ok(&format!("af- {}", f.offset))?;
+            self.cmd_ok(&format!("af @ {}", f.offset))?;
+            if !f.name.is_empty() {
+                self.cmd_ok(&format!("afn {} @ {}", f.name, f.offset))?;
+            }
+            let info: Vec<FunctionInfo> = self.cmd_deserialize(&format!("afij @ {}", f.offset))?;
+            redone.extend(info.into_iter().next());
+        }
+        Ok(redone)
+    }
+
+    fn run_analysis(&mut self, cmd: &str, version: R2Version) -> Result<(), Error> {
+        let before: Vec<TaskId> = self.analysis_tasks(version)?.iter().map(|t| t.id).collect();
+        self.cmd(cmd)?;
+        let started: Vec<TaskId> = self
+            .analysis_tasks(version)?
+            .into_iter()
+            .filter(|t| t.state != "done" && !before.contains(&t.id))
+            .map(|t| t.id)
+            .collect();
+        for id in started {
+            self.wait_task(id)?;
+        }
+        Ok(())
+    }
+
+    /// The version of r2, `0.0.0` when it doesn't say, which skips the
+    /// task handling.
+    fn analysis_version(&mut self) -> Result<R2Version, Error> {
+        match self.r2_version() {
+            Err(ref e) if matches!(e.root(), Error::UnexpectedOutput(_)) => {
+                Ok(R2Version::new(0, 0, 0))
+            }
+            res => res,
+        }
+    }
+
+    /// The tasks of r2, none on versions whose listing can't be read.
+    fn analysis_tasks(&mut self, version: R2Version) -> Result<Vec<TaskEntry>, Error> {
+        if version < TASKS_JSON {
+            return Ok(Vec::new());
+        }
+        match self.tasks() {
+            Err(ref e) if e.is_empty_response() => Ok(Vec::new()),
+            res => res,
+        }
+    }
+}
+
+/// Whether the ranges of `(start, len)` overlap, empty ones covering their
+/// first byte. Written so ranges at the end of the address space don't
+/// overflow.
+fn overlaps(a: (u64, u64), b: (u64, u64)) -> bool {
+    let (first, second) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+    second.0 - first.0 < first.1.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    #[test]
+    fn overlapping_ranges() {
+        assert!(overlaps((0x1000, 0x10), (0x100f, 1)));
+        assert!(!overlaps((0x1000, 0x10), (0x1010, 1)));
+        assert!(overlaps((0x1000, 0), (0x1000, 0)));
+        assert!(overlaps((0x2000, 4), (0x1000, 0x1001)));
+        assert!(overlaps((u64::MAX - 1, 8), (u64::MAX, 0)));
+        assert!(!overlaps((u64::MAX - 1, 1), (u64::MAX, 0)));
+    }
+
+    #[test]
+    fn single_passes_wait_for_tasks() {
+        let mock = MockR2Pipe::new()
+            .on("?V", "5.9.4 0 @ linux-x86-64")
+            .expect("&j", "[]")
+            .expect("aar", "")
+            .expect("&j", "[]");
+        let mut r2 = R2Pipe::from_commands(mock);
+        r2.set_history(8);
+        r2.analyze_refs().unwrap();
+        let cmds: Vec<_> = r2.history().map(|h| h.cmd.as_str()).collect();
+        assert_eq!(cmds, ["?V", "&j", "aar", "&j"]);
+    }
+}
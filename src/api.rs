@@ -0,0 +1,382 @@
+//! A discoverable facade grouping the typed wrappers by topic.
+//!
+//! `R2Api` owns an `R2Pipe` and hands out short-lived namespaces such as
+//! `r2.cfg()`, `r2.fns()` or `r2.dbg()`, so the available functionality
+//! can be explored without knowing r2's commands. The raw `cmd()`/`cmdj()`
+//! are still there.
+
+use crate::analysis::{AnalysisLevel, AnalysisProgress};
+use crate::config::ConfigGuard;
+use crate::debug::{Access, HeapArena, SyscallEvent};
+use crate::decompiler::DecompilerBackend;
+use crate::error::Error;
+use crate::iter::FunctionIter;
+use crate::r2pipe::{R2Pipe, R2PipeCommands};
+use crate::seek::SeekGuard;
+use crate::signatures::FlirtResult;
+use crate::structs::{
+    Addr, Breakpoint, Frame, FunctionInfo, HeapChunk, Import, Info, Section, StringEntry, Symbol,
+};
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// High-level entry point wrapping an `R2Pipe`.
+pub struct R2Api {
+    pipe: R2Pipe,
+}
+
+impl R2Api {
+    pub fn new(pipe: R2Pipe) -> R2Api {
+        R2Api { pipe }
+    }
+
+    /// Runs a raw r2 command.
+    pub fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        self.pipe.cmd(cmd)
+    }
+
+    /// Runs a raw r2 command and parses its output as JSON.
+    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        self.pipe.cmdj(cmd)
+    }
+
+    /// Access to the underlying pipe.
+    pub fn pipe(&mut self) -> &mut R2Pipe {
+        &mut self.pipe
+    }
+
+    pub fn into_inner(self) -> R2Pipe {
+        self.pipe
+    }
+
+    pub fn close(&mut self) {
+        self.pipe.close();
+    }
+
+    /// Information about the loaded binary.
+    pub fn info(&mut self) -> InfoApi<'_> {
+        InfoApi {
+            pipe: &mut self.pipe,
+        }
+    }
+
+    /// Functions, decompilation and signatures.
+    pub fn fns(&mut self) -> FunctionsApi<'_> {
+        FunctionsApi {
+            pipe: &mut self.pipe,
+        }
+    }
+
+    /// Whole-binary analysis.
+    pub fn analysis(&mut self) -> AnalysisApi<'_> {
+        AnalysisApi {
+            pipe: &mut self.pipe,
+        }
+    }
+
+    /// Configuration variables.
+    pub fn cfg(&mut self) -> ConfigApi<'_> {
+        ConfigApi {
+            pipe: &mut self.pipe,
+        }
+    }
+
+    /// Current offset and seek history.
+    pub fn seek(&mut self) -> SeekApi<'_> {
+        SeekApi {
+            pipe: &mut self.pipe,
+        }
+    }
+
+    /// Breakpoints, memory and heap of the debugged process.
+    pub fn dbg(&mut self) -> DebugApi<'_> {
+        DebugApi {
+            pipe: &mut self.pipe,
+        }
+    }
+}
+
+impl R2PipeCommands for R2Api {
+    fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        self.pipe.cmd(cmd)
+    }
+
+    fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        self.pipe.cmdj(cmd)
+    }
+
+    fn close(&mut self) {
+        self.pipe.close();
+    }
+}
+
+impl From<R2Pipe> for R2Api {
+    fn from(pipe: R2Pipe) -> R2Api {
+        R2Api::new(pipe)
+    }
+}
+
+pub struct InfoApi<'a> {
+    pipe: &'a mut R2Pipe,
+}
+
+impl<'a> InfoApi<'a> {
+    /// See `R2Pipe::bin_info()`.
+    pub fn bin(&mut self) -> Result<Info, Error> {
+        self.pipe.bin_info()
+    }
+
+    /// See `R2Pipe::sections()`.
+    pub fn sections(&mut self) -> Result<Vec<Section>, Error> {
+        self.pipe.sections()
+    }
+
+    /// See `R2Pipe::imports()`.
+    pub fn imports(&mut self) -> Result<Vec<Import>, Error> {
+        self.pipe.imports()
+    }
+
+    /// See `R2Pipe::exports()`.
+    pub fn exports(&mut self) -> Result<Vec<Symbol>, Error> {
+        self.pipe.exports()
+    }
+
+    /// See `R2Pipe::symbols()`.
+    pub fn symbols(&mut self) -> Result<Vec<Symbol>, Error> {
+        self.pipe.symbols()
+    }
+
+    /// See `R2Pipe::strings()`.
+    pub fn strings(&mut self) -> Result<Vec<StringEntry>, Error> {
+        self.pipe.strings()
+    }
+}
+
+pub struct FunctionsApi<'a> {
+    pipe: &'a mut R2Pipe,
+}
+
+impl<'a> FunctionsApi<'a> {
+    /// Analyzed functions (`aflj`), none before analysis.
+    pub fn list(&mut self) -> Result<Vec<FunctionInfo>, Error> {
+        match self.pipe.cmd_deserialize("aflj") {
+            Err(ref e) if e.is_empty_response() => Ok(Vec::new()),
+            res => res,
+        }
+    }
+
+    /// See `R2Pipe::iter_functions()`.
+    pub fn iter(self) -> Result<FunctionIter<'a>, Error> {
+        self.pipe.iter_functions()
+    }
+
+    /// See `R2Pipe::decompile()`.
+    pub fn decompile<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        backend: DecompilerBackend,
+    ) -> Result<String, Error> {
+        self.pipe.decompile(addr, backend)
+    }
+
+    /// See `R2Pipe::cmd_foreach_function()`.
+    pub fn foreach(&mut self, cmd: &str) -> Result<BTreeMap<Addr, Value>, Error> {
+        self.pipe.cmd_foreach_function(cmd)
+    }
+
+    /// See `R2Pipe::apply_flirt()`.
+    pub fn apply_flirt(&mut self, path: &str) -> Result<FlirtResult, Error> {
+        self.pipe.apply_flirt(path)
+    }
+}
+
+pub struct AnalysisApi<'a> {
+    pipe: &'a mut R2Pipe,
+}
+
+impl<'a> AnalysisApi<'a> {
+    /// See `R2Pipe::analyze()`.
+    pub fn run(&mut self, level: AnalysisLevel) -> Result<(), Error> {
+        self.pipe.analyze(level)
+    }
+
+    /// See `R2Pipe::analyze_with_progress()`.
+    pub fn run_with_progress<F>(&mut self, level: AnalysisLevel, progress: F) -> Result<(), Error>
+    where
+        F: FnMut(&AnalysisProgress),
+    {
+        self.pipe.analyze_with_progress(level, progress)
+    }
+
+    /// See `R2Pipe::analyze_calls()`.
+    pub fn calls(&mut self) -> Result<(), Error> {
+        self.pipe.analyze_calls()
+    }
+
+    /// See `R2Pipe::analyze_refs()`.
+    pub fn refs(&mut self) -> Result<(), Error> {
+        self.pipe.analyze_refs()
+    }
+
+    /// See `R2Pipe::analyze_preludes()`.
+    pub fn preludes(&mut self) -> Result<(), Error> {
+        self.pipe.analyze_preludes()
+    }
+}
+
+pub struct ConfigApi<'a> {
+    pipe: &'a mut R2Pipe,
+}
+
+impl<'a> ConfigApi<'a> {
+    /// See `R2Pipe::get_config()`.
+    pub fn get(&mut self, key: &str) -> Result<String, Error> {
+        self.pipe.get_config(key)
+    }
+
+    /// See `R2Pipe::set_config()`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        self.pipe.set_config(key, value)
+    }
+
+    /// See `R2Pipe::config_guard()`.
+    pub fn guard(self, key: &str, value: &str) -> Result<ConfigGuard<'a>, Error> {
+        self.pipe.config_guard(key, value)
+    }
+}
+
+pub struct SeekApi<'a> {
+    pipe: &'a mut R2Pipe,
+}
+
+impl<'a> SeekApi<'a> {
+    /// See `R2Pipe::seek()`.
+    pub fn to<A: Into<Addr>>(&mut self, addr: A) -> Result<Addr, Error> {
+        self.pipe.seek(addr)
+    }
+
+    /// See `R2Pipe::seek_relative()`.
+    pub fn relative(&mut self, delta: i64) -> Result<Addr, Error> {
+        self.pipe.seek_relative(delta)
+    }
+
+    /// See `R2Pipe::current_offset()`.
+    pub fn current(&mut self) -> Result<Addr, Error> {
+        self.pipe.current_offset()
+    }
+
+    /// See `R2Pipe::seek_undo()`.
+    pub fn undo(&mut self) -> Result<Addr, Error> {
+        self.pipe.seek_undo()
+    }
+
+    /// See `R2Pipe::seek_redo()`.
+    pub fn redo(&mut self) -> Result<Addr, Error> {
+        self.pipe.seek_redo()
+    }
+
+    /// See `R2Pipe::seek_history()`.
+    pub fn history(&mut self) -> Result<Vec<Addr>, Error> {
+        self.pipe.seek_history()
+    }
+
+    /// See `R2Pipe::seek_guard()`.
+    pub fn guard<A: Into<Addr>>(self, addr: A) -> Result<SeekGuard<'a>, Error> {
+        self.pipe.seek_guard(addr)
+    }
+}
+
+pub struct DebugApi<'a> {
+    pipe: &'a mut R2Pipe,
+}
+
+impl<'a> DebugApi<'a> {
+    /// See `R2Pipe::is_debugging()`.
+    pub fn is_active(&mut self) -> Result<bool, Error> {
+        self.pipe.is_debugging()
+    }
+
+    /// See `R2Pipe::breakpoints()`.
+    pub fn breakpoints(&mut self) -> Result<Vec<Breakpoint>, Error> {
+        self.pipe.breakpoints()
+    }
+
+    /// See `R2Pipe::add_breakpoint()`.
+    pub fn add_breakpoint<A: Into<Addr>>(&mut self, addr: A) -> Result<(), Error> {
+        self.pipe.add_breakpoint(addr)
+    }
+
+    /// See `R2Pipe::remove_breakpoint()`.
+    pub fn remove_breakpoint<A: Into<Addr>>(&mut self, addr: A) -> Result<(), Error> {
+        self.pipe.remove_breakpoint(addr)
+    }
+
+    /// See `R2Pipe::add_hw_breakpoint()`.
+    pub fn add_hw_breakpoint<A: Into<Addr>>(&mut self, addr: A) -> Result<(), Error> {
+        self.pipe.add_hw_breakpoint(addr)
+    }
+
+    /// See `R2Pipe::add_watchpoint()`.
+    pub fn add_watchpoint<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        size: u64,
+        access: Access,
+    ) -> Result<(), Error> {
+        self.pipe.add_watchpoint(addr, size, access)
+    }
+
+    /// See `R2Pipe::dbg_read()`.
+    pub fn read<A: Into<Addr>>(&mut self, addr: A, len: usize) -> Result<Vec<u8>, Error> {
+        self.pipe.dbg_read(addr, len)
+    }
+
+    /// See `R2Pipe::dbg_write()`.
+    pub fn write<A: Into<Addr>>(&mut self, addr: A, data: &[u8]) -> Result<(), Error> {
+        self.pipe.dbg_write(addr, data)
+    }
+
+    /// See `R2Pipe::backtrace()`.
+    pub fn backtrace(&mut self) -> Result<Vec<Frame>, Error> {
+        self.pipe.backtrace()
+    }
+
+    /// See `R2Pipe::syscall_events()`.
+    pub fn syscalls(&mut self) -> Result<Vec<SyscallEvent>, Error> {
+        self.pipe.syscall_events()
+    }
+
+    /// See `R2Pipe::heap_chunks()`.
+    pub fn heap_chunks(&mut self) -> Result<Vec<HeapChunk>, Error> {
+        self.pipe.heap_chunks()
+    }
+
+    /// See `R2Pipe::heap_arenas()`.
+    pub fn heap_arenas(&mut self) -> Result<Vec<HeapArena>, Error> {
+        self.pipe.heap_arenas()
+    }
+
+    /// See `R2Pipe::dump_core()`.
+    pub fn dump_core(&mut self, path: &str) -> Result<(), Error> {
+        self.pipe.dump_core(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    #[test]
+    fn typed_function_list() {
+        let mock = MockR2Pipe::new()
+            .expect("aflj", r#"[{"offset": 4096, "name": "main", "size": 32}]"#)
+            .expect("aflj", "");
+        let mut r2 = R2Api::new(R2Pipe::from_commands(mock));
+        let fns = r2.fns().list().unwrap();
+        assert_eq!(fns[0].offset, Addr(0x1000));
+        assert_eq!(fns[0].name, "main");
+        assert!(r2.fns().list().unwrap().is_empty());
+    }
+}
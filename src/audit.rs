@@ -0,0 +1,140 @@
+//! An append-only log of every command run on a pipe, see
+//! `R2Pipe::set_audit_log()`.
+//!
+//! Each command is a line of JSON: when it was sent, the session (the
+//! `SessionId` of the pipe unless named with `AuditLog::session()`), the
+//! command and an excerpt of its reply or its error. Lines can be
+//! encrypted one by one with `AuditLog::encrypt_with()`; the crate has no
+//! cipher of its own, bring one (an AEAD such as AES-GCM or
+//! ChaCha20-Poly1305) with its key.
+
+use crate::bytes::base64_encode;
+use crate::history::HISTORY_EXCERPT;
+use crate::session::SessionId;
+
+use serde_json::json;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type Cipher = Box<dyn Fn(&[u8]) -> Vec<u8> + Send>;
+
+/// Where and how the commands of a pipe are logged.
+///
+/// ```no_run
+/// use r2pipe::AuditLog;
+///
+/// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+/// let log = AuditLog::open("/var/log/r2-audit.jsonl")?.session("sample-42");
+/// r2.set_audit_log(Some(log));
+/// r2.cmd("aaa")?;
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+pub struct AuditLog {
+    writer: Box<dyn Write + Send>,
+    session: Option<String>,
+    excerpt: usize,
+    cipher: Option<Cipher>,
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("session", &self.session)
+            .field("excerpt", &self.excerpt)
+            .field("encrypted", &self.cipher.is_some())
+            .finish()
+    }
+}
+
+impl AuditLog {
+    /// Appends to the file at `path`, created if missing.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<AuditLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLog::to_writer(file))
+    }
+
+    /// Writes the lines to `writer`, which is flushed after each.
+    pub fn to_writer<W: Write + Send + 'static>(writer: W) -> AuditLog {
+        AuditLog {
+            writer: Box::new(writer),
+            session: None,
+            excerpt: HISTORY_EXCERPT,
+            cipher: None,
+        }
+    }
+
+    /// Names the session in the lines, in place of the `SessionId` of the
+    /// pipe.
+    pub fn session(mut self, id: &str) -> AuditLog {
+        self.session = Some(id.to_string());
+        self
+    }
+
+    /// Keeps `bytes` of each reply, `HISTORY_EXCERPT` by default.
+    pub fn excerpt(mut self, bytes: usize) -> AuditLog {
+        self.excerpt = bytes;
+        self
+    }
+
+    /// Encrypts each line with `cipher`, and writes it base64 encoded.
+    pub fn encrypt_with<F>(mut self, cipher: F) -> AuditLog
+    where
+        F: Fn(&[u8]) -> Vec<u8> + Send + 'static,
+    {
+        self.cipher = Some(Box::new(cipher));
+        self
+    }
+
+    /// Bytes of each reply kept.
+    pub(crate) fn excerpt_bytes(&self) -> usize {
+        self.excerpt
+    }
+
+    /// Writes the line of `cmd`, whose reply is `len` bytes long, of which
+    /// `reply` may only be the beginning.
+    pub(crate) fn record(
+        &mut self,
+        session: SessionId,
+        cmd: &str,
+        latency: Duration,
+        reply: Result<&[u8], String>,
+        len: usize,
+    ) -> io::Result<()> {
+        let sent = SystemTime::now()
+            .checked_sub(latency)
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        let (response, truncated, error) = match reply {
+            Ok(res) => {
+                let head = &res[..res.len().min(self.excerpt)];
+                let head = String::from_utf8_lossy(head).into_owned();
+                (head, len > self.excerpt, None)
+            }
+            Err(e) => (String::new(), false, Some(e)),
+        };
+        let session = match self.session {
+            Some(ref name) => name.clone(),
+            None => session.to_string(),
+        };
+        let line = json!({
+            "ts_ms": sent.as_millis() as u64,
+            "session": session,
+            "cmd": cmd,
+            "latency_us": latency.as_micros() as u64,
+            "response": response,
+            "truncated": truncated,
+            "error": error,
+        })
+        .to_string();
+        match self.cipher {
+            Some(ref cipher) => {
+                writeln!(self.writer, "{}", base64_encode(&cipher(line.as_bytes())))?
+            }
+            None => writeln!(self.writer, "{}", line)?,
+        }
+        self.writer.flush()
+    }
+}
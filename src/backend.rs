@@ -0,0 +1,60 @@
+//! Which program answers the commands: radare2, or its fork rizin.
+//!
+//! Both print mostly the same JSON. Keys named differently are aliased in
+//! the models of `r2pipe::structs` (`offset` or `addr`, `ninstrs` or
+//! `ninstr`...), so they read the output of either. What aliases can't
+//! cover is normalized here, in `R2Pipe::cmd_deserialize()`.
+
+use crate::error::Error;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    Radare2,
+    Rizin,
+}
+
+impl Backend {
+    /// Guesses the backend from the name of the executable: `rizin` and
+    /// `rz-*` are rizin, anything else radare2.
+    pub fn from_exepath(exepath: &str) -> Backend {
+        let name = Path::new(exepath)
+            .file_stem()
+            .and_then(|n| n.to_str())
+            .unwrap_or("");
+        if name == "rizin" || name == "rz" || name.starts_with("rz-") {
+            Backend::Rizin
+        } else {
+            Backend::Radare2
+        }
+    }
+
+    /// Deserializes the output of a command run by this backend.
+    ///
+    /// Rizin wraps some listings in an object (`{"strings": [...]}`)
+    /// where r2 prints the bare array, so a lone array member is read in
+    /// place of the object when the object doesn't fit `T`.
+    pub(crate) fn deserialize<T: DeserializeOwned>(self, value: Value) -> Result<T, Error> {
+        if self == Backend::Radare2 {
+            return Ok(serde_json::from_value(value)?);
+        }
+        let unwrapped = match value {
+            Value::Object(ref map) if map.len() == 1 => match map.values().next() {
+                Some(inner @ Value::Array(_)) => Some(inner.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        match serde_json::from_value(value) {
+            Ok(res) => Ok(res),
+            Err(e) => match unwrapped {
+                Some(inner) => serde_json::from_value(inner).map_err(|_| e.into()),
+                None => Err(e.into()),
+            },
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Reading and writing binary data without going through the NUL framed
+//! text output.
+//!
+//! Data read is printed in hex, data written is passed base64 encoded, so
+//! NUL bytes, newlines or `;` never reach r2's command parser.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Comment};
+
+impl R2Pipe {
+    /// Reads `len` bytes at `addr`.
+    ///
+    /// The bytes are printed in hex (`p8`) and decoded here, so unlike
+    /// `cmd_bytes("pr ...")` any byte value, NUL included, is safe.
+    pub fn read_bytes<A: Into<Addr>>(&mut self, addr: A, len: usize) -> Result<Vec<u8>, Error> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let res = self.cmd_at(addr, &format!("p8 {}", len))?;
+        decode_hex(res.trim())
+    }
+
+    /// Writes `data` at `addr` (`w6d`, base64 decode and write).
+    pub fn write_bytes<A: Into<Addr>>(&mut self, addr: A, data: &[u8]) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let res = self.cmd_at(addr, &format!("w6d {}", base64_encode(data)))?;
+        match res.trim() {
+            "" => Ok(()),
+            e => Err(Error::UnexpectedOutput(e.to_string())),
+        }
+    }
+
+    /// Sets the comment at `addr` to `text` (`CCu base64:...`).
+    ///
+    /// Unlike `CC`, the text can contain any character.
+    pub fn set_comment<A: Into<Addr>>(&mut self, addr: A, text: &str) -> Result<(), Error> {
+        self.cmd_at(addr, &format!("CCu {}", base64_arg(text.as_bytes())))?;
+        Ok(())
+    }
+
+    /// All the comments (`CCj`), with the text decoded on versions printing
+    /// it as `base64:...`.
+    pub fn comments(&mut self) -> Result<Vec<Comment>, Error> {
+        let mut comments: Vec<Comment> = match self.cmd_deserialize("CCj") {
+            Err(ref e) if e.is_empty_response() => return Ok(Vec::new()),
+            res => res?,
+        };
+        for c in &mut comments {
+            if let Some(text) = c.text.strip_prefix("base64:").and_then(base64_decode) {
+                c.text = String::from_utf8_lossy(&text).into_owned();
+            }
+        }
+        Ok(comments)
+    }
+}
+
+/// Formats `data` for the commands accepting `base64:` arguments.
+///
+/// ```
+/// assert_eq!(r2pipe::bytes::base64_arg(b"a;b\n"), "base64:YTtiCg==");
+/// ```
+pub fn base64_arg(data: &[u8]) -> String {
+    format!("base64:{}", base64_encode(data))
+}
+
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut res = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let mut res = Vec::with_capacity(s.len() / 4 * 3);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in s.trim_end_matches('=').bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        n = (n << 6) | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((n >> bits) as u8);
+        }
+    }
+    Some(res)
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    let hex = hex.as_bytes();
+    if !hex.len().is_multiple_of(2) {
+        return Err(Error::UnexpectedOutput(
+            "Odd number of hex digits in p8 output".to_string(),
+        ));
+    }
+    hex.chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| {
+                    Error::UnexpectedOutput("Invalid hex digit in p8 output".to_string())
+                })
+        })
+        .collect()
+}
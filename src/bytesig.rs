@@ -0,0 +1,158 @@
+//! Byte patterns of code, with the bytes that change between builds or
+//! load addresses wildcarded, for writing detection signatures.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{parse_r2_number, Addr};
+
+use serde_derive::Deserialize;
+use std::fmt;
+
+/// Bytes and the mask of the bits to match, printed as YARA hex strings
+/// do (`48 8b 05 ?? ?? ?? ??`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ByteSignature {
+    pub bytes: Vec<u8>,
+    /// `0xff` for the bytes to match, `0` for the ones to skip.
+    pub mask: Vec<u8>,
+}
+
+impl ByteSignature {
+    /// The pattern between braces, as YARA rules write it.
+    pub fn to_yara(&self) -> String {
+        format!("{{ {} }}", self)
+    }
+
+    /// The pattern as `/x` searches for it, `hex:mask`.
+    pub fn to_r2(&self) -> String {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+        let masked: Vec<u8> = self
+            .bytes
+            .iter()
+            .zip(&self.mask)
+            .map(|(b, m)| b & m)
+            .collect();
+        format!("{}:{}", hex(&masked), hex(&self.mask))
+    }
+
+    /// Number of bytes wildcarded, even partly.
+    pub fn wildcards(&self) -> usize {
+        self.mask.iter().filter(|&&m| m != 0xff).count()
+    }
+}
+
+impl fmt::Display for ByteSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (b, m)) in self.bytes.iter().zip(&self.mask).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            match m {
+                0xff => write!(f, "{:02x}", b)?,
+                0xf0 => write!(f, "{:x}?", b >> 4)?,
+                0x0f => write!(f, "?{:x}", b & 0xf)?,
+                _ => f.write_str("??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct MaskedOp {
+    addr: Addr,
+    size: u64,
+    /// Hex, with the operands which depend on the address zeroed.
+    mask: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Reloc {
+    vaddr: Addr,
+    /// `SET_64`, `ADD_32`...
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl R2Pipe {
+    /// The pattern of the `len` bytes of code at `addr`, wildcarding the
+    /// operands r2's analysis masks (`aoj`): immediates, displacements
+    /// and branch targets.
+    ///
+    /// With `wildcard_relocs`, the bytes patched by relocations (`irj`)
+    /// are wildcarded too, for patterns to match unrelocated files.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let sig = r2.make_byte_signature(0x4010a0u64, 32, true)?;
+    /// println!("rule r {{ strings: $a = {} condition: $a }}", sig.to_yara());
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn make_byte_signature<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        len: usize,
+        wildcard_relocs: bool,
+    ) -> Result<ByteSignature, Error> {
+        let start = addr.into();
+        if len == 0 {
+            return Err(Error::InvalidCommand(format!(
+                "Empty signature at {}",
+                start
+            )));
+        }
+        let bytes = self.read_bytes(start, len)?;
+        let end = start.0 + len as u64;
+        let mut mask = vec![0xff; len];
+        // Instructions are at least a byte long, `len` of them is enough.
+        let ops: Vec<MaskedOp> = self.cmd_deserialize(&format!("aoj {} @ {}", len, start))?;
+        for op in ops.iter().take_while(|op| op.addr.0 < end) {
+            let op_mask = op.mask.as_bytes().chunks(2).map(|pair| {
+                std::str::from_utf8(pair)
+                    .ok()
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .unwrap_or(0xff)
+            });
+            for (i, m) in (op.addr.0..op.addr.0 + op.size).zip(op_mask) {
+                if let Some(byte) = i
+                    .checked_sub(start.0)
+                    .and_then(|i| mask.get_mut(i as usize))
+                {
+                    *byte &= m;
+                }
+            }
+        }
+        if wildcard_relocs {
+            let bits = parse_r2_number(&self.get_config("asm.bits")?)?;
+            let relocs: Vec<Reloc> = match self.cmd_deserialize("irj") {
+                Err(ref e) if e.is_empty_response() => Vec::new(),
+                res => res?,
+            };
+            for reloc in relocs {
+                let size = reloc_size(&reloc.kind).unwrap_or(bits / 8);
+                for i in reloc.vaddr.0..reloc.vaddr.0 + size {
+                    if let Some(byte) = i
+                        .checked_sub(start.0)
+                        .and_then(|i| mask.get_mut(i as usize))
+                    {
+                        *byte = 0;
+                    }
+                }
+            }
+        }
+        Ok(ByteSignature { bytes, mask })
+    }
+}
+
+/// Bytes patched by a relocation of type `SET_32`, `ADD_64`...
+fn reloc_size(kind: &str) -> Option<u64> {
+    let bits: u64 = kind.rsplit('_').next()?.parse().ok()?;
+    Some(bits / 8).filter(|&n| n > 0)
+}
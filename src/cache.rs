@@ -0,0 +1,163 @@
+//! Caching the replies of read-only commands.
+//!
+//! Frontends tend to re-run `ij` or `iSj` on every redraw. `CachedPipe`
+//! answers those from memory and drops everything it remembers as soon as a
+//! command that may change r2's state (writes, analysis, seeks...) goes
+//! through it.
+
+use crate::error::Error;
+use crate::r2pipe::{parse_json, R2Pipe, R2PipeCommands};
+
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+
+/// Number of replies kept by `CachedPipe::new()`.
+pub const DEFAULT_CACHE_CAPACITY: usize = 64;
+
+/// Commands whose reply only depends on the opened file.
+const READ_ONLY: &[&str] = &[
+    "i", "ij", "iI", "iIj", "ie", "iej", "iE", "iEj", "ii", "iij", "il", "ilj", "iM", "iMj", "ir",
+    "irj", "is", "isj", "iS", "iSj", "iSS", "iSSj", "iz", "izj", "izz", "izzj",
+];
+
+/// Characters that make a command chain, pipe or depend on other state.
+const UNCACHEABLE: &[char] = &[';', '|', '>', '`', '@', '~', '$', '('];
+
+/// Returns true for the commands `CachedPipe` caches by default.
+pub fn is_read_only(cmd: &str) -> bool {
+    !cmd.contains(UNCACHEABLE) && READ_ONLY.contains(&cmd)
+}
+
+/// An `R2Pipe` with a LRU cache of the replies of read-only commands.
+///
+/// Any command that isn't cacheable invalidates the whole cache, since r2
+/// gives no way to know what it changed.
+pub struct CachedPipe {
+    pipe: R2Pipe,
+    capacity: usize,
+    entries: HashMap<String, String>,
+    // Least recently used first.
+    order: VecDeque<String>,
+    cacheable: fn(&str) -> bool,
+}
+
+impl CachedPipe {
+    pub fn new(pipe: R2Pipe) -> CachedPipe {
+        CachedPipe::with_capacity(pipe, DEFAULT_CACHE_CAPACITY)
+    }
+
+    pub fn with_capacity(pipe: R2Pipe, capacity: usize) -> CachedPipe {
+        CachedPipe {
+            pipe,
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            cacheable: is_read_only,
+        }
+    }
+
+    /// Replaces `is_read_only()` as the test for cacheable commands.
+    pub fn cacheable(mut self, f: fn(&str) -> bool) -> CachedPipe {
+        self.cacheable = f;
+        self
+    }
+
+    /// Runs `cmd`, or returns its cached reply.
+    pub fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let cmd = cmd.trim();
+        if !(self.cacheable)(cmd) {
+            self.invalidate();
+            return self.pipe.cmd(cmd);
+        }
+        if let Some(res) = self.entries.get(cmd) {
+            let res = res.clone();
+            self.touch(cmd);
+            return Ok(res);
+        }
+        let res = self.pipe.cmd(cmd)?;
+        self.insert(cmd, res.clone());
+        Ok(res)
+    }
+
+    /// Same as `cmd()`, parsing the reply as JSON.
+    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        let res = self.cmd(cmd)?;
+        parse_json(cmd, res.into_bytes())
+    }
+
+    /// Forgets every cached reply.
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// Number of cached replies.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Access to the underlying pipe.
+    ///
+    /// Commands run directly on it bypass the cache, so call `invalidate()`
+    /// afterwards if they changed anything.
+    pub fn pipe(&mut self) -> &mut R2Pipe {
+        &mut self.pipe
+    }
+
+    pub fn into_inner(self) -> R2Pipe {
+        self.pipe
+    }
+
+    pub fn close(&mut self) {
+        self.invalidate();
+        self.pipe.close();
+    }
+
+    fn touch(&mut self, cmd: &str) {
+        if let Some(pos) = self.order.iter().position(|c| c == cmd) {
+            if let Some(c) = self.order.remove(pos) {
+                self.order.push_back(c);
+            }
+        }
+    }
+
+    fn insert(&mut self, cmd: &str, res: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            match self.order.pop_front() {
+                Some(old) => {
+                    self.entries.remove(&old);
+                }
+                None => break,
+            }
+        }
+        self.entries.insert(cmd.to_string(), res);
+        self.order.push_back(cmd.to_string());
+    }
+}
+
+impl R2PipeCommands for CachedPipe {
+    fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        CachedPipe::cmd(self, cmd)
+    }
+
+    fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        CachedPipe::cmdj(self, cmd)
+    }
+
+    fn close(&mut self) {
+        CachedPipe::close(self);
+    }
+}
+
+impl From<R2Pipe> for CachedPipe {
+    fn from(pipe: R2Pipe) -> CachedPipe {
+        CachedPipe::new(pipe)
+    }
+}
@@ -0,0 +1,260 @@
+//! A triage summary of what the binary looks able to do.
+//!
+//! `R2Pipe::capabilities()` matches the imports, strings and sections of
+//! the binary against lists of well known indicators. It is a first look,
+//! not a verdict: a binary can import `socket` and never call it, and a
+//! packed one only shows its real imports once unpacked.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Import, Section, StringEntry};
+
+/// The kinds of behavior looked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    Network,
+    Crypto,
+    AntiDebug,
+    Packer,
+}
+
+/// Where an indicator was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Import,
+    String,
+    Section,
+}
+
+/// An indicator found in the binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evidence {
+    pub category: Category,
+    pub source: Source,
+    /// The import, string or section name that matched.
+    pub name: String,
+    /// Address of the import (its PLT entry), string or section.
+    pub addr: Addr,
+}
+
+/// The indicators found by `R2Pipe::capabilities()`, in the order of
+/// `Category`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapabilityReport {
+    pub evidence: Vec<Evidence>,
+}
+
+impl CapabilityReport {
+    /// Whether anything points at `category`.
+    pub fn has(&self, category: Category) -> bool {
+        self.evidence.iter().any(|e| e.category == category)
+    }
+
+    /// The indicators of `category`.
+    pub fn of(&self, category: Category) -> impl Iterator<Item = &Evidence> + '_ {
+        self.evidence.iter().filter(move |e| e.category == category)
+    }
+
+    /// The categories with at least one indicator.
+    pub fn categories(&self) -> Vec<Category> {
+        let mut categories: Vec<Category> = self.evidence.iter().map(|e| e.category).collect();
+        categories.dedup();
+        categories
+    }
+}
+
+/// Imported functions, without their `A`/`W`/`Ex` variants.
+const IMPORTS: &[(Category, &[&str])] = &[
+    (
+        Category::Network,
+        &[
+            "socket",
+            "connect",
+            "bind",
+            "listen",
+            "accept",
+            "send",
+            "sendto",
+            "recv",
+            "recvfrom",
+            "getaddrinfo",
+            "gethostbyname",
+            "WSAStartup",
+            "WSASocket",
+            "InternetOpen",
+            "InternetOpenUrl",
+            "InternetConnect",
+            "HttpOpenRequest",
+            "HttpSendRequest",
+            "URLDownloadToFile",
+            "WinHttpOpen",
+            "WinHttpConnect",
+            "curl_easy_init",
+        ],
+    ),
+    (
+        Category::Crypto,
+        &[
+            "CryptAcquireContext",
+            "CryptGenKey",
+            "CryptImportKey",
+            "CryptEncrypt",
+            "CryptDecrypt",
+            "CryptCreateHash",
+            "BCryptOpenAlgorithmProvider",
+            "BCryptEncrypt",
+            "BCryptDecrypt",
+            "EVP_EncryptInit",
+            "EVP_DecryptInit",
+            "EVP_CipherInit",
+            "EVP_DigestInit",
+            "AES_set_encrypt_key",
+            "AES_set_decrypt_key",
+            "RSA_public_encrypt",
+            "RSA_private_decrypt",
+            "MD5_Init",
+            "SHA1_Init",
+            "SHA256_Init",
+        ],
+    ),
+    (
+        Category::AntiDebug,
+        &[
+            "IsDebuggerPresent",
+            "CheckRemoteDebuggerPresent",
+            "NtQueryInformationProcess",
+            "ZwQueryInformationProcess",
+            "NtSetInformationThread",
+            "ZwSetInformationThread",
+            "OutputDebugString",
+            "ptrace",
+        ],
+    ),
+];
+
+/// Substrings of the strings, matched ignoring case.
+const STRINGS: &[(Category, &[&str])] = &[
+    (
+        Category::Network,
+        &["http://", "https://", "ftp://", "user-agent:"],
+    ),
+    (Category::Crypto, &["-----begin ", "aes-", "chacha20"]),
+    (
+        Category::AntiDebug,
+        &[
+            "tracerpid",
+            "/proc/self/status",
+            "ollydbg",
+            "x64dbg",
+            "windbg",
+        ],
+    ),
+    (
+        Category::Packer,
+        &["upx!", "this file is packed with", "mpress", "themida"],
+    ),
+];
+
+/// Section names left by packers and protectors, matched ignoring case.
+const PACKER_SECTIONS: &[&str] = &[
+    "upx0", "upx1", "upx2", ".upx", ".aspack", ".adata", ".mpress1", ".mpress2", ".petite",
+    ".nsp0", ".nsp1", ".themida", ".vmp0", ".vmp1", ".enigma1", ".enigma2",
+];
+
+impl R2Pipe {
+    /// Looks for network, crypto, anti-debugging and packing indicators in
+    /// the imports (`iij`), strings (`izj`) and sections (`iSj`).
+    ///
+    /// Sections both writable and executable, or only present in memory,
+    /// count as packer indicators too. See `find_high_entropy_regions()`
+    /// for a look at the bytes themselves.
+    ///
+    /// ```no_run
+    /// use r2pipe::capabilities::Category;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let report = r2.capabilities()?;
+    /// for e in report.of(Category::Network) {
+    ///     println!("{} at {}", e.name, e.addr);
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn capabilities(&mut self) -> Result<CapabilityReport, Error> {
+        let imports: Vec<Import> = match self.imports() {
+            // Static binaries print nothing.
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        let strings = match self.strings() {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        let sections = match self.sections() {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        let mut evidence = Vec::new();
+        evidence.extend(imports.iter().filter_map(import_evidence));
+        evidence.extend(strings.iter().filter_map(string_evidence));
+        evidence.extend(sections.iter().filter_map(section_evidence));
+        evidence.sort_by_key(|e| e.category);
+        Ok(CapabilityReport { evidence })
+    }
+}
+
+fn import_evidence(import: &Import) -> Option<Evidence> {
+    let name = import
+        .name
+        .rsplit('.')
+        .next()
+        .unwrap_or("")
+        .trim_start_matches("__imp_")
+        .trim_start_matches('_');
+    let (category, _) = IMPORTS
+        .iter()
+        .find(|(_, apis)| apis.iter().any(|api| is_api(name, api)))?;
+    Some(Evidence {
+        category: *category,
+        source: Source::Import,
+        name: import.name.clone(),
+        addr: import.plt,
+    })
+}
+
+/// Whether `name` is `api`, or one of its `A`, `W`, `Ex`, `ExA`, `ExW` or
+/// `_ex` variants.
+fn is_api(name: &str, api: &str) -> bool {
+    match name.strip_prefix(api) {
+        Some(rest) => matches!(rest, "" | "A" | "W" | "Ex" | "ExA" | "ExW" | "_ex"),
+        None => false,
+    }
+}
+
+fn string_evidence(string: &StringEntry) -> Option<Evidence> {
+    let text = string.string.to_lowercase();
+    let (category, _) = STRINGS
+        .iter()
+        .find(|(_, needles)| needles.iter().any(|n| text.contains(n)))?;
+    Some(Evidence {
+        category: *category,
+        source: Source::String,
+        name: string.string.clone(),
+        addr: string.vaddr,
+    })
+}
+
+fn section_evidence(section: &Section) -> Option<Evidence> {
+    let name = section.name.to_lowercase();
+    let writable_code = section.perm.contains('w') && section.perm.contains('x');
+    // Unpacked at run time into space the file doesn't fill.
+    let memory_only = section.size == 0 && section.vsize > 0 && section.perm.contains('x');
+    if !(PACKER_SECTIONS.contains(&name.as_str()) || writable_code || memory_only) {
+        return None;
+    }
+    Some(Evidence {
+        category: Category::Packer,
+        source: Source::Section,
+        name: section.name.clone(),
+        addr: section.vaddr,
+    })
+}
@@ -0,0 +1,188 @@
+//! Building commands from untrusted data.
+//!
+//! Characters such as `;`, `|` or backticks change the meaning of an r2
+//! command, so values interpolated into a command must be escaped first.
+
+use crate::error::Error;
+use crate::structs::Addr;
+
+use std::fmt;
+
+/// Characters r2's command parser treats specially.
+const SPECIAL: &[char] = &[
+    '\\', ';', '|', '>', '<', '`', '~', '@', '"', '\'', '$', '#', '(', ')',
+];
+
+/// Escapes `arg` so it is read by r2 as a single literal argument.
+///
+/// Special characters are prefixed with a backslash and line breaks are
+/// written as `\n`/`\r`, so they can't end the command early.
+pub fn escape_arg(arg: &str) -> String {
+    let mut res = String::with_capacity(arg.len());
+    for c in arg.chars() {
+        match c {
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\0' => res.push_str("\\0"),
+            c if SPECIAL.contains(&c) => {
+                res.push('\\');
+                res.push(c);
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Displays the wrapped value escaped with `escape_arg()`.
+///
+/// Used by the `r2cmd!` macro on each interpolated argument.
+pub struct Escaped<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for Escaped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&escape_arg(&self.0.to_string()))
+    }
+}
+
+/// Builder for a single r2 command with escaped arguments.
+///
+/// The command name is trusted, while every argument is escaped so its
+/// content can't chain, pipe, redirect or grep the output:
+///
+/// ```
+/// use r2pipe::cmd::Cmd;
+/// let cmd = Cmd::new("CC").arg("name from sample;!sh").at(0x1000u64);
+/// assert_eq!(cmd.to_string(), "CC name from sample\\;!sh @ 0x1000");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cmd {
+    name: String,
+    args: Vec<String>,
+    at: Option<Addr>,
+    config: Vec<(String, String)>,
+}
+
+impl Cmd {
+    pub fn new(name: &str) -> Cmd {
+        Cmd {
+            name: name.trim().to_string(),
+            args: Vec::new(),
+            at: None,
+            config: Vec::new(),
+        }
+    }
+
+    /// Appends an argument, escaped with `escape_arg()`.
+    pub fn arg<T: fmt::Display>(mut self, arg: T) -> Cmd {
+        self.args.push(escape_arg(&arg.to_string()));
+        self
+    }
+
+    /// Appends several arguments, each escaped with `escape_arg()`.
+    pub fn args<I, T>(mut self, args: I) -> Cmd
+    where
+        I: IntoIterator<Item = T>,
+        T: fmt::Display,
+    {
+        self.args
+            .extend(args.into_iter().map(|a| escape_arg(&a.to_string())));
+        self
+    }
+
+    /// Runs the command at `addr` (`@ addr` suffix).
+    pub fn at<A: Into<Addr>>(mut self, addr: A) -> Cmd {
+        self.at = Some(addr.into());
+        self
+    }
+
+    /// Sets `key` to `value` for this command only (`@e:key=value`
+    /// suffix), without the round trips of `R2Pipe::with_config()`.
+    ///
+    /// The key is trusted like the command name, the value is escaped like
+    /// an argument, commas and spaces included so it can't set other keys:
+    ///
+    /// ```
+    /// use r2pipe::cmd::Cmd;
+    /// let cmd = Cmd::new("pd")
+    ///     .arg(4)
+    ///     .at(0x1000u64)
+    ///     .with_temp_config("asm.arch", "arm")
+    ///     .with_temp_config("asm.bits", 16);
+    /// assert_eq!(cmd.to_string(), "pd 4 @ 0x1000 @e:asm.arch=arm,asm.bits=16");
+    /// ```
+    pub fn with_temp_config<T: fmt::Display>(mut self, key: &str, value: T) -> Cmd {
+        let mut escaped = String::new();
+        for c in escape_arg(&value.to_string()).chars() {
+            match c {
+                ',' | ' ' | '\t' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                c => escaped.push(c),
+            }
+        }
+        self.config.push((key.trim().to_string(), escaped));
+        self
+    }
+}
+
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        if let Some(addr) = self.at {
+            write!(f, " @ {}", addr)?;
+        }
+        for (i, (key, value)) in self.config.iter().enumerate() {
+            let sep = if i == 0 { " @e:" } else { "," };
+            write!(f, "{}{}={}", sep, key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces each `{}` in `template` with the next escaped value in `args`.
+///
+/// Runtime equivalent of the `r2cmd!` macro, for templates that are not
+/// known at compile time. Errors if the placeholders and `args` don't match.
+pub fn format_cmd(template: &str, args: &[&dyn fmt::Display]) -> Result<String, Error> {
+    let mut parts = template.split("{}");
+    let mut res = parts.next().unwrap_or("").to_string();
+    let mut args = args.iter();
+    for part in parts {
+        let arg = args.next().ok_or_else(|| {
+            Error::InvalidCommand(format!("Missing argument for template: {}", template))
+        })?;
+        res.push_str(&Escaped(arg).to_string());
+        res.push_str(part);
+    }
+    if args.next().is_some() {
+        return Err(Error::InvalidCommand(format!(
+            "Too many arguments for template: {}",
+            template
+        )));
+    }
+    Ok(res)
+}
+
+/// Formats an r2 command, escaping every interpolated argument.
+///
+/// ```
+/// # #[macro_use] extern crate r2pipe;
+/// # fn main() {
+/// let name = "evil;!rm";
+/// assert_eq!(r2cmd!("wz {} @ {}", name, 16), "wz evil\\;!rm @ 16");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! r2cmd {
+    ($fmt:expr) => {
+        format!($fmt)
+    };
+    ($fmt:expr, $($arg:expr),+ $(,)?) => {
+        format!($fmt, $($crate::cmd::Escaped(&$arg)),+)
+    };
+}
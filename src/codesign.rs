@@ -0,0 +1,149 @@
+//! Code signing state of the binary: Authenticode for PE, the code
+//! signature of Mach-O (`iC`).
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// A certificate of the signature, with its names flattened to
+/// `key=value, ...`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Certificate {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: String,
+    pub not_after: String,
+}
+
+/// What r2 knows of the signature of the binary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SigningInfo {
+    pub signed: bool,
+    /// The Authenticode hash of a PE, as computed by r2.
+    pub authentihash: Option<String>,
+    /// Whether `authentihash` matches the signed one, i.e. the PE wasn't
+    /// modified since signing. The certificate chain isn't checked.
+    pub authhash_valid: Option<bool>,
+    /// The certificates of a PKCS#7 signature, signer first.
+    pub certificates: Vec<Certificate>,
+    /// The entitlements of a Mach-O.
+    pub entitlements: Option<String>,
+    /// The signature as printed by `iCj`, for what isn't typed above.
+    pub raw: Option<Value>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct SigningBin {
+    signed: bool,
+    authentihash: Option<String>,
+    #[serde(alias = "is_authhash_valid")]
+    authhash_valid: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct SigningFields {
+    bin: SigningBin,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Signature {
+    signature: Option<Value>,
+}
+
+impl R2Pipe {
+    /// The code signing state of the binary (`ij` and `iCj`).
+    ///
+    /// Fields r2 doesn't print for the format, or its version, are left
+    /// empty.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("setup.exe", None).unwrap();
+    /// let info = r2.signatures_info()?;
+    /// if info.signed && info.authhash_valid == Some(false) {
+    ///     println!("modified after signing");
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn signatures_info(&mut self) -> Result<SigningInfo, Error> {
+        let SigningFields { bin } = self.cmd_deserialize("ij")?;
+        let raw = match self.cmd_deserialize::<Signature>("iCj") {
+            Ok(sig) => sig.signature.filter(|v| !v.is_null()),
+            // Formats without signatures print nothing.
+            Err(ref e) if e.is_empty_response() => None,
+            Err(e) => return Err(e),
+        };
+        let mut certificates = Vec::new();
+        if let Some(ref raw) = raw {
+            collect_certificates(raw, &mut certificates);
+        }
+        Ok(SigningInfo {
+            signed: bin.signed,
+            authentihash: bin.authentihash.filter(|h| !h.is_empty()),
+            authhash_valid: bin.authhash_valid,
+            certificates,
+            entitlements: raw.as_ref().and_then(Value::as_str).map(str::to_string),
+            raw,
+        })
+    }
+}
+
+/// Finds the `Certificates` arrays anywhere in the PKCS#7 dump.
+fn collect_certificates(v: &Value, out: &mut Vec<Certificate>) {
+    match *v {
+        Value::Object(ref map) => {
+            for (key, value) in map {
+                match (key.eq_ignore_ascii_case("certificates"), value) {
+                    (true, Value::Array(certs)) => {
+                        out.extend(certs.iter().filter_map(certificate));
+                    }
+                    _ => collect_certificates(value, out),
+                }
+            }
+        }
+        Value::Array(ref items) => items.iter().for_each(|i| collect_certificates(i, out)),
+        _ => {}
+    }
+}
+
+fn certificate(v: &Value) -> Option<Certificate> {
+    let cert = v.as_object()?;
+    let field = |name: &str| {
+        cert.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    };
+    let validity = field("validity");
+    let date = |name: &str| {
+        validity
+            .and_then(|v| v.get(name))
+            .map(flatten)
+            .unwrap_or_default()
+    };
+    Some(Certificate {
+        subject: field("subject").map(flatten).unwrap_or_default(),
+        issuer: field("issuer").map(flatten).unwrap_or_default(),
+        serial: field("serialnumber").map(flatten).unwrap_or_default(),
+        not_before: date("NotBefore"),
+        not_after: date("NotAfter"),
+    })
+}
+
+fn flatten(v: &Value) -> String {
+    match *v {
+        Value::String(ref s) => s.clone(),
+        Value::Object(ref map) => map
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, flatten(v)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Value::Array(ref items) => items.iter().map(flatten).collect::<Vec<_>>().join(", "),
+        Value::Null => String::new(),
+        ref other => other.to_string(),
+    }
+}
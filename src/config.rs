@@ -0,0 +1,157 @@
+//! Reading and temporarily changing r2's configuration variables (`e`).
+
+use crate::error::Error;
+use crate::r2pipe::{r2_error_message, R2Pipe};
+
+use std::ops::{Deref, DerefMut};
+
+/// Restores the previous value of a configuration variable when dropped.
+///
+/// Created by `R2Pipe::config_guard()`, it derefs to the underlying pipe so
+/// commands can be issued while the temporary value is active.
+pub struct ConfigGuard<'a> {
+    pipe: &'a mut R2Pipe,
+    key: String,
+    previous: String,
+}
+
+impl<'a> ConfigGuard<'a> {
+    /// Value that will be restored on drop.
+    pub fn previous(&self) -> &str {
+        &self.previous
+    }
+}
+
+impl<'a> Deref for ConfigGuard<'a> {
+    type Target = R2Pipe;
+
+    fn deref(&self) -> &R2Pipe {
+        self.pipe
+    }
+}
+
+impl<'a> DerefMut for ConfigGuard<'a> {
+    fn deref_mut(&mut self) -> &mut R2Pipe {
+        self.pipe
+    }
+}
+
+impl<'a> Drop for ConfigGuard<'a> {
+    fn drop(&mut self) {
+        let _ = self.pipe.set_config(&self.key, &self.previous);
+    }
+}
+
+impl R2Pipe {
+    /// Returns the value of the configuration variable `key` (`e key`).
+    ///
+    /// Fails with `Error::R2Error` when r2 has no such variable, which it
+    /// tells by printing nothing at all, not even the newline ending an
+    /// empty value.
+    pub fn get_config(&mut self, key: &str) -> Result<String, Error> {
+        config_arg("key", key)?;
+        let res = self.cmd(&format!("e {}", key))?;
+        if res.is_empty() {
+            let e = Error::R2Error(format!("Unknown config key: {}", key));
+            return Err(self.with_session(e));
+        }
+        if let Some(e) = r2_error_message(&res) {
+            return Err(self.with_session(Error::R2Error(e.to_string())));
+        }
+        Ok(res.trim_end().to_string())
+    }
+
+    /// Sets the configuration variable `key` to `value` (`e key=value`).
+    ///
+    /// `key` and `value` can't hold the characters r2 would take as the
+    /// end of the command, e.g. `;` or `|`.
+    pub fn set_config(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        config_arg("key", key)?;
+        config_arg("value", value)?;
+        let res = self.cmd(&format!("e {}={}", key, value))?;
+        match r2_error_message(&res) {
+            Some(e) => Err(self.with_session(Error::R2Error(e.to_string()))),
+            None => Ok(()),
+        }
+    }
+
+    /// Sets `key` to `value` until the returned guard goes out of scope.
+    pub fn config_guard(&mut self, key: &str, value: &str) -> Result<ConfigGuard<'_>, Error> {
+        let previous = self.get_config(key)?;
+        // Checked now, restoring it on drop can't fail loudly.
+        config_arg("value", &previous)?;
+        self.set_config(key, value)?;
+        Ok(ConfigGuard {
+            pipe: self,
+            key: key.to_string(),
+            previous,
+        })
+    }
+
+    /// Runs `f` with `key` set to `value`, then restores the previous value.
+    ///
+    /// The value is restored even if `f` returns an error or panics.
+    pub fn with_config<T, F>(&mut self, key: &str, value: &str, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut R2Pipe) -> Result<T, Error>,
+    {
+        let mut guard = self.config_guard(key, value)?;
+        f(&mut guard)
+    }
+}
+
+/// Rejects a `what` of `e` that would end or redirect the command.
+fn config_arg(what: &str, arg: &str) -> Result<(), Error> {
+    let empty_key = what == "key" && arg.trim().is_empty();
+    if empty_key || arg.contains([';', '\n', '@', '|', '~', '`']) {
+        return Err(Error::InvalidCommand(format!(
+            "Unusable config {}: {:?}",
+            what, arg
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    #[test]
+    fn guard_restores_previous_value() {
+        let mock = MockR2Pipe::new()
+            .expect("e asm.bits", "64\n")
+            .expect("e asm.bits=32", "")
+            .expect("e asm.bits=64", "")
+            .expect("?e done", "done\n");
+        let mut r2 = R2Pipe::from_commands(mock);
+        {
+            let guard = r2.config_guard("asm.bits", "32").unwrap();
+            assert_eq!(guard.previous(), "64");
+        }
+        // Only answered once the value was restored.
+        assert_eq!(r2.cmd("?e done").unwrap(), "done\n");
+    }
+
+    #[test]
+    fn unknown_key_is_an_error() {
+        let mut r2 =
+            R2Pipe::from_commands(MockR2Pipe::new().on("e nope", "").on("e asm.cpu", "\n"));
+        let e = r2.get_config("nope").unwrap_err();
+        assert!(matches!(e.root(), Error::R2Error(_)));
+        assert_eq!(e.session(), Some(r2.session_id()));
+        assert_eq!(r2.get_config("asm.cpu").unwrap(), "");
+        assert!(r2.config_guard("nope", "1").is_err());
+    }
+
+    #[test]
+    fn rejects_chained_commands() {
+        let mut r2 = R2Pipe::from_commands(MockR2Pipe::new().lenient());
+        for (key, value) in [("asm.bits", "32;!id"), ("asm.bits;!id", "32"), ("a", "b|c")] {
+            let e = r2.set_config(key, value).unwrap_err();
+            assert!(matches!(e, Error::InvalidCommand(_)));
+        }
+        assert!(r2.get_config("scr.color @ 0").is_err());
+        assert!(r2.get_config("").is_err());
+    }
+}
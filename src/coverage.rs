@@ -0,0 +1,136 @@
+//! Collecting code coverage from r2's execution traces.
+//!
+//! The debugger records the executed addresses when `dbg.trace` is set
+//! (`dt`), ESIL emulation when a trace session is running (`aets`).
+//! `R2Pipe::coverage()` maps those addresses to basic blocks, and
+//! `Coverage::write_drcov()` exports the result for tools reading drcov
+//! files (lighthouse, bncov...).
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, BasicBlock, TracePoint};
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// A basic block and how many times it was entered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockHit {
+    pub block: BasicBlock,
+    pub hits: u64,
+}
+
+/// The basic blocks covered by a trace.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    /// Sorted by address.
+    pub blocks: Vec<BlockHit>,
+    /// Traced addresses outside of any known function.
+    pub unmapped: Vec<Addr>,
+}
+
+impl Coverage {
+    /// Writes the blocks in drcov format, as a single module loaded at
+    /// `base` and ending at `end`.
+    pub fn write_drcov<W: Write>(
+        &self,
+        mut w: W,
+        path: &str,
+        base: Addr,
+        end: Addr,
+    ) -> io::Result<()> {
+        let blocks: Vec<&BlockHit> = self
+            .blocks
+            .iter()
+            .filter(|b| b.block.addr >= base && b.block.addr < end)
+            .collect();
+        writeln!(w, "DRCOV VERSION: 2")?;
+        writeln!(w, "DRCOV FLAVOR: r2pipe")?;
+        writeln!(w, "Module Table: version 2, count 1")?;
+        writeln!(
+            w,
+            "Columns: id, base, end, entry, checksum, timestamp, path"
+        )?;
+        writeln!(
+            w,
+            " 0, {:#018x}, {:#018x}, {:#018x}, 0x00000000, 0x00000000, {}",
+            base.0, end.0, 0, path
+        )?;
+        writeln!(w, "BB Table: {} bbs", blocks.len())?;
+        for hit in blocks {
+            // struct { u32 start; u16 size; u16 mod_id; }, little endian.
+            let start = (hit.block.addr.0 - base.0) as u32;
+            let size = hit.block.size.min(u64::from(u16::MAX)) as u16;
+            w.write_all(&start.to_le_bytes())?;
+            w.write_all(&size.to_le_bytes())?;
+            w.write_all(&0u16.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl R2Pipe {
+    /// The addresses recorded by the debugger trace (`dtj`).
+    ///
+    /// Set `dbg.trace` to `true` before stepping or continuing to record
+    /// them.
+    pub fn trace_points(&mut self) -> Result<Vec<TracePoint>, Error> {
+        self.cmd_deserialize("dtj")
+    }
+
+    /// Clears the debugger trace (`dt-`).
+    pub fn clear_trace(&mut self) -> Result<(), Error> {
+        self.cmd_ok("dt-")
+    }
+
+    /// Starts recording the ESIL emulation (`aets+`).
+    pub fn start_esil_trace(&mut self) -> Result<(), Error> {
+        self.cmd_ok("aets+")
+    }
+
+    /// Stops recording the ESIL emulation (`aets-`).
+    pub fn stop_esil_trace(&mut self) -> Result<(), Error> {
+        self.cmd_ok("aets-")
+    }
+
+    /// Maps the executed `points` to the basic blocks of the analyzed
+    /// functions (`afbj`).
+    ///
+    /// Functions must have been analyzed first, addresses in no function
+    /// end up in `Coverage::unmapped`.
+    pub fn coverage(&mut self, points: &[TracePoint]) -> Result<Coverage, Error> {
+        let mut known: Vec<BasicBlock> = Vec::new();
+        let mut hits: BTreeMap<Addr, BlockHit> = BTreeMap::new();
+        let mut unmapped = Vec::new();
+        for point in points {
+            if !known.iter().any(|b| b.contains(point.addr)) {
+                let blocks: Result<Vec<BasicBlock>, Error> =
+                    self.cmd_deserialize(&format!("afbj @ {}", point.addr));
+                match blocks {
+                    Ok(blocks) => known.extend(blocks),
+                    // r2 prints nothing outside of functions.
+                    Err(ref e)
+                        if matches!(e.root(), Error::EmptyResponse(_) | Error::Json { .. }) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            match known.iter().find(|b| b.contains(point.addr)) {
+                Some(block) => {
+                    let hit = hits.entry(block.addr).or_insert_with(|| BlockHit {
+                        block: block.clone(),
+                        hits: 0,
+                    });
+                    // Count entries in the block, not its instructions.
+                    if point.addr == block.addr {
+                        hit.hits += point.times.max(1);
+                    }
+                }
+                None => unmapped.push(point.addr),
+            }
+        }
+        Ok(Coverage {
+            blocks: hits.into_values().collect(),
+            unmapped,
+        })
+    }
+}
@@ -0,0 +1,288 @@
+//! Driving r2's debugger (`d*` commands) on sessions opened with `-d`.
+
+use crate::error::Error;
+use crate::r2pipe::{file_arg, R2Pipe};
+use crate::structs::{parse_r2_number, Addr, Breakpoint, Frame, HeapChunk};
+
+use serde_derive::Deserialize;
+
+/// Accesses triggering a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn flag(self) -> &'static str {
+        match self {
+            Access::Read => "r",
+            Access::Write => "w",
+            Access::ReadWrite => "rw",
+        }
+    }
+}
+
+/// A system call made by the debugged process, as printed by `dcs*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallEvent {
+    /// Address of the syscall instruction.
+    pub addr: Addr,
+    pub number: u64,
+    /// Empty when r2 doesn't know the number for this OS.
+    pub name: String,
+    /// The first arguments, as r2 prints them.
+    pub args: Vec<Addr>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct HeapChunks {
+    chunks: Vec<HeapChunk>,
+}
+
+/// A malloc arena, as listed by `dmha`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapArena {
+    /// `main_arena`, or `thread` for the arenas of other threads.
+    pub name: String,
+    pub addr: Addr,
+}
+
+/// Architectures whose debug registers r2 can program.
+const HW_ARCHS: &[&str] = &["x86", "arm"];
+
+impl R2Pipe {
+    /// Lists the breakpoints and watchpoints (`dbj`).
+    pub fn breakpoints(&mut self) -> Result<Vec<Breakpoint>, Error> {
+        self.cmd_deserialize("dbj")
+    }
+
+    /// Sets a software breakpoint at `addr` (`db`).
+    pub fn add_breakpoint<A: Into<Addr>>(&mut self, addr: A) -> Result<(), Error> {
+        self.cmd_ok(&format!("db {}", addr.into()))
+    }
+
+    /// Removes the breakpoint or watchpoint at `addr` (`db-`).
+    pub fn remove_breakpoint<A: Into<Addr>>(&mut self, addr: A) -> Result<(), Error> {
+        self.cmd_ok(&format!("db- {}", addr.into()))
+    }
+
+    /// Whether the debug registers of the target can be used, i.e. the
+    /// native debugger runs a target r2 knows the debug registers of.
+    pub fn hw_breakpoints_supported(&mut self) -> Result<bool, Error> {
+        let backend = self.get_config("dbg.backend")?;
+        let arch = self.get_config("asm.arch")?;
+        Ok(backend == "native" && HW_ARCHS.contains(&arch.as_str()))
+    }
+
+    /// Sets a hardware breakpoint at `addr` (`dbH`).
+    ///
+    /// Fails with `Error::Unsupported` where `hw_breakpoints_supported()`
+    /// is false. Targets only have a few debug registers (4 on x86), r2
+    /// errors out once they are all in use.
+    pub fn add_hw_breakpoint<A: Into<Addr>>(&mut self, addr: A) -> Result<(), Error> {
+        self.require_hw()?;
+        self.cmd_ok(&format!("dbH {}", addr.into()))
+    }
+
+    /// Watches `size` bytes at `addr` for `access` (`dbw`).
+    ///
+    /// Watchpoints use the debug registers, with the same limits as
+    /// `add_hw_breakpoint()`. `size` must be 1, 2, 4 or 8, and `addr`
+    /// aligned on it.
+    pub fn add_watchpoint<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        size: u64,
+        access: Access,
+    ) -> Result<(), Error> {
+        let addr = addr.into();
+        if ![1, 2, 4, 8].contains(&size) || addr.0 % size != 0 {
+            return Err(Error::InvalidCommand(format!(
+                "Can't watch {} bytes at {}",
+                size, addr
+            )));
+        }
+        self.require_hw()?;
+        self.with_config("dbg.bpsize", &size.to_string(), |r2| {
+            r2.cmd_ok(&format!("dbw {} {}", addr, access.flag()))
+        })
+    }
+
+    /// Reads `len` bytes of the debugged process' memory at `addr`.
+    ///
+    /// Unlike `read_bytes()`, fails on sessions not debugging anything, and
+    /// bypasses `io.cache`, which would return patched bytes rather than
+    /// what the process sees.
+    pub fn dbg_read<A: Into<Addr>>(&mut self, addr: A, len: usize) -> Result<Vec<u8>, Error> {
+        let addr = addr.into();
+        self.require_debug()?;
+        self.with_config("io.cache", "false", |r2| r2.read_bytes(addr, len))
+    }
+
+    /// Writes `data` to the debugged process' memory at `addr`.
+    ///
+    /// Unlike `write_bytes()`, fails on sessions not debugging anything,
+    /// and bypasses `io.cache`, which would keep the write in r2 instead of
+    /// the process.
+    pub fn dbg_write<A: Into<Addr>>(&mut self, addr: A, data: &[u8]) -> Result<(), Error> {
+        let addr = addr.into();
+        self.require_debug()?;
+        self.with_config("io.cache", "false", |r2| r2.write_bytes(addr, data))
+    }
+
+    /// The call stack of the current thread, innermost frame first
+    /// (`dbtj`).
+    pub fn backtrace(&mut self) -> Result<Vec<Frame>, Error> {
+        self.require_debug()?;
+        self.cmd_deserialize("dbtj")
+    }
+
+    /// Starts recording a trace session (`dts+`), so the steps taken
+    /// while tracing syscalls can be looked at afterwards with `dts`.
+    pub fn start_syscall_trace(&mut self) -> Result<(), Error> {
+        self.require_debug()?;
+        self.cmd_ok("dts+")
+    }
+
+    /// Stops the trace session started by `start_syscall_trace()` (`dts-`).
+    pub fn stop_syscall_trace(&mut self) -> Result<(), Error> {
+        self.cmd_ok("dts-")
+    }
+
+    /// Lets the process run, strace-like, until it exits or hits a
+    /// breakpoint, and returns the syscalls it made (`dcs*`).
+    pub fn syscall_events(&mut self) -> Result<Vec<SyscallEvent>, Error> {
+        self.require_debug()?;
+        let res = self.cmd("dcs*")?;
+        Ok(res.lines().filter_map(parse_syscall_line).collect())
+    }
+
+    /// The chunks of the heap (`dmhj`).
+    ///
+    /// Only glibc's malloc is supported (`dbg.malloc=glibc`, the default on
+    /// Linux), the process must have initialized its heap.
+    pub fn heap_chunks(&mut self) -> Result<Vec<HeapChunk>, Error> {
+        self.require_glibc_heap()?;
+        let res: HeapChunks = self.cmd_deserialize("dmhj")?;
+        Ok(res.chunks)
+    }
+
+    /// The malloc arenas (`dmha`), the main arena first.
+    pub fn heap_arenas(&mut self) -> Result<Vec<HeapArena>, Error> {
+        self.require_glibc_heap()?;
+        let res = self.cmd("dmha")?;
+        Ok(res.lines().filter_map(parse_arena_line).collect())
+    }
+
+    /// Writes a core file of the debugged process to `path` (`dg`),
+    /// replacing any file there.
+    ///
+    /// `path` is on the machine running r2. When that's this one, fails if
+    /// no core file was written, r2 only complains about it on stderr.
+    pub fn dump_core(&mut self, path: &str) -> Result<(), Error> {
+        file_arg(path)?;
+        self.require_debug()?;
+        self.dump_to(path, &format!("dg {}", path), |len| len > 0)
+    }
+
+    /// Writes `len` bytes of the debugged process' memory at `addr` to
+    /// `path` (`wtf`), replacing any file there.
+    ///
+    /// As with `dump_core()`, the file is checked when r2 runs on this
+    /// machine: fails unless exactly `len` bytes were written, e.g. when
+    /// part of the range isn't mapped.
+    pub fn dump_memory_range<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        len: u64,
+        path: &str,
+    ) -> Result<(), Error> {
+        let addr = addr.into();
+        file_arg(path)?;
+        if len == 0 {
+            return Err(Error::InvalidCommand(format!(
+                "Nothing to dump at {}",
+                addr
+            )));
+        }
+        self.require_debug()?;
+        let cmd = format!("wtf {} {} @ {}", path, len, addr);
+        self.with_config("io.cache", "false", |r2| {
+            r2.dump_to(path, &cmd, |written| written == len)
+        })
+    }
+
+    /// Whether r2 is debugging a process (`cfg.debug`).
+    pub fn is_debugging(&mut self) -> Result<bool, Error> {
+        Ok(self.get_config("cfg.debug")? == "true")
+    }
+
+    fn require_debug(&mut self) -> Result<(), Error> {
+        if self.is_debugging()? {
+            Ok(())
+        } else {
+            Err(Error::Unsupported(
+                "Not a debug session, open the target with -d".to_string(),
+            ))
+        }
+    }
+
+    fn require_glibc_heap(&mut self) -> Result<(), Error> {
+        self.require_debug()?;
+        match self.get_config("dbg.malloc")?.as_str() {
+            "glibc" => Ok(()),
+            other => Err(Error::Unsupported(format!(
+                "No typed heap listing for the {} allocator",
+                other
+            ))),
+        }
+    }
+
+    fn require_hw(&mut self) -> Result<(), Error> {
+        if self.hw_breakpoints_supported()? {
+            return Ok(());
+        }
+        let arch = self.get_config("asm.arch")?;
+        Err(Error::Unsupported(format!(
+            "No hardware breakpoints with {} on {}",
+            self.get_config("dbg.backend")?,
+            arch
+        )))
+    }
+}
+
+/// Parses `--> SN 0x00401000 syscall 1 write (0x1 0x402000 0xe)`.
+fn parse_syscall_line(line: &str) -> Option<SyscallEvent> {
+    let line = line.trim().strip_prefix("-->")?;
+    let (head, args) = match line.find('(') {
+        Some(i) => (&line[..i], line[i + 1..].trim_end_matches(')')),
+        None => (line, ""),
+    };
+    let mut head = head.split_whitespace().skip_while(|t| !t.starts_with("0x"));
+    let addr = head.next()?.parse().ok()?;
+    if head.next()? != "syscall" {
+        return None;
+    }
+    let number = parse_r2_number(head.next()?).ok()?;
+    Some(SyscallEvent {
+        addr,
+        number,
+        name: head.next().unwrap_or("").to_string(),
+        args: args
+            .split_whitespace()
+            .filter_map(|a| a.parse().ok())
+            .collect(),
+    })
+}
+
+/// Parses `main_arena @ 0x7ffff7e19c80` or `thread arena @ 0x7ffff0000030`.
+fn parse_arena_line(line: &str) -> Option<HeapArena> {
+    let (name, addr) = line.split_once('@')?;
+    Some(HeapArena {
+        name: name.split_whitespace().next()?.to_string(),
+        addr: addr.split_whitespace().next()?.parse().ok()?,
+    })
+}
@@ -0,0 +1,164 @@
+//! Source line information from the debug info of the binary: DWARF,
+//! or a PDB loaded next to a PE.
+
+use crate::error::Error;
+use crate::r2pipe::{file_arg, R2Pipe};
+use crate::structs::{Addr, Instruction, LineInfo};
+
+/// The line table of the binary, sorted by address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    pub lines: Vec<LineInfo>,
+}
+
+/// An instruction and the source line it was compiled from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceInstruction {
+    pub ins: Instruction,
+    /// `None` before the first entry of the line table.
+    pub source: Option<LineInfo>,
+}
+
+impl DebugInfo {
+    /// The line `addr` was compiled from: the last entry at or before it.
+    pub fn line_at(&self, addr: Addr) -> Option<&LineInfo> {
+        match self.lines.binary_search_by(|l| l.addr.cmp(&addr)) {
+            Ok(i) => self.lines.get(i),
+            Err(0) => None,
+            Err(i) => self.lines.get(i - 1),
+        }
+    }
+
+    /// The addresses compiled from `line` of `file`, which matches the end
+    /// of the recorded paths (`main.c` matches `/src/main.c`).
+    pub fn addrs_of(&self, file: &str, line: u64) -> Vec<Addr> {
+        self.lines
+            .iter()
+            .filter(|l| l.line == line && path_matches(&l.file, file))
+            .map(|l| l.addr)
+            .collect()
+    }
+}
+
+impl R2Pipe {
+    /// The source lines of the debug info (`idj`, or the `CL` commands of
+    /// `id*` for versions without JSON).
+    ///
+    /// Empty for binaries without line info. DWARF is read from the
+    /// binary, PDBs have to be loaded first with `load_pdb()` or
+    /// `download_pdb()`.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let info = r2.debug_info()?;
+    /// if let Some(line) = info.line_at(0x4011d6u64.into()) {
+    ///     println!("{}:{}", line.file, line.line);
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn debug_info(&mut self) -> Result<DebugInfo, Error> {
+        let mut lines: Vec<LineInfo> = match self.cmd_deserialize("idj") {
+            Ok(lines) => lines,
+            Err(ref e) if matches!(e.root(), Error::EmptyResponse(_) | Error::Json { .. }) => {
+                let res = self.cmd("id*")?;
+                res.lines().filter_map(parse_cl_line).collect()
+            }
+            Err(e) => return Err(e),
+        };
+        lines.sort_by_key(|l| l.addr);
+        Ok(DebugInfo { lines })
+    }
+
+    /// Disassembles `n` instructions at `addr` (`pdj`), each with the
+    /// line `DebugInfo::line_at()` gives for it.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let mut last = None;
+    /// for si in r2.disassemble_with_source(0x4011d6u64, 32)? {
+    ///     match si.source {
+    ///         Some(ref line) if si.source != last => println!("; {}:{}", line.file, line.line),
+    ///         _ => {}
+    ///     }
+    ///     println!("{}", si.ins.disasm);
+    ///     last = si.source;
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn disassemble_with_source<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        n: u64,
+    ) -> Result<Vec<SourceInstruction>, Error> {
+        let addr = addr.into();
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let info = self.debug_info()?;
+        let instructions: Vec<Instruction> =
+            self.cmd_deserialize(&format!("pdj {} @ {}", n, addr))?;
+        Ok(instructions
+            .into_iter()
+            .map(|ins| SourceInstruction {
+                source: info.line_at(ins.offset).cloned(),
+                ins,
+            })
+            .collect())
+    }
+
+    /// Loads the PDB at `path` (`idp`), for the PE opened.
+    ///
+    /// Fails when r2 can't parse it (`idpi` prints nothing).
+    pub fn load_pdb(&mut self, path: &str) -> Result<(), Error> {
+        file_arg(path)?;
+        if self.cmd(&format!("idpi {}", path))?.trim().is_empty() {
+            return Err(Error::Other(format!("Not a PDB r2 can read: {}", path)));
+        }
+        self.cmd_ok(&format!("idp {}", path))
+    }
+
+    /// Downloads the PDB of the PE opened from `pdb.server` into
+    /// `pdb.symstore` (`idpd`), then loads it (`idp`).
+    ///
+    /// Fails with `Error::Unsupported` when no server is configured or the
+    /// binary doesn't name a PDB.
+    pub fn download_pdb(&mut self) -> Result<(), Error> {
+        if self.get_config("pdb.server")?.trim().is_empty() {
+            return Err(Error::Unsupported("No pdb.server configured".to_string()));
+        }
+        let dbg_file = self.bin_info()?.bin.dbg_file;
+        if !dbg_file.to_ascii_lowercase().ends_with(".pdb") {
+            return Err(Error::Unsupported(
+                "The binary doesn't name a PDB".to_string(),
+            ));
+        }
+        let res = self.cmd("idpd")?;
+        if res.to_ascii_lowercase().contains("fail") {
+            return Err(Error::Other(format!(
+                "Downloading {} failed: {}",
+                dbg_file,
+                res.trim()
+            )));
+        }
+        self.cmd_ok("idp")
+    }
+}
+
+/// Whether `path` is `file`, or ends with it after a separator.
+fn path_matches(path: &str, file: &str) -> bool {
+    let path = path.replace('\\', "/");
+    let file = file.replace('\\', "/");
+    path == file || path.ends_with(&format!("/{}", file.trim_start_matches('/')))
+}
+
+/// Parses `CL /src/main.c:12 0x4011d6`.
+fn parse_cl_line(line: &str) -> Option<LineInfo> {
+    let mut parts = line.trim().strip_prefix("CL ")?.split_whitespace();
+    let (file, line) = parts.next()?.rsplit_once(':')?;
+    Some(LineInfo {
+        addr: parts.next()?.parse().ok()?,
+        file: file.to_string(),
+        line: line.parse().ok()?,
+        column: 0,
+    })
+}
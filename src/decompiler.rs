@@ -0,0 +1,255 @@
+//! Access to the decompilers available to r2.
+//!
+//! r2ghidra and r2dec are plugins and may not be installed, so `decompile()`
+//! can fall back to the builtin pseudo-decompiler (`pdc`).
+
+use crate::error::Error;
+use crate::r2pipe::{parse_json, R2Pipe};
+use crate::structs::Addr;
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::ops::Range;
+
+/// Decompiler used to produce source-like output for a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecompilerBackend {
+    /// r2ghidra plugin (`pdg`).
+    Ghidra,
+    /// r2dec plugin (`pddj`).
+    R2Dec,
+    /// Builtin pseudo-decompiler (`pdc`), always available.
+    Pdc,
+    /// Try `Ghidra`, then `R2Dec`, then `Pdc`, returning the first output.
+    #[default]
+    Auto,
+}
+
+/// What a range of the decompiled code stands for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationKind {
+    /// Code generated from the instructions at this address.
+    Offset(Addr),
+    /// Highlighting class: `keyword`, `comment`, `datatype`...
+    Syntax(String),
+    FunctionName {
+        name: String,
+        addr: Addr,
+    },
+    GlobalVariable(Addr),
+    /// A constant that is also the address of something.
+    ConstantVariable(Addr),
+    LocalVariable(String),
+    FunctionParameter(String),
+    Other(String),
+}
+
+/// A range of `AnnotatedCode::code`, in bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub range: Range<usize>,
+    pub kind: AnnotationKind,
+}
+
+impl Annotation {
+    /// The address this range leads to, if any.
+    pub fn addr(&self) -> Option<Addr> {
+        match self.kind {
+            AnnotationKind::Offset(addr)
+            | AnnotationKind::FunctionName { addr, .. }
+            | AnnotationKind::GlobalVariable(addr)
+            | AnnotationKind::ConstantVariable(addr) => Some(addr),
+            _ => None,
+        }
+    }
+}
+
+/// Decompiled code with the annotations r2ghidra attaches to it (`pdgj`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnnotatedCode {
+    pub code: String,
+    /// In r2ghidra's order, ranges can overlap.
+    pub annotations: Vec<Annotation>,
+}
+
+impl AnnotatedCode {
+    /// The code covered by `annotation`.
+    pub fn text(&self, annotation: &Annotation) -> &str {
+        self.code.get(annotation.range.clone()).unwrap_or("")
+    }
+
+    /// The instructions the code at byte `pos` was generated from, from
+    /// the innermost offset annotation covering it.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let code = r2.decompile_annotated(0x1000u64)?;
+    /// let clicked = code.code.find("return").unwrap_or(0);
+    /// if let Some(addr) = code.addr_at(clicked) {
+    ///     r2.cmd(&format!("s {}", addr))?;
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn addr_at(&self, pos: usize) -> Option<Addr> {
+        self.annotations
+            .iter()
+            .filter(|a| matches!(a.kind, AnnotationKind::Offset(_)) && a.range.contains(&pos))
+            .min_by_key(|a| a.range.len())
+            .and_then(Annotation::addr)
+    }
+
+    /// The annotations leading to an address, in code order.
+    pub fn links(&self) -> Vec<(&Annotation, Addr)> {
+        let mut links: Vec<_> = self
+            .annotations
+            .iter()
+            .filter_map(|a| a.addr().map(|addr| (a, addr)))
+            .collect();
+        links.sort_by_key(|(a, _)| a.range.start);
+        links
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RawCode {
+    code: String,
+    annotations: Vec<RawAnnotation>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RawAnnotation {
+    start: usize,
+    end: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    offset: Addr,
+    name: String,
+    syntax_highlight: String,
+}
+
+impl From<RawAnnotation> for Annotation {
+    fn from(raw: RawAnnotation) -> Annotation {
+        let kind = match raw.kind.as_str() {
+            "offset" => AnnotationKind::Offset(raw.offset),
+            "syntax_highlight" => AnnotationKind::Syntax(raw.syntax_highlight),
+            "function_name" => AnnotationKind::FunctionName {
+                name: raw.name,
+                addr: raw.offset,
+            },
+            "global_variable" => AnnotationKind::GlobalVariable(raw.offset),
+            "constant_variable" => AnnotationKind::ConstantVariable(raw.offset),
+            "local_variable" => AnnotationKind::LocalVariable(raw.name),
+            "function_parameter" => AnnotationKind::FunctionParameter(raw.name),
+            _ => AnnotationKind::Other(raw.kind),
+        };
+        Annotation {
+            range: raw.start..raw.end,
+            kind,
+        }
+    }
+}
+
+impl R2Pipe {
+    /// Decompiles the function containing `addr` with the requested backend.
+    ///
+    /// A backend whose plugin is not installed prints nothing or r2's usage
+    /// of `pd`, which is reported as an error (`Error::EmptyResponse` and
+    /// `Error::R2Error`), so that `DecompilerBackend::Auto` moves on to the
+    /// next one.
+    pub fn decompile<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        backend: DecompilerBackend,
+    ) -> Result<String, Error> {
+        let addr = addr.into();
+        match backend {
+            DecompilerBackend::Ghidra => self.decompiler_output(addr, "pdg"),
+            DecompilerBackend::R2Dec => {
+                let res = self.decompiler_output(addr, "pddj")?;
+                let json: Value =
+                    parse_json("pddj", res.into_bytes()).map_err(|e| self.with_session(e))?;
+                r2dec_lines(&json)
+                    .ok_or_else(|| self.with_session(Error::UnexpectedOutput("pddj".to_string())))
+            }
+            DecompilerBackend::Pdc => self.decompiler_output(addr, "pdc"),
+            DecompilerBackend::Auto => {
+                let backends = [
+                    DecompilerBackend::Ghidra,
+                    DecompilerBackend::R2Dec,
+                    DecompilerBackend::Pdc,
+                ];
+                let mut last_err = Error::Other(String::new());
+                for b in backends.iter() {
+                    match self.decompile(addr, *b) {
+                        Ok(res) => return Ok(res),
+                        Err(e) => last_err = e,
+                    }
+                }
+                Err(last_err)
+            }
+        }
+    }
+
+    /// Decompiles the function containing `addr` with r2ghidra, keeping
+    /// the annotations linking the code to addresses (`pdgj`).
+    pub fn decompile_annotated<A: Into<Addr>>(&mut self, addr: A) -> Result<AnnotatedCode, Error> {
+        let res = self.decompiler_output(addr.into(), "pdgj")?;
+        let raw: RawCode =
+            parse_json("pdgj", res.into_bytes()).map_err(|e| self.with_session(e))?;
+        Ok(AnnotatedCode {
+            code: raw.code,
+            annotations: raw.annotations.into_iter().map(Annotation::from).collect(),
+        })
+    }
+
+    /// The output of `cmd` at `addr`, failing on r2's error messages as
+    /// `cmd_checked()` does and on an empty output.
+    fn decompiler_output(&mut self, addr: Addr, cmd: &str) -> Result<String, Error> {
+        let res = self.cmd_checked(&format!("{} @ {}", cmd, addr))?;
+        if res.trim().is_empty() {
+            return Err(self.with_session(Error::EmptyResponse(cmd.to_string())));
+        }
+        Ok(res)
+    }
+}
+
+/// Joins the `lines` array of `pddj` into plain source text.
+fn r2dec_lines(json: &Value) -> Option<String> {
+    let lines = json["lines"].as_array()?;
+    let text = lines
+        .iter()
+        .filter_map(|l| l["str"].as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    #[test]
+    fn auto_skips_missing_plugins() {
+        let mock = MockR2Pipe::new()
+            .on("pdg @ 0x1000", "Usage: pd[...]")
+            .on("pddj @ 0x1000", "")
+            .on("pdc @ 0x1000", "int main() {}");
+        let mut r2 = R2Pipe::from_commands(mock);
+        let code = r2.decompile(0x1000u64, DecompilerBackend::Auto).unwrap();
+        assert_eq!(code, "int main() {}");
+        let e = r2
+            .decompile(0x1000u64, DecompilerBackend::R2Dec)
+            .unwrap_err();
+        assert!(e.session().is_some() && e.is_empty_response());
+        match r2
+            .decompile(0x1000u64, DecompilerBackend::Ghidra)
+            .map_err(Error::into_root)
+        {
+            Err(Error::R2Error(_)) => {}
+            res => panic!("{:?}", res),
+        }
+    }
+}
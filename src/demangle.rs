@@ -0,0 +1,57 @@
+//! Demangling any string through r2 (`iD`), not only the symbols of the
+//! opened binary.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+
+use std::fmt;
+
+/// Mangling scheme of a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    /// Itanium C++ ABI, as GCC and Clang do.
+    Cpp,
+    Rust,
+    Swift,
+    ObjC,
+    /// Microsoft Visual C++.
+    Msvc,
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Lang::Cpp => "c++",
+            Lang::Rust => "rust",
+            Lang::Swift => "swift",
+            Lang::ObjC => "objc",
+            Lang::Msvc => "msvc",
+        })
+    }
+}
+
+impl R2Pipe {
+    /// Demangles `name` as `lang` (`iD`), or `None` when r2 can't.
+    ///
+    /// The command is quoted like `eval()`'s, so `@` or `$` in `name` reach
+    /// the demangler.
+    ///
+    /// ```no_run
+    /// use r2pipe::Lang;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let name = r2.demangle("_ZN3foo3barEv", Lang::Cpp)?;
+    /// assert_eq!(name.as_deref(), Some("foo::bar()"));
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn demangle(&mut self, name: &str, lang: Lang) -> Result<Option<String>, Error> {
+        if name.trim().is_empty() || name.contains(['"', '\n', '\0']) {
+            return Err(Error::InvalidCommand(format!("Can't demangle {:?}", name)));
+        }
+        let res = self.cmd(&format!("\"iD {} {}\"", lang, name.trim()))?;
+        match res.trim() {
+            "" => Ok(None),
+            demangled => Ok(Some(demangled.to_string())),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Diffing two binaries with radiff2.
+//!
+//! `R2Diff` runs `radiff2` on two files and parses what it prints: the
+//! changed bytes (`radiff2 -j`) or the functions matched between the two
+//! (`radiff2 -AC`), with their similarity.
+
+use crate::error::Error;
+use crate::structs::Addr;
+
+use serde_derive::Deserialize;
+use std::process::{Command, Output};
+
+/// Bytes that differ between the two files, at the same offset.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ByteChange {
+    pub addr: Addr,
+    /// Hex of the bytes in the first file.
+    pub from: String,
+    /// Hex of the bytes in the second file.
+    pub to: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ByteDiff {
+    changes: Vec<ByteChange>,
+}
+
+/// How a function of the first file relates to the second file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchKind {
+    /// Found in both, identical.
+    Match,
+    /// Found in both, with changes.
+    Unmatch,
+    /// Only in one of the files.
+    New,
+    Other(String),
+}
+
+/// A line of `radiff2 -C`.
+#[derive(Clone, Debug)]
+pub struct FunctionMatch {
+    pub name: String,
+    pub addr: Addr,
+    pub kind: MatchKind,
+    /// 1.0 for identical functions.
+    pub similarity: f64,
+    pub other_name: String,
+    pub other_addr: Addr,
+}
+
+/// Diffs between two files, computed by radiff2.
+///
+/// ```no_run
+/// use r2pipe::R2Diff;
+///
+/// let diff = R2Diff::new("v1.bin", "v2.bin");
+/// for f in diff.functions()? {
+///     if f.similarity < 1.0 {
+///         println!("{} changed ({:.2})", f.name, f.similarity);
+///     }
+/// }
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+pub struct R2Diff {
+    exepath: String,
+    a: String,
+    b: String,
+}
+
+impl R2Diff {
+    pub fn new(a: &str, b: &str) -> R2Diff {
+        R2Diff {
+            exepath: "radiff2".to_string(),
+            a: a.to_string(),
+            b: b.to_string(),
+        }
+    }
+
+    /// Runs another radiff2 executable than the one in `PATH`.
+    pub fn exepath(mut self, exepath: &str) -> R2Diff {
+        self.exepath = exepath.to_string();
+        self
+    }
+
+    /// The byte ranges that differ (`radiff2 -j`).
+    pub fn bytes(&self) -> Result<Vec<ByteChange>, Error> {
+        let out = self.run(&["-j"])?;
+        if out.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let diff: ByteDiff = serde_json::from_str(&out)?;
+        Ok(diff.changes)
+    }
+
+    /// The functions of both files, matched (`radiff2 -AC`).
+    ///
+    /// Both files are analyzed first, which can take a while on big ones.
+    pub fn functions(&self) -> Result<Vec<FunctionMatch>, Error> {
+        let out = self.run(&["-AC"])?;
+        Ok(out.lines().filter_map(parse_function_line).collect())
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String, Error> {
+        let Output {
+            status,
+            stdout,
+            stderr,
+        } = Command::new(&self.exepath)
+            .args(args)
+            .arg(&self.a)
+            .arg(&self.b)
+            .output()
+            .map_err(|source| Error::Spawn {
+                exepath: self.exepath.clone(),
+                source,
+            })?;
+        // radiff2 exits with 1 when the files differ, so only an empty
+        // output means it failed.
+        if stdout.is_empty() && !status.success() {
+            return Err(Error::ProcessExited {
+                status: Some(status),
+                stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            });
+        }
+        String::from_utf8(stdout).map_err(Error::Utf8)
+    }
+}
+
+/// Parses `name size addr | KIND (similarity) | addr size name`.
+fn parse_function_line(line: &str) -> Option<FunctionMatch> {
+    let mut parts = line.split('|');
+    let (left, middle, right) = (parts.next()?, parts.next()?, parts.next()?);
+    let left: Vec<&str> = left.split_whitespace().collect();
+    let right: Vec<&str> = right.split_whitespace().collect();
+    let mut middle = middle.split_whitespace();
+    let kind = match middle.next()? {
+        "MATCH" => MatchKind::Match,
+        "UNMATCH" => MatchKind::Unmatch,
+        "NEW" => MatchKind::New,
+        other => MatchKind::Other(other.to_string()),
+    };
+    let similarity = middle
+        .next()
+        .map(|s| s.trim_matches(|c| c == '(' || c == ')'))
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0.0);
+    Some(FunctionMatch {
+        name: left.first()?.to_string(),
+        addr: left.last()?.parse().ok()?,
+        kind,
+        similarity,
+        other_name: right.last().map(|s| s.to_string()).unwrap_or_default(),
+        other_addr: right
+            .first()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default(),
+    })
+}
@@ -0,0 +1,122 @@
+//! Disassembling with the style chosen per call.
+//!
+//! The options are set for the command only (`@e:`), so frontends can show
+//! several listings of one session, each styled its own way, without
+//! changing its configuration.
+
+use crate::cmd::Cmd;
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Instruction};
+
+use std::fmt;
+
+/// Syntax of the x86 instructions (`asm.syntax`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AsmSyntax {
+    #[default]
+    Intel,
+    Att,
+}
+
+impl fmt::Display for AsmSyntax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            AsmSyntax::Intel => "intel",
+            AsmSyntax::Att => "att",
+        })
+    }
+}
+
+/// How instructions are written, r2's defaults by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisasmOptions {
+    pub syntax: AsmSyntax,
+    /// Show the bytes of each instruction (`asm.bytes`).
+    pub bytes: bool,
+    /// Write them as pseudocode, e.g. `eax = 0` (`asm.pseudo`).
+    pub pseudo: bool,
+    /// Color them with ANSI escape codes (`scr.color`).
+    pub color: bool,
+}
+
+impl Default for DisasmOptions {
+    fn default() -> DisasmOptions {
+        DisasmOptions {
+            syntax: AsmSyntax::Intel,
+            bytes: true,
+            pseudo: false,
+            color: false,
+        }
+    }
+}
+
+impl DisasmOptions {
+    /// `cmd` set to run with these options.
+    ///
+    /// ```
+    /// use r2pipe::cmd::Cmd;
+    /// use r2pipe::disasm::{AsmSyntax, DisasmOptions};
+    ///
+    /// let opts = DisasmOptions {
+    ///     syntax: AsmSyntax::Att,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(
+    ///     opts.apply(Cmd::new("pd").arg(2)).to_string(),
+    ///     "pd 2 @e:asm.syntax=att,asm.bytes=true,asm.pseudo=false,scr.color=0"
+    /// );
+    /// ```
+    pub fn apply(&self, cmd: Cmd) -> Cmd {
+        cmd.with_temp_config("asm.syntax", self.syntax)
+            .with_temp_config("asm.bytes", self.bytes)
+            .with_temp_config("asm.pseudo", self.pseudo)
+            .with_temp_config("scr.color", if self.color { 1 } else { 0 })
+    }
+}
+
+impl R2Pipe {
+    /// Disassembles `n` instructions at `addr` (`pdj`), written as `opts`
+    /// tell in their `disasm`.
+    pub fn disassemble<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        n: u64,
+        opts: &DisasmOptions,
+    ) -> Result<Vec<Instruction>, Error> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let cmd = opts.apply(Cmd::new("pdj").arg(n).at(addr));
+        self.cmd_deserialize(&cmd.to_string())
+    }
+
+    /// The listing of `n` instructions at `addr` as r2 prints it (`pd`),
+    /// styled with `opts`.
+    ///
+    /// ```no_run
+    /// use r2pipe::disasm::DisasmOptions;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let plain = r2.disassembly_text(0x4000u64, 16, &DisasmOptions::default())?;
+    /// let colored = DisasmOptions {
+    ///     color: true,
+    ///     bytes: false,
+    ///     ..Default::default()
+    /// };
+    /// let pretty = r2.disassembly_text(0x4000u64, 16, &colored)?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn disassembly_text<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        n: u64,
+        opts: &DisasmOptions,
+    ) -> Result<String, Error> {
+        if n == 0 {
+            return Ok(String::new());
+        }
+        let cmd = opts.apply(Cmd::new("pd").arg(n).at(addr));
+        self.cmd(&cmd.to_string())
+    }
+}
@@ -0,0 +1,146 @@
+//! Byte statistics of the opened file, for packer and crypto detection.
+//!
+//! r2's `p=e` and `p=b` draw these as bar charts, with values scaled to
+//! a byte and a layout depending on the version. The bytes are read here
+//! instead (`p8`) and the statistics computed on them.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::Addr;
+
+use std::ops::Range;
+
+/// Bytes read per command, so big files take few round trips.
+const READ_CHUNK: u64 = 1 << 20;
+
+/// Block size of the profile regions are carved from.
+const REGION_BLOCK: u64 = 4096;
+
+/// A run of file offsets whose bytes look packed or encrypted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntropyRegion {
+    pub range: Range<Addr>,
+    /// Mean over the region, in bits per byte.
+    pub entropy: f64,
+}
+
+impl R2Pipe {
+    /// The Shannon entropy, from 0 to 8 bits per byte, of each
+    /// `block_size` bytes of the file.
+    ///
+    /// Addresses are file offsets. The last block is shorter when the file
+    /// size isn't a multiple of `block_size`.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let packed = r2
+    ///     .entropy_profile(4096)?
+    ///     .iter()
+    ///     .filter(|(_, e)| *e > 7.2)
+    ///     .count();
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn entropy_profile(&mut self, block_size: u64) -> Result<Vec<(Addr, f64)>, Error> {
+        if block_size == 0 {
+            return Err(Error::InvalidCommand("Empty entropy blocks".to_string()));
+        }
+        let size = self.eval("$s")?;
+        // Whole blocks per read, so none straddles two chunks.
+        let chunk = (READ_CHUNK / block_size).max(1) * block_size;
+        self.with_config("io.va", "false", |r2| {
+            let mut profile = Vec::new();
+            let mut offset = 0;
+            while offset < size {
+                let len = chunk.min(size - offset);
+                let data = r2.read_bytes(offset, len as usize)?;
+                for (i, block) in data.chunks(block_size as usize).enumerate() {
+                    let addr = Addr(offset + i as u64 * block_size);
+                    profile.push((addr, entropy(&histogram(block))));
+                }
+                offset += len;
+            }
+            Ok(profile)
+        })
+    }
+
+    /// The regions of at least `min_size` bytes whose entropy stays above
+    /// `threshold` bits per byte, e.g. 7.2 for compressed or encrypted data.
+    ///
+    /// Built on `entropy_profile()` with blocks of 4 KiB (or `min_size`
+    /// when smaller) merged while above `threshold`, so regions are as
+    /// precise as the blocks.
+    pub fn find_high_entropy_regions(
+        &mut self,
+        threshold: f64,
+        min_size: u64,
+    ) -> Result<Vec<EntropyRegion>, Error> {
+        let block_size = REGION_BLOCK.min(min_size).max(1);
+        let size = self.eval("$s")?;
+        let mut regions = Vec::new();
+        // Start, end and sum of entropy * length of the current run.
+        let mut run: Option<(u64, u64, f64)> = None;
+        for (addr, e) in self.entropy_profile(block_size)? {
+            let end = (addr.0 + block_size).min(size);
+            if e > threshold {
+                let (_, run_end, weighted) = run.get_or_insert((addr.0, addr.0, 0.0));
+                *run_end = end;
+                *weighted += e * (end - addr.0) as f64;
+            } else if let Some(r) = run.take() {
+                regions.extend(region(r, min_size));
+            }
+        }
+        regions.extend(run.and_then(|r| region(r, min_size)));
+        Ok(regions)
+    }
+
+    /// How many times each byte value occurs in `range`, indexed by value.
+    pub fn byte_histogram(&mut self, range: Range<Addr>) -> Result<[u64; 256], Error> {
+        let mut counts = [0; 256];
+        let mut addr = range.start.0;
+        while addr < range.end.0 {
+            let len = READ_CHUNK.min(range.end.0 - addr);
+            for (count, n) in counts
+                .iter_mut()
+                .zip(histogram(&self.read_bytes(addr, len as usize)?).iter())
+            {
+                *count += n;
+            }
+            addr += len;
+        }
+        Ok(counts)
+    }
+}
+
+fn region((start, end, weighted): (u64, u64, f64), min_size: u64) -> Option<EntropyRegion> {
+    if end - start < min_size.max(1) {
+        return None;
+    }
+    Some(EntropyRegion {
+        range: Addr(start)..Addr(end),
+        entropy: weighted / (end - start) as f64,
+    })
+}
+
+fn histogram(data: &[u8]) -> [u64; 256] {
+    let mut counts = [0; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    counts
+}
+
+/// Shannon entropy of the bytes counted in `counts`, in bits per byte.
+pub fn entropy(counts: &[u64; 256]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -0,0 +1,334 @@
+//! The error type returned by the pipes and the typed wrappers.
+
+#[cfg(feature = "pipe")]
+use crate::session::SessionId;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::process::ExitStatus;
+use std::string::FromUtf8Error;
+use std::time::Duration;
+
+/// Everything that can go wrong while talking with r2.
+///
+/// New variants may be added, so matches need a wildcard arm.
+///
+/// The errors of a pipe come wrapped in `Error::Session`, telling which
+/// pipe failed: match on `root()` to tell them apart.
+///
+/// ```no_run
+/// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+/// match r2.cmd("aaa") {
+///     Err(e) => match e.root() {
+///         r2pipe::Error::Timeout { .. } => eprintln!("{} is too slow", e.session().unwrap()),
+///         _ => return Err(e),
+///     },
+///     Ok(_) => {}
+/// }
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The r2 executable couldn't be started.
+    Spawn {
+        exepath: String,
+        source: io::Error,
+    },
+    /// r2 started, but the initial exchange with it failed.
+    Handshake(String),
+    /// The file to open doesn't exist.
+    TargetNotFound(String),
+    /// The file to open can't be read.
+    PermissionDenied(String),
+    /// Reading from or writing to r2 failed in the middle of a command.
+    Io(io::Error),
+    /// The request to a remote r2 failed.
+    Http(String),
+    /// r2 exited, or closed its output, in the middle of a command.
+    ///
+    /// `status` is `None` when the exit status can't be known, for instance
+    /// on a pipe opened from inside r2. `stderr` holds the last messages r2
+    /// printed, when they were captured.
+    ProcessExited {
+        status: Option<ExitStatus>,
+        stderr: String,
+    },
+    /// `cmd` didn't complete within `after`.
+    Timeout {
+        cmd: String,
+        after: Duration,
+    },
+    /// `cmd` timed out and r2 had to be spawned again, so anything done in
+    /// the session so far (analysis, seek, flags...) is lost.
+    Recovered {
+        cmd: String,
+        after: Duration,
+    },
+    /// The deadline of the pipe passed before or while running `cmd`, see
+    /// `R2Pipe::set_deadline()`.
+    DeadlineExceeded {
+        cmd: String,
+    },
+    /// The replies no longer match the commands, see `R2Pipe::resync()`.
+    Desync(String),
+    /// The command can't be run as given, e.g. it is empty or would be
+    /// split in two.
+    InvalidCommand(String),
+    /// The output of a command isn't valid UTF-8, see `Utf8Policy`.
+    ///
+    /// The raw output is available with `FromUtf8Error::into_bytes()`.
+    Utf8(FromUtf8Error),
+    /// The output of `cmd` isn't the expected JSON.
+    ///
+    /// `excerpt` is the beginning of what r2 printed instead. Both are empty
+    /// when the JSON didn't come straight from a command.
+    Json {
+        cmd: String,
+        excerpt: String,
+        message: String,
+    },
+    /// A command expected to print JSON, or some other data, printed
+    /// nothing.
+    ///
+    /// Only the helpers parsing or exporting output return this: plenty
+    /// of commands (`e x=y`, `s addr`, `wx ...`) print nothing when they
+    /// succeed, see `R2Pipe::cmd_ok()`.
+    EmptyResponse(String),
+    /// The target, the r2 build or the kind of session can't do this.
+    Unsupported(String),
+    /// The output of a command doesn't have the expected shape.
+    UnexpectedOutput(String),
+    /// r2 printed an error or usage message as the output of a command,
+    /// see `R2Pipe::cmd_checked()`.
+    R2Error(String),
+    Other(String),
+    /// `error` happened on the pipe `session`, see `R2Pipe::session_id()`.
+    ///
+    /// On spawned pipes, `stderr` has what r2 wrote to its stderr while
+    /// running the failed command, often the actual reason (a missing
+    /// plugin, an unreadable file). `Error::ProcessExited` carries its own.
+    #[cfg(feature = "pipe")]
+    Session {
+        session: SessionId,
+        stderr: String,
+        error: Box<Error>,
+    },
+    /// `error` stopped `R2Pipe::cmd_batch()` at the command `index`, the
+    /// following ones didn't run or their replies were dropped.
+    #[cfg(feature = "pipe")]
+    Batch {
+        index: usize,
+        error: Box<Error>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Spawn {
+                ref exepath,
+                ref source,
+            } => write!(f, "Unable to spawn {}: {}", exepath, source),
+            Error::Handshake(ref e) => write!(f, "Handshake with r2 failed: {}", e),
+            Error::TargetNotFound(ref path) => write!(f, "No such file: {}", path),
+            Error::PermissionDenied(ref path) => write!(f, "Permission denied: {}", path),
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Http(ref e) => write!(f, "HTTP error: {}", e),
+            Error::ProcessExited {
+                ref status,
+                ref stderr,
+            } => {
+                match *status {
+                    Some(status) => write!(f, "r2 exited ({})", status)?,
+                    None => f.write_str("r2 closed the pipe")?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+            Error::Timeout { ref cmd, after } => {
+                write!(f, "`{}` timed out after {:?}", cmd, after)
+            }
+            Error::Recovered { ref cmd, after } => {
+                write!(f, "`{}` timed out after {:?}, r2 was restarted", cmd, after)
+            }
+            Error::DeadlineExceeded { ref cmd } => {
+                write!(f, "`{}` didn't complete before the deadline", cmd)
+            }
+            Error::Desync(ref e) => write!(f, "Lost track of the replies: {}", e),
+            Error::InvalidCommand(ref e) => write!(f, "Invalid command: {}", e),
+            Error::Utf8(ref e) => write!(f, "Invalid UTF-8 in output: {}", e),
+            Error::Json {
+                ref cmd,
+                ref excerpt,
+                ref message,
+            } => {
+                f.write_str("Unable to parse json")?;
+                if !cmd.is_empty() {
+                    write!(f, " from `{}`", cmd)?;
+                }
+                write!(f, ": {}", message)?;
+                if !excerpt.is_empty() {
+                    write!(f, " (output: {:?})", excerpt)?;
+                }
+                Ok(())
+            }
+            Error::EmptyResponse(ref cmd) => write!(f, "`{}` printed nothing", cmd),
+            Error::Unsupported(ref e) => write!(f, "Unsupported: {}", e),
+            Error::UnexpectedOutput(ref e) => write!(f, "Unexpected output: {}", e),
+            Error::R2Error(ref e) => write!(f, "r2 error: {}", e),
+            Error::Other(ref e) => f.write_str(e),
+            #[cfg(feature = "pipe")]
+            Error::Session {
+                session,
+                ref stderr,
+                ref error,
+            } => match stderr.trim() {
+                "" => write!(f, "{}: {}", session, error),
+                stderr => write!(f, "{}: {} (stderr: {})", session, error, stderr),
+            },
+            #[cfg(feature = "pipe")]
+            Error::Batch { index, ref error } => {
+                write!(f, "Batch stopped at command {}: {}", index, error)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Spawn { ref source, .. } => Some(source),
+            Error::Io(ref e) => Some(e),
+            Error::Utf8(ref e) => Some(e),
+            #[cfg(feature = "pipe")]
+            Error::Session { ref error, .. } => Some(&**error),
+            #[cfg(feature = "pipe")]
+            Error::Batch { ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::json(e.to_string())
+    }
+}
+
+impl From<String> for Error {
+    fn from(e: String) -> Error {
+        Error::Other(e)
+    }
+}
+
+impl<'a> From<&'a str> for Error {
+    fn from(e: &'a str) -> Error {
+        Error::Other(e.to_string())
+    }
+}
+
+impl Error {
+    /// The error without the `Error::Session` and `Error::Batch` around
+    /// it, if any.
+    pub fn root(&self) -> &Error {
+        match *self {
+            #[cfg(feature = "pipe")]
+            Error::Session { ref error, .. } | Error::Batch { ref error, .. } => error.root(),
+            _ => self,
+        }
+    }
+
+    /// Same as `root()`, by value, e.g. to match errors as they were
+    /// before the session was added with `map_err(Error::into_root)`.
+    pub fn into_root(self) -> Error {
+        match self {
+            #[cfg(feature = "pipe")]
+            Error::Session { error, .. } | Error::Batch { error, .. } => error.into_root(),
+            e => e,
+        }
+    }
+
+    /// The command of `R2Pipe::cmd_batch()` that failed.
+    #[cfg(feature = "pipe")]
+    pub fn batch_index(&self) -> Option<usize> {
+        match *self {
+            Error::Session { ref error, .. } => error.batch_index(),
+            Error::Batch { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    /// This error, as the one of the command `index` of a batch.
+    #[cfg(feature = "pipe")]
+    pub(crate) fn in_batch(self, index: usize) -> Error {
+        match self {
+            Error::Session {
+                session,
+                stderr,
+                error,
+            } => Error::Session {
+                session,
+                stderr,
+                error: Box::new(error.in_batch(index)),
+            },
+            e @ Error::Batch { .. } => e,
+            e => Error::Batch {
+                index,
+                error: Box::new(e),
+            },
+        }
+    }
+
+    /// The pipe the error happened on.
+    #[cfg(feature = "pipe")]
+    pub fn session(&self) -> Option<SessionId> {
+        match *self {
+            Error::Session { session, .. } => Some(session),
+            _ => None,
+        }
+    }
+
+    /// Whether this is an `Error::EmptyResponse`, wrapped or not.
+    pub fn is_empty_response(&self) -> bool {
+        matches!(*self.root(), Error::EmptyResponse(_))
+    }
+
+    /// A JSON error without a command attached.
+    pub(crate) fn json<T: Into<String>>(message: T) -> Error {
+        Error::Json {
+            cmd: String::new(),
+            excerpt: String::new(),
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "pipe"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_shows_stderr() {
+        let e = |stderr: &str| Error::Session {
+            session: SessionId(3),
+            stderr: stderr.to_string(),
+            error: Box::new(Error::EmptyResponse("pdgj".to_string())),
+        };
+        assert_eq!(e("").to_string(), "r2-3: `pdgj` printed nothing");
+        assert_eq!(
+            e("ERROR: no plugin\n").to_string(),
+            "r2-3: `pdgj` printed nothing (stderr: ERROR: no plugin)"
+        );
+        assert!(e("").is_empty_response());
+    }
+}
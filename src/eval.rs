@@ -0,0 +1,39 @@
+//! Evaluating r2 math expressions (`?v`) into numbers.
+//!
+//! Expressions can use flags, registers and r2 variables (`$$`, `$s`...),
+//! so scripts can ask r2 to compute addresses or conditions instead of
+//! parsing its output themselves.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::parse_r2_number;
+
+impl R2Pipe {
+    /// Evaluates `expr` with `?v` and returns its value.
+    ///
+    /// The command is quoted, so `>`, `~` or `;` in `expr` are part of the
+    /// expression rather than r2 redirections, greps or chains.
+    pub fn eval(&mut self, expr: &str) -> Result<u64, Error> {
+        if expr.contains('"') {
+            return Err(Error::InvalidCommand(
+                "Expression can't contain `\"`".to_string(),
+            ));
+        }
+        let res = self.cmd(&format!("\"?v {}\"", expr))?;
+        // `0x` prefixed hex, or decimal on older versions.
+        parse_r2_number(&res)
+    }
+
+    /// Evaluates `expr` as a condition, true when it isn't 0.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// if r2.cmd_bool("$s > 0x1000")? {
+    ///     r2.cmd("s 0x1000")?;
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn cmd_bool(&mut self, expr: &str) -> Result<bool, Error> {
+        Ok(self.eval(expr)? != 0)
+    }
+}
@@ -0,0 +1,134 @@
+//! Noticing what changed in a session, e.g. to keep a UI in sync while
+//! the analysis or a user in another client adds things.
+//!
+//! r2 has no event stream over r2pipe, so `SessionWatcher` compares the
+//! functions (`aflj`), flags (`fj`) and comments (`CCj`) with what it
+//! saw the last time it was polled.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Comment, Flag, FunctionInfo};
+
+use std::collections::BTreeMap;
+
+/// A change between two polls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    FunctionAdded {
+        addr: Addr,
+        name: String,
+    },
+    FunctionRemoved(Addr),
+    /// Same function, new name.
+    FunctionRenamed {
+        addr: Addr,
+        name: String,
+    },
+    /// Added, or moved to another address or size.
+    FlagSet(Flag),
+    FlagRemoved(String),
+    /// Added or changed.
+    CommentSet(Comment),
+    CommentRemoved(Addr),
+}
+
+/// Tells what changed in a session since the previous poll.
+///
+/// It doesn't borrow the pipe, so the session stays usable in between.
+///
+/// ```no_run
+/// use r2pipe::{SessionEvent, SessionWatcher};
+///
+/// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+/// let mut watcher = SessionWatcher::new(&mut r2)?;
+/// r2.cmd("aaa")?;
+/// for event in watcher.poll(&mut r2)? {
+///     if let SessionEvent::FunctionAdded { addr, name } = event {
+///         println!("{} {}", addr, name);
+///     }
+/// }
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SessionWatcher {
+    functions: BTreeMap<Addr, String>,
+    flags: BTreeMap<String, Flag>,
+    comments: BTreeMap<Addr, String>,
+}
+
+impl SessionWatcher {
+    /// Starts from what `r2` has now, so only later changes are reported.
+    pub fn new(r2: &mut R2Pipe) -> Result<SessionWatcher, Error> {
+        let mut watcher = SessionWatcher::default();
+        watcher.poll(r2)?;
+        Ok(watcher)
+    }
+
+    /// The changes since the previous poll, functions first, then flags,
+    /// then comments.
+    pub fn poll(&mut self, r2: &mut R2Pipe) -> Result<Vec<SessionEvent>, Error> {
+        let functions: BTreeMap<Addr, String> = listing::<FunctionInfo>(r2, "aflj")?
+            .into_iter()
+            .map(|f| (f.offset, f.name))
+            .collect();
+        let flags: BTreeMap<String, Flag> = listing::<Flag>(r2, "fj")?
+            .into_iter()
+            .map(|f| (f.name.clone(), f))
+            .collect();
+        let comments: BTreeMap<Addr, String> = r2
+            .comments()?
+            .into_iter()
+            .map(|c| (c.offset, c.text))
+            .collect();
+
+        let mut events = Vec::new();
+        for (&addr, name) in &functions {
+            match self.functions.get(&addr) {
+                None => events.push(SessionEvent::FunctionAdded {
+                    addr,
+                    name: name.clone(),
+                }),
+                Some(old) if old != name => events.push(SessionEvent::FunctionRenamed {
+                    addr,
+                    name: name.clone(),
+                }),
+                _ => {}
+            }
+        }
+        for addr in self.functions.keys().filter(|a| !functions.contains_key(a)) {
+            events.push(SessionEvent::FunctionRemoved(*addr));
+        }
+        for (name, flag) in &flags {
+            if self.flags.get(name) != Some(flag) {
+                events.push(SessionEvent::FlagSet(flag.clone()));
+            }
+        }
+        for name in self.flags.keys().filter(|n| !flags.contains_key(*n)) {
+            events.push(SessionEvent::FlagRemoved(name.clone()));
+        }
+        for (&offset, text) in &comments {
+            if self.comments.get(&offset) != Some(text) {
+                events.push(SessionEvent::CommentSet(Comment {
+                    offset,
+                    text: text.clone(),
+                }));
+            }
+        }
+        for addr in self.comments.keys().filter(|a| !comments.contains_key(a)) {
+            events.push(SessionEvent::CommentRemoved(*addr));
+        }
+
+        self.functions = functions;
+        self.flags = flags;
+        self.comments = comments;
+        Ok(events)
+    }
+}
+
+/// Deserializes `cmd`, which prints nothing rather than `[]` when empty.
+fn listing<T: serde::de::DeserializeOwned>(r2: &mut R2Pipe, cmd: &str) -> Result<Vec<T>, Error> {
+    match r2.cmd_deserialize(cmd) {
+        Err(ref e) if e.is_empty_response() => Ok(Vec::new()),
+        res => res,
+    }
+}
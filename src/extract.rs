@@ -0,0 +1,126 @@
+//! Carving parts of the binary out to files (`wtf`).
+//!
+//! Writing files is denied by `CommandPolicy::new()`, so these fail on
+//! hardened pipes.
+
+use crate::error::Error;
+use crate::r2pipe::{file_arg, R2Pipe};
+use crate::structs::{Addr, Resource, Section};
+
+use std::fs;
+
+/// What an extracted file holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractKind {
+    Section,
+    Resource,
+}
+
+/// A file written by `extract_section()` or `extract_resources()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extracted {
+    pub kind: ExtractKind,
+    /// Of the section, or the type and name of the resource.
+    pub name: String,
+    /// File offset of a section, virtual address of a resource.
+    pub addr: Addr,
+    pub size: u64,
+    pub path: String,
+}
+
+impl R2Pipe {
+    /// Writes the bytes of the section `name` in the file to `path`,
+    /// replacing any file there.
+    ///
+    /// Sections without bytes in the file, like `.bss`, can't be
+    /// extracted. As with `dump_memory_range()`, the size of the file is
+    /// checked when r2 runs on this machine.
+    pub fn extract_section(&mut self, name: &str, path: &str) -> Result<Extracted, Error> {
+        file_arg(path)?;
+        let sections: Vec<Section> = self.cmd_deserialize("iSj")?;
+        let section = match sections.into_iter().find(|s| s.name == name) {
+            Some(section) => section,
+            None => return Err(Error::InvalidCommand(format!("No section named {}", name))),
+        };
+        if section.size == 0 {
+            return Err(Error::InvalidCommand(format!(
+                "Section {} has no bytes in the file",
+                name
+            )));
+        }
+        let cmd = format!("wtf {} {} @ {}", path, section.size, section.paddr);
+        self.with_config("io.va", "false", |r2| {
+            r2.dump_to(path, &cmd, |written| written == section.size)
+        })?;
+        Ok(Extracted {
+            kind: ExtractKind::Section,
+            name: section.name,
+            addr: section.paddr,
+            size: section.size,
+            path: path.to_string(),
+        })
+    }
+
+    /// Writes each resource (`iRj`) to a file in `dir`, named after its
+    /// index, type and name.
+    ///
+    /// `dir` is created when r2 runs on this machine, otherwise it must
+    /// exist where r2 runs. Only PE binaries have resources, others give
+    /// an empty list.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("setup.exe", None).unwrap();
+    /// for res in r2.extract_resources("out")? {
+    ///     println!("{} ({} bytes) -> {}", res.name, res.size, res.path);
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn extract_resources(&mut self, dir: &str) -> Result<Vec<Extracted>, Error> {
+        file_arg(dir)?;
+        let resources: Vec<Resource> = match self.cmd_deserialize("iRj") {
+            Err(ref e) if e.is_empty_response() => return Ok(Vec::new()),
+            res => res?,
+        };
+        if self.is_local() && !resources.is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut written = Vec::new();
+        for res in resources.into_iter().filter(|r| r.size > 0) {
+            let name = format!("{}/{}", res.kind, res.name);
+            let path = format!(
+                "{}/{}_{}_{}",
+                dir.trim_end_matches('/'),
+                res.index,
+                file_part(&res.kind),
+                file_part(&res.name)
+            );
+            let cmd = format!("wtf {} {} @ {}", path, res.size, res.vaddr);
+            self.dump_to(&path, &cmd, |len| len == res.size)?;
+            written.push(Extracted {
+                kind: ExtractKind::Resource,
+                name,
+                addr: res.vaddr,
+                size: res.size,
+                path,
+            });
+        }
+        Ok(written)
+    }
+}
+
+/// `s` with only the characters `file_arg()` accepts and no separators.
+fn file_part(s: &str) -> String {
+    let part: String = s
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+    let part = part.trim_start_matches('.');
+    if part.is_empty() {
+        "_".to_string()
+    } else {
+        part.to_string()
+    }
+}
@@ -0,0 +1,133 @@
+//! Containers of several binaries, such as fat Mach-O holding a slice per
+//! architecture.
+//!
+//! r2 loads the slices as binary objects of the file (`ob`), the one
+//! matching `-a` and `-b` first.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{BinObject, SubBin};
+
+use serde_derive::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubBins {
+    Wrapped { bins: Vec<SubBin> },
+    List(Vec<SubBin>),
+}
+
+impl R2Pipe {
+    /// The binaries in the current file (`iAj`), a single one for files
+    /// that aren't containers.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/usr/lib/dyld", None).unwrap();
+    /// for i in 0..r2.subbinaries()?.len() {
+    ///     let slice = r2.select_subbinary(i)?;
+    ///     println!("{} {}: {}", slice.arch, slice.bits, r2.imports()?.len());
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn subbinaries(&mut self) -> Result<Vec<SubBin>, Error> {
+        Ok(match self.cmd_deserialize("iAj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => match res? {
+                SubBins::Wrapped { bins } => bins,
+                SubBins::List(bins) => bins,
+            },
+        })
+    }
+
+    /// Makes the `index`th of `subbinaries()` the current binary, and
+    /// returns it.
+    ///
+    /// Fails with `Error::Unsupported` when r2 didn't load that slice; it
+    /// can be loaded by spawning r2 with its arch and bits
+    /// (`-a arm -b 64`). Fails with `Error::UnexpectedOutput` when r2
+    /// doesn't tell the offsets of the binary objects and the slice can't
+    /// be told from another one of the same arch and bits.
+    pub fn select_subbinary(&mut self, index: usize) -> Result<SubBin, Error> {
+        let bins = self.subbinaries()?;
+        let bin = bins.get(index).cloned().ok_or_else(|| {
+            Error::InvalidCommand(format!("No subbinary {} in {} of them", index, bins.len()))
+        })?;
+        let matches: Vec<BinObject> = self
+            .bin_objects()?
+            .into_iter()
+            .filter(|o| loads(o, &bin))
+            .collect();
+        let twins = bins.iter().filter(|b| same_kind(b, &bin)).count();
+        let by_kind = matches.iter().any(|o| o.offset != bin.offset);
+        let obj = match matches[..] {
+            [] => Err(Error::Unsupported(format!(
+                "The {} {} bits slice at {:#x} isn't loaded",
+                bin.arch, bin.bits, bin.offset
+            ))),
+            [ref obj] if !by_kind || twins == 1 => Ok(obj),
+            _ => Err(Error::UnexpectedOutput(format!(
+                "Can't tell which binary object is the {} {} bits slice at {:#x}",
+                bin.arch, bin.bits, bin.offset
+            ))),
+        }?;
+        self.select_binobj(obj.id)?;
+        Ok(bin)
+    }
+}
+
+/// Whether `obj` may be the binary `bin`: the one at its offset, or one of
+/// its arch and bits when r2 doesn't tell the offsets.
+fn loads(obj: &BinObject, bin: &SubBin) -> bool {
+    if obj.offset != 0 || bin.offset == 0 {
+        return obj.offset == bin.offset;
+    }
+    obj.arch == bin.arch && obj.bits == bin.bits
+}
+
+fn same_kind(a: &SubBin, b: &SubBin) -> bool {
+    a.arch == b.arch && a.bits == b.bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    const BINS: &str = r#"{"bins": [
+        {"arch": "arm", "bits": 64, "offset": 16384},
+        {"arch": "arm", "bits": 64, "offset": 81920},
+        {"arch": "x86", "bits": 64, "offset": 147456}
+    ]}"#;
+
+    fn fat(objects: &str) -> R2Pipe {
+        R2Pipe::from_commands(
+            MockR2Pipe::new()
+                .on("iAj", BINS)
+                .on("obj", objects)
+                .lenient(),
+        )
+    }
+
+    #[test]
+    fn selects_by_offset() {
+        let mut r2 = fat(r#"[{"id": 3, "arch": "arm", "bits": 64, "offset": 81920}]"#);
+        assert_eq!(r2.select_subbinary(1).unwrap().offset, 81920);
+        assert!(matches!(
+            r2.select_subbinary(0).unwrap_err().root(),
+            Error::Unsupported(_)
+        ));
+    }
+
+    #[test]
+    fn twins_without_offsets() {
+        let mut r2 =
+            fat(r#"[{"id": 3, "arch": "arm", "bits": 64}, {"id": 4, "arch": "x86", "bits": 64}]"#);
+        assert_eq!(r2.select_subbinary(2).unwrap().arch, "x86");
+        for twin in 0..2 {
+            assert!(matches!(
+                r2.select_subbinary(twin).unwrap_err().root(),
+                Error::UnexpectedOutput(_)
+            ));
+        }
+    }
+}
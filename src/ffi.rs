@@ -0,0 +1,176 @@
+//! A small C ABI over `R2Pipe`, for C, C++ or Python (ctypes, cffi) code
+//! to reuse the process handling of this crate.
+//!
+//! Build a shared library with
+//! `cargo rustc --release --features ffi --crate-type cdylib`, and declare:
+//!
+//! ```c
+//! typedef struct R2Pipe R2Pipe;
+//!
+//! R2Pipe *r2pipe_open(const char *path);
+//! char *r2pipe_cmd(R2Pipe *r2, const char *cmd);
+//! void r2pipe_free_string(char *s);
+//! void r2pipe_free(R2Pipe *r2);
+//! const char *r2pipe_last_error(void);
+//! ```
+//!
+//! Functions returning a pointer return NULL on failure, and
+//! `r2pipe_last_error()` then tells why. A panic doesn't unwind into the
+//! caller, which would be undefined behavior: it is caught and reported
+//! the same way.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(e: Error) {
+    // The messages can't contain NUL, but if one does keep what's before.
+    let msg = e.to_string();
+    let msg = msg.split('\0').next().unwrap_or_default();
+    let msg = CString::new(msg).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+}
+
+/// Runs `f`, with a panic turned into an error.
+///
+/// The pipe `f` was using may be left halfway through a command, callers
+/// should free it after such failures.
+fn catch_panic<T, F: FnOnce() -> Result<T, Error>>(f: F) -> Result<T, Error> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let msg = match payload.downcast_ref::<&str>() {
+            Some(msg) => msg.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_default(),
+        };
+        Err(Error::Other(format!("r2pipe panicked: {}", msg)))
+    })
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(Error::InvalidCommand(format!("{} is NULL", name)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| Error::InvalidCommand(format!("{} isn't UTF-8", name)))
+}
+
+/// Spawns r2 on `path`, or connects to the r2 running this process when
+/// `path` is NULL (`#!pipe`).
+///
+/// # Safety
+///
+/// `path` must be NULL or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn r2pipe_open(path: *const c_char) -> *mut R2Pipe {
+    let r2 = catch_panic(|| {
+        if path.is_null() {
+            R2Pipe::open()
+        } else {
+            str_arg(path, "path").and_then(|path| R2Pipe::spawn(path, None))
+        }
+    });
+    match r2 {
+        Ok(r2) => Box::into_raw(Box::new(r2)),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Runs `cmd` and returns its output, to free with `r2pipe_free_string()`.
+///
+/// Output containing NUL bytes is an error, use commands printing hex or
+/// base64 for binary data. After a panic, reported as an error too, `r2`
+/// may be out of sync and is best freed.
+///
+/// # Safety
+///
+/// `r2` must come from `r2pipe_open()` and not be freed, nor used by
+/// another thread at the same time. `cmd` must be a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn r2pipe_cmd(r2: *mut R2Pipe, cmd: *const c_char) -> *mut c_char {
+    let res = catch_panic(|| match r2.as_mut() {
+        Some(r2) => str_arg(cmd, "cmd").and_then(|cmd| r2.cmd(cmd)),
+        None => Err(Error::InvalidCommand("r2 is NULL".to_string())),
+    });
+    let res = res.and_then(|out| {
+        CString::new(out).map_err(|_| Error::UnexpectedOutput("Output contains NUL".to_string()))
+    });
+    match res {
+        Ok(out) => out.into_raw(),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a string returned by `r2pipe_cmd()`. Does nothing on NULL.
+///
+/// # Safety
+///
+/// `s` must come from `r2pipe_cmd()` and not be freed already.
+#[no_mangle]
+pub unsafe extern "C" fn r2pipe_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Closes the pipe, quitting the r2 it spawned, and frees it. Does nothing
+/// on NULL.
+///
+/// # Safety
+///
+/// `r2` must come from `r2pipe_open()` and not be freed already.
+#[no_mangle]
+pub unsafe extern "C" fn r2pipe_free(r2: *mut R2Pipe) {
+    if !r2.is_null() {
+        let mut r2 = Box::from_raw(r2);
+        // Nothing to report a panic to, but it mustn't unwind into C.
+        let _ = catch_panic(|| {
+            r2.close();
+            Ok(())
+        });
+    }
+}
+
+/// The error of the last call failing on this thread, or NULL.
+///
+/// The string belongs to the library and is valid until the next failing
+/// call on this thread.
+#[no_mangle]
+pub extern "C" fn r2pipe_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match *last.borrow() {
+        Some(ref msg) => msg.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_become_errors() {
+        let res: Result<(), Error> = catch_panic(|| panic!("bad {}", 1));
+        assert_eq!(res.unwrap_err().to_string(), "r2pipe panicked: bad 1");
+        let null = unsafe { r2pipe_cmd(ptr::null_mut(), ptr::null()) };
+        assert!(null.is_null());
+        let msg = unsafe { CStr::from_ptr(r2pipe_last_error()) };
+        assert_eq!(msg.to_str().unwrap(), "Invalid command: r2 is NULL");
+    }
+}
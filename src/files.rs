@@ -0,0 +1,236 @@
+//! Opening files and URIs through r2's IO plugins (`o*`), and mapping
+//! them in the address space (`om*`).
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::raw::MapSpec;
+use crate::structs::{Addr, BinObject, IoMap, IoPlugin, OpenFile};
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IoPlugins {
+    Wrapped { io_plugins: Vec<IoPlugin> },
+    List(Vec<IoPlugin>),
+}
+
+impl R2Pipe {
+    /// The IO plugins of this r2 build (`oLj`).
+    pub fn io_plugins(&mut self) -> Result<Vec<IoPlugin>, Error> {
+        Ok(match self.cmd_deserialize("oLj")? {
+            IoPlugins::Wrapped { io_plugins } => io_plugins,
+            IoPlugins::List(plugins) => plugins,
+        })
+    }
+
+    /// The files opened in the session (`oj`).
+    pub fn open_files(&mut self) -> Result<Vec<OpenFile>, Error> {
+        self.cmd_deserialize("oj")
+    }
+
+    /// How the files are mapped in the address space (`omj`).
+    pub fn io_maps(&mut self) -> Result<Vec<IoMap>, Error> {
+        self.cmd_deserialize("omj")
+    }
+
+    /// Maps a range of the file `fd` (`om`), and returns the new map.
+    pub fn add_map(&mut self, fd: u64, map: &MapSpec) -> Result<IoMap, Error> {
+        check_map(map)?;
+        let before = self.io_maps()?;
+        self.cmd_ok(&format!(
+            "om {} {} {} {} {}",
+            fd,
+            map.addr,
+            map.size,
+            map.offset,
+            map_perms(&map.perms)
+        ))?;
+        self.io_maps()?
+            .into_iter()
+            .find(|m| !before.iter().any(|b| b.id == m.id))
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "r2 didn't map {:#x} bytes at {}",
+                    map.size, map.addr
+                ))
+            })
+    }
+
+    /// Removes the map `id` (`om-`).
+    pub fn remove_map(&mut self, id: u64) -> Result<(), Error> {
+        self.cmd_ok(&format!("om- {}", id))
+    }
+
+    /// Removes all the maps (`om-*`).
+    pub fn remove_all_maps(&mut self) -> Result<(), Error> {
+        self.cmd_ok("om-*")
+    }
+
+    /// Changes the permissions of the map `id` (`omp`), e.g. to `rw-`.
+    pub fn set_map_perms(&mut self, id: u64, perms: &str) -> Result<IoMap, Error> {
+        check_perms(perms)?;
+        self.cmd_ok(&format!("omp {} {}", id, map_perms(perms)))?;
+        let map = self.io_map(id)?;
+        if perm_bits(&map.perm) != perm_bits(map_perms(perms)) {
+            return Err(Error::Other(format!(
+                "Map {} kept the permissions {}",
+                id, map.perm
+            )));
+        }
+        Ok(map)
+    }
+
+    /// Moves the map `id` to start at `addr` (`omb`).
+    pub fn rebase_map<A: Into<Addr>>(&mut self, id: u64, addr: A) -> Result<IoMap, Error> {
+        let addr = addr.into();
+        self.cmd_ok(&format!("omb {} {}", id, addr))?;
+        let map = self.io_map(id)?;
+        if map.from != addr {
+            return Err(Error::Other(format!("Map {} is still at {}", id, map.from)));
+        }
+        Ok(map)
+    }
+
+    fn io_map(&mut self, id: u64) -> Result<IoMap, Error> {
+        self.io_maps()?
+            .into_iter()
+            .find(|m| m.id == id)
+            .ok_or_else(|| Error::Other(format!("No map {}", id)))
+    }
+
+    /// Opens `uri` (`o`, or `o+` when `perms` is `rw`) and returns the new
+    /// file.
+    ///
+    /// `perms` is `r` or `rw`. The scheme of `uri` is checked against
+    /// `io_plugins()` first: r2 can hang or silently open nothing on an URI
+    /// no plugin handles, this fails with `Error::Unsupported` instead.
+    /// Plain paths go to the default plugin.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("--", None).unwrap();
+    /// let file = r2.open_uri("malloc://4096", "rw")?;
+    /// r2.select_fd(file.fd)?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn open_uri(&mut self, uri: &str, perms: &str) -> Result<OpenFile, Error> {
+        let open = match perms {
+            "r" => "o",
+            "rw" => "o+",
+            _ => {
+                return Err(Error::InvalidCommand(format!(
+                    "Can't open {} as {:?}, use r or rw",
+                    uri, perms
+                )))
+            }
+        };
+        if uri.is_empty() || uri.contains(|c: char| c.is_whitespace() || c == ';') {
+            return Err(Error::InvalidCommand(format!("Unusable URI: {:?}", uri)));
+        }
+        if let Some((scheme, _)) = uri.split_once("://") {
+            if !self.io_plugins()?.iter().any(|p| p.handles(scheme)) {
+                return Err(Error::Unsupported(format!(
+                    "IO plugin for {}:// not compiled in",
+                    scheme
+                )));
+            }
+        }
+        let before = self.open_files()?;
+        self.cmd_ok(&format!("{} {}", open, uri))?;
+        self.open_files()?
+            .into_iter()
+            .find(|f| !before.iter().any(|b| b.fd == f.fd))
+            .ok_or_else(|| Error::Other(format!("r2 could not open {}", uri)))
+    }
+
+    /// Makes the file `fd` the current one (`op`), which commands read and
+    /// write from then on.
+    pub fn select_fd(&mut self, fd: u64) -> Result<OpenFile, Error> {
+        if !self.open_files()?.iter().any(|f| f.fd == fd) {
+            return Err(Error::InvalidCommand(format!("No opened file {}", fd)));
+        }
+        self.cmd_ok(&format!("op {}", fd))?;
+        self.open_files()?
+            .into_iter()
+            .find(|f| f.fd == fd && f.raised)
+            .ok_or_else(|| Error::Other(format!("r2 didn't select the file {}", fd)))
+    }
+
+    /// The binaries loaded from the opened files (`obj`).
+    pub fn bin_objects(&mut self) -> Result<Vec<BinObject>, Error> {
+        let entries: Vec<Value> = match self.cmd_deserialize("obj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        entries
+            .into_iter()
+            .map(|entry| Ok(serde_json::from_value(bin_object(entry))?))
+            .collect()
+    }
+
+    /// Makes the binary `id` the current one (`ob`), whose symbols,
+    /// sections... the `i` commands list.
+    pub fn select_binobj(&mut self, id: u64) -> Result<BinObject, Error> {
+        let obj = self
+            .bin_objects()?
+            .into_iter()
+            .find(|o| o.id == id)
+            .ok_or_else(|| Error::InvalidCommand(format!("No binary object {}", id)))?;
+        self.cmd_ok(&format!("ob {}", id))?;
+        Ok(obj)
+    }
+}
+
+/// An entry of `obj` as a `BinObject`: recent r2 keep the details of the
+/// binary in an `obj` member, and `size` is that of the file there.
+fn bin_object(entry: Value) -> Value {
+    match entry {
+        Value::Object(mut map) => {
+            if let Some(Value::Object(obj)) = map.remove("obj") {
+                map.extend(obj);
+            }
+            if let Some(size) = map.remove("objsize") {
+                map.insert("size".to_string(), size);
+            }
+            Value::Object(map)
+        }
+        other => other,
+    }
+}
+
+pub(crate) fn check_map(map: &MapSpec) -> Result<(), Error> {
+    check_perms(&map.perms)?;
+    if map.size == 0 || map.addr.0.checked_add(map.size).is_none() {
+        return Err(Error::InvalidCommand(format!("Can't map {:?}", map)));
+    }
+    Ok(())
+}
+
+fn check_perms(perms: &str) -> Result<(), Error> {
+    if perms.len() > 3 || !perms.chars().all(|c| "rwx-".contains(c)) {
+        return Err(Error::InvalidCommand(format!(
+            "Invalid permissions: {:?}",
+            perms
+        )));
+    }
+    Ok(())
+}
+
+/// `perms`, `r-x` when empty.
+fn map_perms(perms: &str) -> &str {
+    if perms.is_empty() {
+        "r-x"
+    } else {
+        perms
+    }
+}
+
+/// Readable, writable and executable, whatever the dashes look like.
+fn perm_bits(perms: &str) -> [bool; 3] {
+    [
+        perms.contains('r'),
+        perms.contains('w'),
+        perms.contains('x'),
+    ]
+}
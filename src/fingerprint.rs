@@ -0,0 +1,264 @@
+//! Fingerprints for clustering samples: hashes of how a binary links
+//! rather than of its bytes, which change with every build.
+//!
+//! The hashes of the file itself come from r2 (`R2Pipe::file_hashes()`),
+//! r2 has none of these, so they are computed here from the imports.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::Import;
+
+impl R2Pipe {
+    /// The PE import hash (imphash), as computed by pefile and VirusTotal.
+    ///
+    /// Empty for binaries without imports. Other formats get a hash too,
+    /// but the import libraries are missing from it, see `symhash()`.
+    pub fn imphash(&mut self) -> Result<String, Error> {
+        Ok(imphash(&self.imports()?))
+    }
+
+    /// The Mach-O symhash: the hash of the sorted import names, for
+    /// binaries whose imports don't record their library.
+    ///
+    /// This isn't telfhash, the usual import fingerprint of ELF binaries,
+    /// which is a locality sensitive hash (TLSH) of the symbols. Computed
+    /// on an ELF, it only compares with the symhash of other ELFs.
+    pub fn symhash(&mut self) -> Result<String, Error> {
+        Ok(symhash(&self.imports()?))
+    }
+}
+
+/// The imphash of `imports`, in their import table order.
+///
+/// Each import is written `library.function`, lowercased, without the
+/// `.dll`, `.ocx` or `.sys` extension, and `ordN` for the functions
+/// imported by ordinal. The md5 of the comma separated list is returned in
+/// hex.
+///
+/// pefile names the ordinals of `ws2_32`, `wsock32` and `oleaut32` from a
+/// table, which isn't done here: samples importing those by ordinal get
+/// another hash.
+pub fn imphash(imports: &[Import]) -> String {
+    if imports.is_empty() {
+        return String::new();
+    }
+    let list: Vec<String> = imports
+        .iter()
+        .map(|imp| {
+            let lib = imp.libname.to_lowercase();
+            let lib = match lib.rsplit_once('.') {
+                Some((stem, "dll")) | Some((stem, "ocx")) | Some((stem, "sys")) => stem,
+                _ => &lib,
+            };
+            let name = if imp.name.is_empty() {
+                format!("ord{}", imp.ordinal)
+            } else {
+                imp.name.to_lowercase()
+            };
+            if lib.is_empty() {
+                name
+            } else {
+                format!("{}.{}", lib, name)
+            }
+        })
+        .collect();
+    hex(&md5(list.join(",").as_bytes()))
+}
+
+/// The symhash of `imports`: md5 of their sorted, deduplicated names joined
+/// with commas, in hex, as CrowdStrike computes it for Mach-O.
+pub fn symhash(imports: &[Import]) -> String {
+    if imports.is_empty() {
+        return String::new();
+    }
+    let mut names: Vec<&str> = imports
+        .iter()
+        .map(|imp| imp.name.as_str())
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    hex(&md5(names.join(",").as_bytes()))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// RFC 1321.
+fn md5(data: &[u8]) -> [u8; 16] {
+    const K: [u32; 64] = [
+        0xd76a_a478,
+        0xe8c7_b756,
+        0x2420_70db,
+        0xc1bd_ceee,
+        0xf57c_0faf,
+        0x4787_c62a,
+        0xa830_4613,
+        0xfd46_9501,
+        0x6980_98d8,
+        0x8b44_f7af,
+        0xffff_5bb1,
+        0x895c_d7be,
+        0x6b90_1122,
+        0xfd98_7193,
+        0xa679_438e,
+        0x49b4_0821,
+        0xf61e_2562,
+        0xc040_b340,
+        0x265e_5a51,
+        0xe9b6_c7aa,
+        0xd62f_105d,
+        0x0244_1453,
+        0xd8a1_e681,
+        0xe7d3_fbc8,
+        0x21e1_cde6,
+        0xc337_07d6,
+        0xf4d5_0d87,
+        0x455a_14ed,
+        0xa9e3_e905,
+        0xfcef_a3f8,
+        0x676f_02d9,
+        0x8d2a_4c8a,
+        0xfffa_3942,
+        0x8771_f681,
+        0x6d9d_6122,
+        0xfde5_380c,
+        0xa4be_ea44,
+        0x4bde_cfa9,
+        0xf6bb_4b60,
+        0xbebf_bc70,
+        0x289b_7ec6,
+        0xeaa1_27fa,
+        0xd4ef_3085,
+        0x0488_1d05,
+        0xd9d4_d039,
+        0xe6db_99e5,
+        0x1fa2_7cf8,
+        0xc4ac_5665,
+        0xf429_2244,
+        0x432a_ff97,
+        0xab94_23a7,
+        0xfc93_a039,
+        0x655b_59c3,
+        0x8f0c_cc92,
+        0xffef_f47d,
+        0x8584_5dd1,
+        0x6fa8_7e4f,
+        0xfe2c_e6e0,
+        0xa301_4314,
+        0x4e08_11a1,
+        0xf753_7e82,
+        0xbd3a_f235,
+        0x2ad7_d2bb,
+        0xeb86_d391,
+    ];
+    const S: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for chunk in msg.chunks(64) {
+        let m: Vec<u32> = chunk
+            .chunks(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(K[i]).wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(S[i]));
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d].iter()) {
+            *s = s.wrapping_add(*v);
+        }
+    }
+    let mut digest = [0; 16];
+    for (out, s) in digest.chunks_mut(4).zip(state.iter()) {
+        out.copy_from_slice(&s.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(libname: &str, name: &str, ordinal: u64) -> Import {
+        Import {
+            libname: libname.to_string(),
+            name: name.to_string(),
+            ordinal,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn md5_rfc_vectors() {
+        let vectors = [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("a", "0cc175b9c0f1b6a831c399e269772661"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                "abcdefghijklmnopqrstuvwxyz",
+                "c3fcd3d76192e4007dfb496cca67e13b",
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "d174ab98d277d9f5a5611c2c9f419d9f",
+            ),
+            (
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+        for (data, digest) in vectors.iter() {
+            assert_eq!(hex(&md5(data.as_bytes())), *digest, "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn imphash_as_pefile() {
+        // What pefile hashes: "kernel32.getprocaddress,kernel32.loadlibrarya,
+        // comctl32.ord17,msvcrt.printf".
+        let imports = [
+            import("KERNEL32.dll", "GetProcAddress", 1),
+            import("KERNEL32.dll", "LoadLibraryA", 2),
+            import("COMCTL32.dll", "", 17),
+            import("msvcrt.dll", "printf", 3),
+        ];
+        assert_eq!(imphash(&imports), "407aa9c138570ebb4776c650310fe4d4");
+        assert_eq!(imphash(&[]), "");
+    }
+
+    #[test]
+    fn symhash_sorts_and_dedups() {
+        let imports = [
+            import("", "_printf", 0),
+            import("", "_malloc", 0),
+            import("", "_printf", 0),
+        ];
+        assert_eq!(
+            hex(&md5(b"_malloc,_printf")),
+            "f11bd1e48227d71c9279769c74733e2a"
+        );
+        assert_eq!(symhash(&imports), "f11bd1e48227d71c9279769c74733e2a");
+    }
+}
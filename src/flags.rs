@@ -0,0 +1,216 @@
+//! Moving flags in and out of a session in bulk, e.g. names recovered by
+//! another tool or shared between reverse engineers.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Flag};
+
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+/// The files `export_flags()` writes and `import_flags()` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagFormat {
+    /// A line per flag, `f name size addr`, as printed by `f*`.
+    R2Script,
+    /// `name,offset,size` columns, after a header line with those names.
+    Csv,
+    /// An array of `{"name": ..., "offset": ..., "size": ...}`, like `fj`.
+    Json,
+}
+
+const CSV_HEADER: &str = "name,offset,size";
+
+impl R2Pipe {
+    /// Writes the flags of the session to `path` in `format`, and returns
+    /// how many there are.
+    ///
+    /// ```no_run
+    /// use r2pipe::flags::FlagFormat;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.export_flags("ls.csv", FlagFormat::Csv)?;
+    /// # let mut other = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// other.import_flags("ls.csv")?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn export_flags<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        format: FlagFormat,
+    ) -> Result<usize, Error> {
+        let flags: Vec<Flag> = match self.cmd_deserialize("fj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        let out = match format {
+            FlagFormat::R2Script => flags
+                .iter()
+                .map(|f| format!("f {} {} {}\n", f.name, f.size, f.offset))
+                .collect(),
+            FlagFormat::Csv => {
+                let mut out = format!("{}\n", CSV_HEADER);
+                for f in &flags {
+                    out.push_str(&format!("{},{},{}\n", csv_field(&f.name), f.offset, f.size));
+                }
+                out
+            }
+            FlagFormat::Json => {
+                let list: Vec<_> = flags
+                    .iter()
+                    .map(|f| json!({"name": f.name, "offset": f.offset.0, "size": f.size}))
+                    .collect();
+                serde_json::to_string_pretty(&list)?
+            }
+        };
+        fs::write(path, out)?;
+        Ok(flags.len())
+    }
+
+    /// Sets the flags of the file at `path`, written by `export_flags()`
+    /// in any format, and returns how many were set.
+    ///
+    /// The format is told from the content. Only flags are read from an
+    /// r2 script, other commands in it are skipped rather than run. Names
+    /// get the characters r2 doesn't allow in flags replaced with `_`.
+    /// The flags are set in a batch, see `cmd_batch()`.
+    pub fn import_flags<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, Error> {
+        let text = fs::read_to_string(path)?;
+        let flags = parse_flags(&text)?;
+        let cmds: Vec<String> = flags
+            .iter()
+            .map(|f| format!("f {} {} @ {}", flag_name(&f.name), f.size.max(1), f.offset))
+            .collect();
+        let cmds: Vec<&str> = cmds.iter().map(String::as_str).collect();
+        self.cmd_batch(&cmds)?;
+        Ok(cmds.len())
+    }
+}
+
+/// The flags in `text`, in whichever of the `FlagFormat`s it is.
+fn parse_flags(text: &str) -> Result<Vec<Flag>, Error> {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('[') {
+        return Ok(serde_json::from_str(trimmed)?);
+    }
+    let mut lines = trimmed.lines().map(str::trim).filter(|l| !l.is_empty());
+    if trimmed.lines().next().map(str::trim) == Some(CSV_HEADER) {
+        lines.next();
+        return lines.map(parse_csv_line).collect();
+    }
+    Ok(lines.filter_map(parse_script_line).collect())
+}
+
+/// A `f name size addr` line, `None` for other commands.
+fn parse_script_line(line: &str) -> Option<Flag> {
+    match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["f", name, size, addr] => Some(Flag {
+            name: name.to_string(),
+            size: size.parse().ok()?,
+            offset: addr.parse().ok()?,
+            ..Flag::default()
+        }),
+        _ => None,
+    }
+}
+
+fn parse_csv_line(line: &str) -> Result<Flag, Error> {
+    let invalid = || Error::UnexpectedOutput(format!("Not a flag: {:?}", line));
+    // Only the name may be quoted, the numbers follow the last commas.
+    let mut cols = line.rsplitn(3, ',');
+    let size = cols.next().ok_or_else(invalid)?.trim();
+    let offset = cols.next().ok_or_else(invalid)?.trim();
+    let name = cols.next().ok_or_else(invalid)?.trim();
+    let name = match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => name.to_string(),
+    };
+    Ok(Flag {
+        name,
+        size: size.parse().map_err(|_| invalid())?,
+        offset: offset.parse::<Addr>().map_err(|_| invalid())?,
+        ..Flag::default()
+    })
+}
+
+/// `field` quoted when it holds a comma or a quote.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// `name` with the characters r2 doesn't allow in flag names replaced.
+pub(crate) fn flag_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._:-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag(name: &str, offset: u64, size: u64) -> Flag {
+        Flag {
+            name: name.to_string(),
+            offset: Addr(offset),
+            size,
+            ..Flag::default()
+        }
+    }
+
+    #[test]
+    fn reads_csv() {
+        let text = format!(
+            "{}\nmain,0x1000,16\n{},4096,1\n\n  \"a \"\"b\"\"\",0x20,8\n",
+            CSV_HEADER,
+            csv_field("str.hello,world")
+        );
+        assert_eq!(
+            parse_flags(&text).unwrap(),
+            vec![
+                flag("main", 0x1000, 16),
+                flag("str.hello,world", 0x1000, 1),
+                flag("a \"b\"", 0x20, 8),
+            ]
+        );
+        let bad = format!("{}\nmain,0x1000\n", CSV_HEADER);
+        assert!(parse_flags(&bad).is_err());
+        let bad = format!("{}\nmain,there,16\n", CSV_HEADER);
+        assert!(parse_flags(&bad).is_err());
+    }
+
+    #[test]
+    fn reads_scripts_and_json() {
+        let script =
+            "fs symbols\nf main 16 0x1000\nf  sym.exit 1 4096\nCC hi @ 0x1000\nf bad x 0x10\n";
+        assert_eq!(
+            parse_flags(script).unwrap(),
+            vec![flag("main", 0x1000, 16), flag("sym.exit", 0x1000, 1)]
+        );
+        let json = r#" [{"name": "main", "offset": 4096, "size": 16}]"#;
+        assert_eq!(parse_flags(json).unwrap(), vec![flag("main", 0x1000, 16)]);
+    }
+
+    #[test]
+    fn flag_names() {
+        assert_eq!(flag_name("sym.std::vec<int>"), "sym.std::vec_int_");
+        assert_eq!(flag_name(""), "_");
+    }
+}
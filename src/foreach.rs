@@ -0,0 +1,92 @@
+//! Running a JSON command over every function, flag or section.
+//!
+//! r2's `@@` iterators concatenate the output of each iteration, so instead
+//! of splitting text the offsets are fetched first and the command is run
+//! with `@@=` over them, parsing the resulting stream of JSON values.
+
+use crate::error::Error;
+use crate::r2pipe::{parse_json, suffixable, R2Pipe};
+use crate::structs::Addr;
+
+use serde_json::{Deserializer, Value};
+use std::collections::BTreeMap;
+
+/// Number of offsets passed to a single `@@=` command.
+const CHUNK_SIZE: usize = 512;
+
+impl R2Pipe {
+    /// Runs `cmd` at every function (`aflj`), keyed by function offset.
+    pub fn cmd_foreach_function(&mut self, cmd: &str) -> Result<BTreeMap<Addr, Value>, Error> {
+        let offsets = self.listing_offsets("aflj", "offset")?;
+        self.cmdj_foreach(cmd, &offsets)
+    }
+
+    /// Runs `cmd` at every flag (`fj`), keyed by flag offset.
+    pub fn cmd_foreach_flag(&mut self, cmd: &str) -> Result<BTreeMap<Addr, Value>, Error> {
+        let offsets = self.listing_offsets("fj", "offset")?;
+        self.cmdj_foreach(cmd, &offsets)
+    }
+
+    /// Runs `cmd` at every section (`iSj`), keyed by section virtual address.
+    pub fn cmd_foreach_section(&mut self, cmd: &str) -> Result<BTreeMap<Addr, Value>, Error> {
+        let offsets = self.listing_offsets("iSj", "vaddr")?;
+        self.cmdj_foreach(cmd, &offsets)
+    }
+
+    /// Runs `cmd` at each of `offsets`, returning the parsed JSON per offset.
+    ///
+    /// Offsets where the command produced no output are missing from the map.
+    pub fn cmdj_foreach(
+        &mut self,
+        cmd: &str,
+        offsets: &[Addr],
+    ) -> Result<BTreeMap<Addr, Value>, Error> {
+        let cmd = suffixable(cmd)?.to_string();
+        let mut results = BTreeMap::new();
+        for chunk in offsets.chunks(CHUNK_SIZE) {
+            let list = chunk
+                .iter()
+                .map(|o| o.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let res = self.cmd(&format!("{} @@= {}", cmd, list))?;
+            let values = Deserializer::from_str(&res)
+                .into_iter::<Value>()
+                .collect::<Result<Vec<_>, _>>();
+            match values {
+                Ok(values) if values.len() == chunk.len() => {
+                    results.extend(chunk.iter().cloned().zip(values));
+                }
+                _ => {
+                    // Some iterations printed nothing (or garbage), so the
+                    // stream can't be matched to offsets: go one by one.
+                    for &offset in chunk {
+                        let res = self.cmd_at(offset, &cmd)?;
+                        if res.trim().is_empty() {
+                            continue;
+                        }
+                        let value = parse_json(&cmd, res.into_bytes())?;
+                        results.insert(offset, value);
+                    }
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Collects the `key` field of every entry in a JSON listing command.
+    fn listing_offsets(&mut self, cmd: &str, key: &str) -> Result<Vec<Addr>, Error> {
+        let res = self.cmd(cmd)?;
+        if res.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let json: Value = parse_json(cmd, res.into_bytes())?;
+        let offsets = json
+            .as_array()
+            .ok_or_else(|| Error::UnexpectedOutput(cmd.to_string()))?
+            .iter()
+            .filter_map(|e| serde_json::from_value::<Addr>(e[key].clone()).ok())
+            .collect();
+        Ok(offsets)
+    }
+}
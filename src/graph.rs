@@ -0,0 +1,68 @@
+//! Exporting r2's graphs (`ag*`) for reports.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+
+/// Which graph to export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphKind {
+    /// Basic blocks of the function at the current seek (`agf`).
+    Cfg,
+    /// Calls between all the functions (`agC`).
+    CallGraph,
+    /// Data references of all the functions (`agA`).
+    DataRefs,
+}
+
+/// Output format of an exported graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Gml,
+    Json,
+}
+
+impl GraphKind {
+    fn cmd(self) -> &'static str {
+        match self {
+            GraphKind::Cfg => "agf",
+            GraphKind::CallGraph => "agC",
+            GraphKind::DataRefs => "agA",
+        }
+    }
+}
+
+impl GraphFormat {
+    fn suffix(self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "d",
+            GraphFormat::Gml => "g",
+            GraphFormat::Json => "j",
+        }
+    }
+}
+
+impl R2Pipe {
+    /// Exports a graph, as printed by r2 in `format`.
+    ///
+    /// Fails with `Error::EmptyResponse` when r2 prints nothing, e.g. for a
+    /// CFG with no function at the current seek or before analysis.
+    ///
+    /// ```no_run
+    /// use r2pipe::graph::{GraphFormat, GraphKind};
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.cmd("aa")?;
+    /// let dot = r2.with_seek(0x1000u64, |r2| r2.export_graph(GraphKind::Cfg, GraphFormat::Dot))?;
+    /// std::fs::write("cfg.dot", dot)?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn export_graph(&mut self, kind: GraphKind, format: GraphFormat) -> Result<String, Error> {
+        let cmd = format!("{}{}", kind.cmd(), format.suffix());
+        let res = self.cmd(&cmd)?;
+        if res.trim().is_empty() {
+            return Err(self.with_session(Error::EmptyResponse(cmd)));
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,112 @@
+//! Correcting the analysis where it guesses wrong, with hints (`ah`).
+//!
+//! A hint overrides what the analysis and the disassembler decide at one
+//! address: the size or type of an instruction, the architecture and bits
+//! of the code there (data in code, ARM and Thumb mixed)... It applies to
+//! the instructions disassembled and analyzed from then on, `af`/`aaa`
+//! again to redo those already analyzed.
+
+use crate::error::Error;
+use crate::plugins::PluginKind;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Hint};
+
+/// The bits `ahb` accepts.
+const BITS: &[u64] = &[8, 16, 32, 64];
+
+/// The bases `ahi` accepts as numbers.
+const BASES: &[u32] = &[2, 8, 10, 16];
+
+impl R2Pipe {
+    /// The hints set so far (`ahj`).
+    pub fn hints(&mut self) -> Result<Vec<Hint>, Error> {
+        Ok(match self.cmd_deserialize("ahj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        })
+    }
+
+    /// The hints at `addr`, if any.
+    pub fn hint_at<A: Into<Addr>>(&mut self, addr: A) -> Result<Option<Hint>, Error> {
+        let addr = addr.into();
+        Ok(self.hints()?.into_iter().find(|h| h.addr == addr))
+    }
+
+    /// Makes the instruction at `addr` `size` bytes long (`ahs`), e.g. to
+    /// skip data between instructions.
+    pub fn hint_size<A: Into<Addr>>(&mut self, addr: A, size: u64) -> Result<(), Error> {
+        if size == 0 || size > 64 {
+            return Err(Error::InvalidCommand(format!(
+                "Invalid instruction size: {}",
+                size
+            )));
+        }
+        self.cmd_ok(&format!("ahs {} @ {}", size, addr.into()))
+    }
+
+    /// Disassembles the code from `addr` as `arch` (`aha`), which must be
+    /// an arch plugin of this r2.
+    pub fn hint_arch<A: Into<Addr>>(&mut self, addr: A, arch: &str) -> Result<(), Error> {
+        if arch.is_empty()
+            || !arch
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+        {
+            return Err(Error::InvalidCommand(format!("Invalid arch: {:?}", arch)));
+        }
+        self.require_plugin(PluginKind::Arch, arch)?;
+        self.cmd_ok(&format!("aha {} @ {}", arch, addr.into()))
+    }
+
+    /// Disassembles the code from `addr` with `bits` (`ahb`), e.g. 16 for
+    /// Thumb in ARM code.
+    pub fn hint_bits<A: Into<Addr>>(&mut self, addr: A, bits: u64) -> Result<(), Error> {
+        if !BITS.contains(&bits) {
+            return Err(Error::InvalidCommand(format!("Invalid bits: {}", bits)));
+        }
+        self.cmd_ok(&format!("ahb {} @ {}", bits, addr.into()))
+    }
+
+    /// Shows `opcode` as the disassembly of the instruction at `addr`
+    /// (`ahd`).
+    pub fn hint_opcode<A: Into<Addr>>(&mut self, addr: A, opcode: &str) -> Result<(), Error> {
+        if opcode.trim().is_empty() || opcode.contains(['"', '\n', '\0']) {
+            return Err(Error::InvalidCommand(format!(
+                "Invalid opcode: {:?}",
+                opcode
+            )));
+        }
+        self.cmd_ok(&format!("\"ahd {} @ {}\"", opcode.trim(), addr.into()))
+    }
+
+    /// Analyzes the instruction at `addr` as one of type `kind` (`aho`),
+    /// e.g. `call` or `nop`.
+    pub fn hint_type<A: Into<Addr>>(&mut self, addr: A, kind: &str) -> Result<(), Error> {
+        if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(Error::InvalidCommand(format!(
+                "Invalid instruction type: {:?}",
+                kind
+            )));
+        }
+        self.cmd_ok(&format!("aho {} @ {}", kind, addr.into()))
+    }
+
+    /// Shows the immediates of the instruction at `addr` in `base`, 2, 8,
+    /// 10 or 16 (`ahi`).
+    pub fn hint_immediate_base<A: Into<Addr>>(&mut self, addr: A, base: u32) -> Result<(), Error> {
+        if !BASES.contains(&base) {
+            return Err(Error::InvalidCommand(format!("Invalid base: {}", base)));
+        }
+        self.cmd_ok(&format!("ahi {} @ {}", base, addr.into()))
+    }
+
+    /// Removes the hints at `addr` (`ah-`).
+    pub fn remove_hints<A: Into<Addr>>(&mut self, addr: A) -> Result<(), Error> {
+        self.cmd_ok(&format!("ah- @ {}", addr.into()))
+    }
+
+    /// Removes every hint (`ah-*`).
+    pub fn clear_hints(&mut self) -> Result<(), Error> {
+        self.cmd_ok("ah-*")
+    }
+}
@@ -0,0 +1,73 @@
+//! The last commands run on a pipe, see `R2Pipe::set_history()`.
+
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+/// Bytes of each reply kept in the history.
+pub const HISTORY_EXCERPT: usize = 256;
+
+/// A command run on a pipe.
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub cmd: String,
+    /// When the command was sent.
+    pub at: SystemTime,
+    pub latency: Duration,
+    /// The first `HISTORY_EXCERPT` bytes of the reply, empty on errors.
+    pub response: String,
+    pub truncated: bool,
+    /// The error, if the command failed.
+    pub error: Option<String>,
+}
+
+/// Ring buffer of the last `capacity` commands.
+pub(crate) struct History {
+    capacity: usize,
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl History {
+    pub(crate) fn new(capacity: usize) -> History {
+        History {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Keeps `cmd`, whose reply is `len` bytes long, of which `res` may only
+    /// be the beginning.
+    pub(crate) fn push(
+        &mut self,
+        cmd: &str,
+        latency: Duration,
+        res: Result<&[u8], String>,
+        len: usize,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        let (response, truncated, error) = match res {
+            Ok(res) => {
+                let head = &res[..res.len().min(HISTORY_EXCERPT)];
+                let response = String::from_utf8_lossy(head).into_owned();
+                (response, len > HISTORY_EXCERPT, None)
+            }
+            Err(e) => (String::new(), false, Some(e)),
+        };
+        self.entries.push_back(HistoryEntry {
+            cmd: cmd.to_string(),
+            at: SystemTime::now() - latency,
+            latency,
+            response,
+            truncated,
+            error,
+        });
+    }
+
+    pub(crate) fn entries(&self) -> &VecDeque<HistoryEntry> {
+        &self.entries
+    }
+}
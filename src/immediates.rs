@@ -0,0 +1,110 @@
+//! Finding where constants are used, e.g. the magic numbers of crypto
+//! algorithms.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, BasicBlock, Instruction};
+
+use serde_derive::Deserialize;
+use std::ops::Range;
+
+/// Where a searched constant was found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImmediateHit {
+    /// An operand of an instruction of an analyzed function.
+    Code(Box<Instruction>),
+    /// Anywhere else, e.g. a table in the data.
+    Data(Addr),
+}
+
+impl ImmediateHit {
+    /// Of the instruction, or of the data.
+    pub fn addr(&self) -> Addr {
+        match *self {
+            ImmediateHit::Code(ref ins) => ins.offset,
+            ImmediateHit::Data(addr) => addr,
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct SearchHit {
+    offset: Addr,
+}
+
+impl R2Pipe {
+    /// Searches `value` (`/v4j`, or `/v8j` past 32 bits), in the ranges set
+    /// by `search.in`, and tells the hits in instructions from those in
+    /// data.
+    ///
+    /// Instructions are only recognized in analyzed functions, and when
+    /// their immediate operand is `value`.
+    ///
+    /// ```no_run
+    /// use r2pipe::immediates::ImmediateHit;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.cmd("aa")?;
+    /// // First constant of SHA-256.
+    /// for hit in r2.find_immediates(0x6a09e667)? {
+    ///     if let ImmediateHit::Code(ins) = hit {
+    ///         println!("{} {}", ins.offset, ins.disasm);
+    ///     }
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn find_immediates(&mut self, value: u64) -> Result<Vec<ImmediateHit>, Error> {
+        let width = if value > u64::from(u32::MAX) { 8 } else { 4 };
+        let found: Vec<SearchHit> = match self.cmd_deserialize(&format!("/v{}j {}", width, value)) {
+            Err(ref e) if e.is_empty_response() => return Ok(Vec::new()),
+            res => res?,
+        };
+        let mut hits: Vec<ImmediateHit> = Vec::new();
+        for SearchHit { offset } in found {
+            let hit = match self.instruction_with(offset, value)? {
+                Some(ins) => ImmediateHit::Code(Box::new(ins)),
+                None => ImmediateHit::Data(offset),
+            };
+            if !hits.contains(&hit) {
+                hits.push(hit);
+            }
+        }
+        Ok(hits)
+    }
+
+    /// Lazily disassembles `range`, yielding the instructions with an
+    /// immediate operand (`pdj`).
+    pub fn iter_immediates(
+        &mut self,
+        range: Range<Addr>,
+    ) -> impl Iterator<Item = Result<Instruction, Error>> + '_ {
+        self.iter_instructions(range.start, range.end)
+            .filter(|ins| ins.as_ref().map_or(true, |ins| ins.val.is_some()))
+    }
+
+    /// The instruction of a function covering `addr` with `value` as
+    /// operand.
+    fn instruction_with(&mut self, addr: Addr, value: u64) -> Result<Option<Instruction>, Error> {
+        let blocks: Vec<BasicBlock> = match self.cmd_deserialize(&format!("afbj @ {}", addr)) {
+            Ok(blocks) => blocks,
+            // Outside of any function.
+            Err(ref e) if matches!(e.root(), Error::EmptyResponse(_) | Error::Json { .. }) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+        let block = match blocks.into_iter().find(|b| b.contains(addr)) {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        for ins in self.iter_instructions(block.addr, Addr(block.addr.0 + block.size)) {
+            let ins = ins?;
+            if ins.offset <= addr && addr.0 < ins.offset.0 + ins.size {
+                let uses = ins.val == Some(value) || ins.ptr == Some(Addr(value));
+                return Ok(if uses { Some(ins) } else { None });
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,144 @@
+//! Typed information about the loaded binary.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{EntryPoint, FileHashes, Import, Info, Section, StringEntry, Symbol};
+
+use serde_derive::Deserialize;
+use std::fmt;
+
+/// Version of the r2 running the session, `-git` suffixes dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct R2Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl R2Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> R2Version {
+        R2Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for R2Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Strings {
+    List(Vec<StringEntry>),
+    Wrapped { strings: Vec<StringEntry> },
+}
+
+impl R2Pipe {
+    /// General information about the opened file and binary (`ij`).
+    pub fn bin_info(&mut self) -> Result<Info, Error> {
+        self.cmd_deserialize("ij")
+    }
+
+    /// The version of r2 (`?V`).
+    pub fn r2_version(&mut self) -> Result<R2Version, Error> {
+        let res = self.cmd("?V")?;
+        // `5.9.4 0 @ linux-x86-64`, older versions start with `radare2`.
+        res.split_whitespace()
+            .find(|t| t.starts_with(|c: char| c.is_ascii_digit()))
+            .and_then(parse_version)
+            .ok_or_else(|| Error::UnexpectedOutput(format!("Not a version: {:?}", res.trim())))
+    }
+
+    /// Sections of the binary (`iSj`).
+    pub fn sections(&mut self) -> Result<Vec<Section>, Error> {
+        self.cmd_deserialize("iSj")
+    }
+
+    /// Segments of the binary (`iSSj`), in the same model as sections.
+    pub fn segments(&mut self) -> Result<Vec<Section>, Error> {
+        match self.cmd_deserialize("iSSj") {
+            // Formats without segments print nothing.
+            Err(ref e) if e.is_empty_response() => Ok(Vec::new()),
+            res => res,
+        }
+    }
+
+    /// The entry points of the binary (`iej`).
+    pub fn entry_points(&mut self) -> Result<Vec<EntryPoint>, Error> {
+        self.entry_list("iej")
+    }
+
+    /// The constructors and destructors r2 knows of (`ieej`).
+    pub fn constructors(&mut self) -> Result<Vec<EntryPoint>, Error> {
+        self.entry_list("ieej")
+    }
+
+    fn entry_list(&mut self, cmd: &str) -> Result<Vec<EntryPoint>, Error> {
+        match self.cmd_deserialize(cmd) {
+            // Binaries without any print nothing.
+            Err(ref e) if e.is_empty_response() => Ok(Vec::new()),
+            res => res,
+        }
+    }
+
+    /// Imported symbols (`iij`).
+    pub fn imports(&mut self) -> Result<Vec<Import>, Error> {
+        self.cmd_deserialize("iij")
+    }
+
+    /// Symbols of the binary (`isj`).
+    pub fn symbols(&mut self) -> Result<Vec<Symbol>, Error> {
+        self.symbol_list("isj")
+    }
+
+    /// Exported symbols (`iEj`).
+    pub fn exports(&mut self) -> Result<Vec<Symbol>, Error> {
+        self.symbol_list("iEj")
+    }
+
+    fn symbol_list(&mut self, cmd: &str) -> Result<Vec<Symbol>, Error> {
+        match self.cmd_deserialize(cmd) {
+            // Stripped binaries print nothing.
+            Err(ref e) if e.is_empty_response() => Ok(Vec::new()),
+            res => res,
+        }
+    }
+
+    /// Hashes of the whole file (`itj`).
+    pub fn file_hashes(&mut self) -> Result<FileHashes, Error> {
+        self.cmd_deserialize("itj")
+    }
+
+    /// Strings in the data sections (`izj`).
+    pub fn strings(&mut self) -> Result<Vec<StringEntry>, Error> {
+        self.string_list("izj")
+    }
+
+    /// Strings in the whole file (`izzj`), which finds more but also more
+    /// noise.
+    pub fn all_strings(&mut self) -> Result<Vec<StringEntry>, Error> {
+        self.string_list("izzj")
+    }
+
+    fn string_list(&mut self, cmd: &str) -> Result<Vec<StringEntry>, Error> {
+        Ok(match self.cmd_deserialize(cmd)? {
+            Strings::List(list) => list,
+            Strings::Wrapped { strings } => strings,
+        })
+    }
+}
+
+/// Parses `5.9.4` or `5.9.5-git`, with the missing numbers as 0.
+fn parse_version(s: &str) -> Option<R2Version> {
+    let mut numbers = s.split(|c: char| !c.is_ascii_digit()).map(|n| n.parse());
+    Some(R2Version {
+        major: numbers.next()?.ok()?,
+        minor: numbers.next().and_then(Result::ok).unwrap_or(0),
+        patch: numbers.next().and_then(Result::ok).unwrap_or(0),
+    })
+}
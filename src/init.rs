@@ -0,0 +1,111 @@
+//! Code running before or after `main`: constructors, destructors and TLS
+//! callbacks, where malware likes to hide.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::Addr;
+
+/// Why a routine runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitKind {
+    /// The program entry point.
+    Entry,
+    /// `DT_INIT`, or a constructor r2 reports.
+    Init,
+    /// `DT_FINI`, or a destructor r2 reports.
+    Fini,
+    Preinit,
+    /// A PE TLS callback, run before the entry point.
+    Tls,
+    /// A pointer in `.init_array`, or Mach-O's `__mod_init_func`.
+    InitArray,
+    /// A pointer in `.fini_array`, or Mach-O's `__mod_term_func`.
+    FiniArray,
+    /// A pointer in `.preinit_array`.
+    PreinitArray,
+    Other(String),
+}
+
+/// A routine run at startup or exit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitRoutine {
+    pub addr: Addr,
+    pub kind: InitKind,
+}
+
+impl R2Pipe {
+    /// The entry points, constructors, destructors and TLS callbacks r2
+    /// knows of (`iej`, `ieej`), and the pointers of the init and fini
+    /// arrays.
+    ///
+    /// The arrays are read as they are mapped: in PIE binaries they are
+    /// only filled by relocations, which r2 applies unless `bin.relocs` is
+    /// off.
+    pub fn init_routines(&mut self) -> Result<Vec<InitRoutine>, Error> {
+        let mut routines: Vec<InitRoutine> = Vec::new();
+        let mut push = |routine: InitRoutine| {
+            if !routines.contains(&routine) {
+                routines.push(routine);
+            }
+        };
+        let mut entries = self.entry_points()?;
+        entries.extend(self.constructors()?);
+        for entry in entries {
+            push(InitRoutine {
+                addr: entry.vaddr,
+                kind: match entry.kind.as_str() {
+                    "program" => InitKind::Entry,
+                    "init" => InitKind::Init,
+                    "fini" => InitKind::Fini,
+                    "preinit" => InitKind::Preinit,
+                    "tls" => InitKind::Tls,
+                    _ => InitKind::Other(entry.kind),
+                },
+            });
+        }
+        let bin = self.bin_info()?.bin;
+        let width = (bin.bits / 8).clamp(4, 8) as usize;
+        for section in self.sections()? {
+            let kind = match array_kind(&section.name) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let data = self.read_bytes(section.vaddr, section.vsize as usize)?;
+            for ptr in data.chunks_exact(width) {
+                let mut bytes = [0; 8];
+                let addr = if bin.endian == "big" {
+                    bytes[8 - width..].copy_from_slice(ptr);
+                    u64::from_be_bytes(bytes)
+                } else {
+                    bytes[..width].copy_from_slice(ptr);
+                    u64::from_le_bytes(bytes)
+                };
+                // 0 and -1 are sentinels, not code.
+                let sentinel = if width == 4 {
+                    u64::from(u32::MAX)
+                } else {
+                    u64::MAX
+                };
+                if addr != 0 && addr != sentinel {
+                    push(InitRoutine {
+                        addr: Addr(addr),
+                        kind: kind.clone(),
+                    });
+                }
+            }
+        }
+        Ok(routines)
+    }
+}
+
+fn array_kind(section: &str) -> Option<InitKind> {
+    if section.ends_with(".preinit_array") {
+        Some(InitKind::PreinitArray)
+    } else if section.ends_with(".init_array") || section.ends_with("__mod_init_func") {
+        Some(InitKind::InitArray)
+    } else if section.ends_with(".fini_array") || section.ends_with("__mod_term_func") {
+        Some(InitKind::FiniArray)
+    } else {
+        None
+    }
+}
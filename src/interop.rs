@@ -0,0 +1,464 @@
+//! Taking the analysis of a session to other tools: a script redoing it in
+//! Ghidra or IDA, or JSON for anything else. And the other way around,
+//! applying names and types from elsewhere with `import_metadata()`.
+//!
+//! What is exported: the functions, the names r2 gave to other addresses,
+//! the comments and the C types. The scripts write addresses relative to
+//! the base address of the binary and rebase them on the image base of the
+//! tool, which often loads position independent code elsewhere. The JSON
+//! keeps r2's addresses, along with the base.
+
+use crate::cmd::Cmd;
+use crate::error::Error;
+use crate::flags::flag_name;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Comment, Flag, FunctionInfo};
+
+use serde_derive::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What `R2Pipe::export_analysis()` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteropFormat {
+    /// A Python script for Ghidra's script manager.
+    GhidraScript,
+    /// An IDAPython script, for `File > Script file...`.
+    IdaPython,
+    /// A document with a `base` and lists of `functions`, `names`,
+    /// `comments`, and the `types` as C.
+    Json,
+}
+
+/// Names and types to apply to a session, e.g. from a symbol server or an
+/// earlier session. Reads the `InteropFormat::Json` export.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(serde_derive::Serialize))]
+#[serde(default)]
+pub struct MetadataBundle {
+    /// The base address of the binary the addresses are for, rebased on
+    /// the base of the session. `None` to take them as they are.
+    pub base: Option<Addr>,
+    pub functions: Vec<NamedAddr>,
+    /// Names of other addresses, set as flags.
+    pub names: Vec<NamedAddr>,
+    pub comments: Vec<AddrComment>,
+    /// C declarations, e.g. `struct point { int x; int y; };`.
+    pub types: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(serde_derive::Serialize))]
+#[serde(default)]
+pub struct NamedAddr {
+    pub addr: Addr,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(serde_derive::Serialize))]
+#[serde(default)]
+pub struct AddrComment {
+    pub addr: Addr,
+    pub text: String,
+}
+
+/// Something of a `MetadataBundle` replacing what the session has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// The function at `addr` has another name than `auto` ones.
+    FunctionName {
+        addr: Addr,
+        current: String,
+        new: String,
+    },
+    /// A flag named `name` is at another address, and will be moved.
+    NameTaken {
+        name: String,
+        current: Addr,
+        new: Addr,
+    },
+    Comment {
+        addr: Addr,
+        current: String,
+        new: String,
+    },
+}
+
+/// What `R2Pipe::import_metadata()` did, or would do in a dry run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub conflicts: Vec<Conflict>,
+    /// Commands run to apply the bundle, or that would be in a dry run.
+    pub commands: usize,
+}
+
+/// Flags naming what the other tools find and name themselves.
+const NOISE: &[&str] = &[
+    "section.", "segment.", "str.", "reloc.", "case.", "switch.", "fcn.",
+];
+
+/// What is exported, at the addresses of r2.
+struct Analysis {
+    base: u64,
+    functions: Vec<FunctionInfo>,
+    names: Vec<Flag>,
+    comments: Vec<Comment>,
+    types: String,
+}
+
+impl R2Pipe {
+    /// Exports the analysis of the session in `format`, see the module
+    /// documentation.
+    ///
+    /// ```no_run
+    /// use r2pipe::interop::InteropFormat;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.analyze(r2pipe::AnalysisLevel::Auto)?;
+    /// let script = r2.export_analysis(InteropFormat::IdaPython)?;
+    /// std::fs::write("ls_r2.py", script)?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn export_analysis(&mut self, format: InteropFormat) -> Result<String, Error> {
+        let analysis = self.analysis()?;
+        Ok(match format {
+            InteropFormat::GhidraScript => analysis.ghidra_script(),
+            InteropFormat::IdaPython => analysis.ida_python(),
+            InteropFormat::Json => analysis.json(),
+        })
+    }
+
+    /// Applies the functions, names, comments and types of `bundle` to the
+    /// session, in a batch (see `cmd_batch()`), overwriting what it has.
+    ///
+    /// With `dry_run` nothing is changed, and the report tells what would
+    /// be overwritten.
+    ///
+    /// ```no_run
+    /// use r2pipe::interop::MetadataBundle;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let bundle: MetadataBundle = serde_json::from_str(&std::fs::read_to_string("ls.json")?)?;
+    /// let report = r2.import_metadata(&bundle, true)?;
+    /// if report.conflicts.is_empty() {
+    ///     r2.import_metadata(&bundle, false)?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn import_metadata(
+        &mut self,
+        bundle: &MetadataBundle,
+        dry_run: bool,
+    ) -> Result<ImportReport, Error> {
+        let rebase = match bundle.base {
+            Some(from) => Some((from, self.bin_info()?.bin.baddr)),
+            None => None,
+        };
+        let at = |addr: Addr| match rebase {
+            Some((from, to)) => Addr(addr.0.wrapping_sub(from.0).wrapping_add(to.0)),
+            None => addr,
+        };
+        let functions: BTreeMap<Addr, String> =
+            match self.cmd_deserialize::<Vec<FunctionInfo>>("aflj") {
+                Err(ref e) if e.is_empty_response() => BTreeMap::new(),
+                res => res?.into_iter().map(|f| (f.offset, f.name)).collect(),
+            };
+        let flags: BTreeMap<String, Addr> = match self.cmd_deserialize::<Vec<Flag>>("fj") {
+            Err(ref e) if e.is_empty_response() => BTreeMap::new(),
+            res => res?.into_iter().map(|f| (f.name, f.offset)).collect(),
+        };
+        let comments: BTreeMap<Addr, String> = self
+            .comments()?
+            .into_iter()
+            .map(|c| (c.offset, c.text))
+            .collect();
+
+        let mut conflicts = Vec::new();
+        let mut cmds = Vec::new();
+        for f in &bundle.functions {
+            let (addr, name) = (at(f.addr), flag_name(&f.name));
+            match functions.get(&addr) {
+                Some(current) if *current == name => continue,
+                Some(current) => {
+                    if !NOISE.iter().any(|p| current.starts_with(p)) {
+                        conflicts.push(Conflict::FunctionName {
+                            addr,
+                            current: current.clone(),
+                            new: name.clone(),
+                        });
+                    }
+                    cmds.push(Cmd::new("afn").arg(&name).at(addr));
+                }
+                None => cmds.push(Cmd::new("af").arg(&name).arg(addr)),
+            }
+        }
+        for n in &bundle.names {
+            let (addr, name) = (at(n.addr), flag_name(&n.name));
+            match flags.get(&name) {
+                Some(&current) if current == addr => continue,
+                Some(&current) => conflicts.push(Conflict::NameTaken {
+                    name: name.clone(),
+                    current,
+                    new: addr,
+                }),
+                None => {}
+            }
+            cmds.push(Cmd::new("f").arg(&name).arg(1).at(addr));
+        }
+        for c in &bundle.comments {
+            let addr = at(c.addr);
+            match comments.get(&addr) {
+                Some(current) if *current == c.text => continue,
+                Some(current) => {
+                    conflicts.push(Conflict::Comment {
+                        addr,
+                        current: current.clone(),
+                        new: c.text.clone(),
+                    });
+                    cmds.push(Cmd::new("CC-").at(addr));
+                }
+                None => {}
+            }
+            cmds.push(Cmd::new("CC").arg(&c.text).at(addr));
+        }
+        let mut cmds: Vec<String> = cmds.iter().map(Cmd::to_string).collect();
+        // `td` reads a single line, and quoted so `;` doesn't end it.
+        for decl in c_declarations(&bundle.types) {
+            if decl.contains(['"', '\0']) {
+                return Err(Error::InvalidCommand(
+                    "Types with quotes can't be declared with td".to_string(),
+                ));
+            }
+            cmds.push(format!("\"td {}\"", decl));
+        }
+        if dry_run {
+            return Ok(ImportReport {
+                conflicts,
+                commands: cmds.len(),
+            });
+        }
+        let cmds: Vec<&str> = cmds.iter().map(String::as_str).collect();
+        self.cmd_batch(&cmds)?;
+        Ok(ImportReport {
+            conflicts,
+            commands: cmds.len(),
+        })
+    }
+
+    fn analysis(&mut self) -> Result<Analysis, Error> {
+        let base = self.bin_info()?.bin.baddr.0;
+        let functions: Vec<FunctionInfo> = match self.cmd_deserialize("aflj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        let named: BTreeSet<u64> = functions.iter().map(|f| f.offset.0).collect();
+        let flags: Vec<Flag> = match self.cmd_deserialize("fj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        let names = flags
+            .into_iter()
+            .filter(|f| !named.contains(&f.offset.0) && f.offset.0 >= base)
+            .filter(|f| !NOISE.iter().any(|p| f.name.starts_with(p)))
+            .collect();
+        Ok(Analysis {
+            base,
+            functions,
+            names,
+            comments: self.comments()?,
+            types: self.cmd("tc")?,
+        })
+    }
+}
+
+/// The declarations of the C source `types`, without comments, each on a
+/// single line and ending with `;`.
+fn c_declarations(types: &str) -> Vec<String> {
+    let mut code = String::with_capacity(types.len());
+    let mut rest = types;
+    while let Some(i) = rest.find('/') {
+        code.push_str(&rest[..i]);
+        let after = &rest[i..];
+        rest = if after.starts_with("//") {
+            after.find('\n').map_or("", |end| &after[end..])
+        } else if let Some(comment) = after.strip_prefix("/*") {
+            code.push(' ');
+            comment.find("*/").map_or("", |end| &comment[end + 2..])
+        } else {
+            code.push('/');
+            &after[1..]
+        };
+    }
+    code.push_str(rest);
+
+    let mut decls = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in code.char_indices() {
+        match c {
+            '{' | '(' => depth += 1,
+            '}' | ')' => depth = depth.saturating_sub(1),
+            ';' if depth == 0 => {
+                decls.push(&code[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    decls.push(&code[start..]);
+    decls
+        .into_iter()
+        .map(|d| d.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|d| !d.is_empty())
+        .map(|d| d + ";")
+        .collect()
+}
+
+impl Analysis {
+    fn rva(&self, addr: u64) -> u64 {
+        addr.wrapping_sub(self.base)
+    }
+
+    /// The tables of the scripts, as Python literals.
+    fn python_tables(&self) -> String {
+        let functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|f| json!([self.rva(f.offset.0), f.name]))
+            .collect();
+        let names: Vec<_> = self
+            .names
+            .iter()
+            .map(|f| json!([self.rva(f.offset.0), f.name]))
+            .collect();
+        let comments: Vec<_> = self
+            .comments
+            .iter()
+            .map(|c| json!([self.rva(c.offset.0), c.text]))
+            .collect();
+        // JSON strings and integers are valid Python literals.
+        format!(
+            "FUNCTIONS = {}\nNAMES = {}\nCOMMENTS = {}\nTYPES = {}\n",
+            json!(functions),
+            json!(names),
+            json!(comments),
+            json!(self.types)
+        )
+    }
+
+    fn ghidra_script(&self) -> String {
+        format!(
+            r#"# -*- coding: utf-8 -*-
+# Analysis exported from radare2.
+# @category r2pipe
+from ghidra.app.util.cparser.C import CParser
+from ghidra.program.model.symbol import SourceType
+
+{tables}
+base = currentProgram.getImageBase()
+if TYPES.strip():
+    try:
+        CParser(currentProgram.getDataTypeManager()).parse(TYPES)
+    except Exception as e:
+        print("types: %s" % e)
+for rva, name in FUNCTIONS:
+    addr = base.add(rva)
+    f = getFunctionAt(addr) or createFunction(addr, name)
+    if f:
+        f.setName(name, SourceType.IMPORTED)
+for rva, name in NAMES:
+    createLabel(base.add(rva), name, True)
+for rva, text in COMMENTS:
+    setEOLComment(base.add(rva), text)
+"#,
+            tables = self.python_tables()
+        )
+    }
+
+    fn ida_python(&self) -> String {
+        format!(
+            r#"# -*- coding: utf-8 -*-
+# Analysis exported from radare2.
+import ida_funcs
+import ida_nalt
+import idc
+
+{tables}
+base = ida_nalt.get_imagebase()
+if TYPES.strip():
+    idc.parse_decls(TYPES, idc.PT_SILENT)
+for rva, name in FUNCTIONS:
+    ida_funcs.add_func(base + rva)
+    idc.set_name(base + rva, name, idc.SN_NOWARN | idc.SN_NOCHECK)
+for rva, name in NAMES:
+    idc.set_name(base + rva, name, idc.SN_NOWARN | idc.SN_NOCHECK)
+for rva, text in COMMENTS:
+    idc.set_cmt(base + rva, text, 0)
+"#,
+            tables = self.python_tables()
+        )
+    }
+
+    fn json(&self) -> String {
+        let functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|f| json!({"addr": f.offset.0, "name": f.name, "size": f.size}))
+            .collect();
+        let names: Vec<_> = self
+            .names
+            .iter()
+            .map(|f| json!({"addr": f.offset.0, "name": f.name}))
+            .collect();
+        let comments: Vec<_> = self
+            .comments
+            .iter()
+            .map(|c| json!({"addr": c.offset.0, "text": c.text}))
+            .collect();
+        json!({
+            "base": self.base,
+            "functions": functions,
+            "names": names,
+            "comments": comments,
+            "types": self.types,
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    #[test]
+    fn declarations_without_comments() {
+        let types = "// points\nstruct point { int x; // across\n int y; };\n\
+                     /* a; b */ typedef int id_t;\nint f(int a, int b);";
+        assert_eq!(
+            c_declarations(types),
+            vec![
+                "struct point { int x; int y; };",
+                "typedef int id_t;",
+                "int f(int a, int b);",
+            ]
+        );
+        assert!(c_declarations(" // nothing\n").is_empty());
+    }
+
+    #[test]
+    fn dry_run_counts_commands() {
+        let bundle = MetadataBundle {
+            functions: vec![NamedAddr {
+                addr: Addr(0x1000),
+                name: "parse".to_string(),
+            }],
+            types: "struct a { int x; }; // a\nstruct b { int y; };".to_string(),
+            ..Default::default()
+        };
+        let mut r2 = R2Pipe::from_commands(MockR2Pipe::new().lenient());
+        let report = r2.import_metadata(&bundle, true).unwrap();
+        assert!(report.conflicts.is_empty());
+        assert_eq!(report.commands, 3);
+    }
+}
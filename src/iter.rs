@@ -0,0 +1,284 @@
+//! Lazy iterators over potentially huge listings.
+
+use crate::cmd::Cmd;
+use crate::disasm::DisasmOptions;
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, FunctionInfo, Instruction};
+
+use serde_json::Deserializer;
+use std::collections::VecDeque;
+
+/// Number of functions fetched per round trip by `iter_functions()`.
+pub const DEFAULT_PAGE_SIZE: usize = 256;
+
+/// Number of instructions disassembled per round trip by `iter_instructions()`.
+pub const DEFAULT_CHUNK_SIZE: usize = 256;
+
+/// Iterator over the analyzed functions, fetching their details in pages.
+///
+/// Only the function offsets are read up front (`aflq`); the `afij` details
+/// are pulled `page_size` functions at a time as the iterator advances.
+pub struct FunctionIter<'a> {
+    pipe: &'a mut R2Pipe,
+    offsets: Vec<Addr>,
+    next: usize,
+    page_size: usize,
+    page: VecDeque<FunctionInfo>,
+}
+
+impl<'a> FunctionIter<'a> {
+    /// Total number of functions the iterator will visit.
+    pub fn total(&self) -> usize {
+        self.offsets.len()
+    }
+
+    fn fetch_page(&mut self) -> Result<(), Error> {
+        let end = (self.next + self.page_size).min(self.offsets.len());
+        let list = self.offsets[self.next..end]
+            .iter()
+            .map(|o| o.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.next = end;
+        let res = self.pipe.cmd(&format!("afij @@= {}", list))?;
+        // every iteration prints its own one-element array
+        for fns in Deserializer::from_str(&res).into_iter::<Vec<FunctionInfo>>() {
+            let fns = fns?;
+            self.page.extend(fns);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for FunctionIter<'a> {
+    type Item = Result<FunctionInfo, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.page.is_empty() {
+            if self.next >= self.offsets.len() {
+                return None;
+            }
+            if let Err(e) = self.fetch_page() {
+                // don't retry the same page forever
+                self.next = self.offsets.len();
+                return Some(Err(e));
+            }
+        }
+        self.page.pop_front().map(Ok)
+    }
+}
+
+/// Linear-sweep iterator over the instructions in an address range.
+///
+/// Instructions are disassembled in chunks (`pdj N @ addr`); each chunk
+/// starts right after the last instruction of the previous one, so no
+/// instruction is ever split between chunks.
+pub struct InstructionIter<'a> {
+    pipe: &'a mut R2Pipe,
+    cur: u64,
+    end: u64,
+    chunk_size: usize,
+    chunk: VecDeque<Instruction>,
+    opts: Option<DisasmOptions>,
+}
+
+impl<'a> InstructionIter<'a> {
+    /// Disassembles with `opts` rather than the configuration of the
+    /// session.
+    pub fn with_options(mut self, opts: DisasmOptions) -> InstructionIter<'a> {
+        self.opts = Some(opts);
+        self
+    }
+
+    fn fetch_chunk(&mut self) -> Result<(), Error> {
+        let cmd = match self.opts {
+            Some(ref opts) => opts
+                .apply(Cmd::new("pdj").arg(self.chunk_size).at(self.cur))
+                .to_string(),
+            None => format!("pdj {} @ 0x{:x}", self.chunk_size, self.cur),
+        };
+        let ins: Vec<Instruction> = self.pipe.cmd_deserialize(&cmd)?;
+        if ins.is_empty() {
+            // nothing readable here, stop instead of looping
+            self.cur = self.end;
+            return Ok(());
+        }
+        for i in ins {
+            if i.offset.0 < self.cur || i.offset.0 >= self.end {
+                self.cur = self.end;
+                break;
+            }
+            // invalid instructions may report a zero size, or a bogus one
+            // running past the end of the address space
+            self.cur = i.offset.0.checked_add(i.size.max(1)).unwrap_or(self.end);
+            self.chunk.push_back(i);
+            if self.cur >= self.end {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for InstructionIter<'a> {
+    type Item = Result<Instruction, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.chunk.is_empty() {
+            if self.cur >= self.end {
+                return None;
+            }
+            if let Err(e) = self.fetch_chunk() {
+                self.cur = self.end;
+                return Some(Err(e));
+            }
+        }
+        self.chunk.pop_front().map(Ok)
+    }
+}
+
+/// Iterator over the output of a command, `page_size` lines at a time.
+///
+/// Each page runs the command again with a line range (`cmd~:a..b`), so
+/// only a page of its output is read at once. The session shouldn't change
+/// in between, or pages may overlap or skip lines.
+pub struct PagedCmd<'a> {
+    pipe: &'a mut R2Pipe,
+    cmd: String,
+    next: usize,
+    total: usize,
+    page_size: usize,
+}
+
+impl<'a> PagedCmd<'a> {
+    /// Number of lines the command printed when the pages were counted.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+impl<'a> Iterator for PagedCmd<'a> {
+    type Item = Result<Vec<String>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.total {
+            return None;
+        }
+        let len = self.page_size.min(self.total - self.next);
+        let cmd = format!("{}~:{}..{}", self.cmd, self.next, self.next + len);
+        self.next += len;
+        match self.pipe.cmd(&cmd) {
+            // Whether r2 includes the end of the range depends on the
+            // version, so keep at most `len` lines.
+            Ok(res) => Some(Ok(res.lines().take(len).map(str::to_string).collect())),
+            Err(e) => {
+                self.next = self.total;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl R2Pipe {
+    /// Reads the output of `cmd`, a listing printing one item per line
+    /// (`afl`, `izq`, `/x ...`), `page_size` lines at a time.
+    ///
+    /// JSON listings are printed on a single line, see `iter_functions()`
+    /// or `JsonStream` for those.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// for page in r2.cmd_paged("izzq", 1000)? {
+    ///     for line in page? {
+    ///         println!("{}", line);
+    ///     }
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn cmd_paged(&mut self, cmd: &str, page_size: usize) -> Result<PagedCmd<'_>, Error> {
+        let cmd = cmd.trim();
+        if cmd.is_empty() || cmd.contains(['~', ';', '\n']) {
+            return Err(Error::InvalidCommand(format!(
+                "Can't page {:?}, it has its own grep or chain",
+                cmd
+            )));
+        }
+        let total = self.cmd(&format!("{}~?", cmd))?;
+        let total = total.trim().parse().map_err(|_| {
+            Error::UnexpectedOutput(format!("Not a line count: {:?}", total.trim()))
+        })?;
+        Ok(PagedCmd {
+            pipe: self,
+            cmd: cmd.to_string(),
+            next: 0,
+            total,
+            page_size: page_size.max(1),
+        })
+    }
+
+    /// Lazily disassembles the instructions in `start..end`.
+    pub fn iter_instructions<A: Into<Addr>>(&mut self, start: A, end: A) -> InstructionIter<'_> {
+        self.iter_instructions_chunked(start, end, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Same as `iter_instructions()`, disassembling `chunk_size` at a time.
+    pub fn iter_instructions_chunked<A: Into<Addr>>(
+        &mut self,
+        start: A,
+        end: A,
+        chunk_size: usize,
+    ) -> InstructionIter<'_> {
+        InstructionIter {
+            pipe: self,
+            cur: start.into().0,
+            end: end.into().0,
+            chunk_size: chunk_size.max(1),
+            chunk: VecDeque::new(),
+            opts: None,
+        }
+    }
+
+    /// Lazily iterates over the analyzed functions.
+    pub fn iter_functions(&mut self) -> Result<FunctionIter<'_>, Error> {
+        self.iter_functions_paged(DEFAULT_PAGE_SIZE)
+    }
+
+    /// Same as `iter_functions()`, fetching `page_size` functions at a time.
+    pub fn iter_functions_paged(&mut self, page_size: usize) -> Result<FunctionIter<'_>, Error> {
+        let res = self.cmd("aflq")?;
+        let offsets = res.lines().filter_map(|l| l.parse().ok()).collect();
+        Ok(FunctionIter {
+            pipe: self,
+            offsets,
+            next: 0,
+            page_size: page_size.max(1),
+            page: VecDeque::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    #[test]
+    fn instructions_up_to_the_end_of_memory() {
+        let start = u64::MAX - 3;
+        let mock = MockR2Pipe::new().expect(
+            &format!("pdj {} @ 0x{:x}", DEFAULT_CHUNK_SIZE, start),
+            &format!(
+                r#"[{{"offset": {}, "size": 2}}, {{"offset": {}, "size": 9}}]"#,
+                start,
+                start + 2
+            ),
+        );
+        let mut r2 = R2Pipe::from_commands(mock);
+        let offsets: Vec<u64> = r2
+            .iter_instructions(start, u64::MAX)
+            .map(|i| i.unwrap().offset.0)
+            .collect();
+        assert_eq!(offsets, [start, start + 2]);
+    }
+}
@@ -0,0 +1,60 @@
+//! The jump tables r2 recovered, so CFG consumers get the targets of
+//! indirect branches without decoding the tables again.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, BasicBlock, SwitchCase};
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// A switch of a function and its table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpTable {
+    /// The indirect branch.
+    pub branch: Addr,
+    /// Start of the table, when the branch reads it directly, e.g.
+    /// `jmp qword [rax*8 + 0x4020]`. Position independent code usually
+    /// loads the entry before branching, leaving this unknown.
+    pub table: Option<Addr>,
+    /// Bytes per entry, when `table` is known.
+    pub entry_size: Option<u64>,
+    pub min_val: u64,
+    pub max_val: u64,
+    /// Value of the default case.
+    pub default_val: u64,
+    pub cases: Vec<SwitchCase>,
+}
+
+/// The fields of `aoj` telling where the branch reads its target from.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BranchOp {
+    ptr: Option<Addr>,
+    // A size in bytes, or a bool on some versions.
+    refptr: Value,
+}
+
+impl R2Pipe {
+    /// The switches of the function at `addr` (`afbj`), with the table
+    /// each branch reads (`aoj`).
+    pub fn jump_tables<A: Into<Addr>>(&mut self, addr: A) -> Result<Vec<JumpTable>, Error> {
+        let blocks: Vec<BasicBlock> = self.cmd_deserialize(&format!("afbj @ {}", addr.into()))?;
+        let mut tables = Vec::new();
+        for switch in blocks.into_iter().filter_map(|b| b.switch_op) {
+            let ops: Vec<BranchOp> = self.cmd_deserialize(&format!("aoj 1 @ {}", switch.addr))?;
+            let op = ops.into_iter().next().unwrap_or_default();
+            let table = op.ptr.filter(|p| p.0 != 0);
+            tables.push(JumpTable {
+                branch: switch.addr,
+                table,
+                entry_size: table.and(op.refptr.as_u64()).filter(|&n| n > 0),
+                min_val: switch.min_val,
+                max_val: switch.max_val,
+                default_val: switch.def_val,
+                cases: switch.cases,
+            });
+        }
+        Ok(tables)
+    }
+}
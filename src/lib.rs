@@ -49,14 +49,272 @@
 //!
 //! The crate offers various methods to interact with r2pipe, eg. via process (multi-threadable), http or tcp.
 //! Check the examples/ dir for more complete examples.
+//!
+//! The most commonly used items can be imported at once with
+//! `use r2pipe::prelude::*;`.
 
 #![doc(html_root_url = "https://radare.github.io/r2pipe.rs/")]
 
+#[cfg(feature = "pipe")]
 #[macro_use]
 pub mod r2pipe;
+#[cfg(feature = "pipe")]
+pub mod abi;
+#[cfg(feature = "pipe")]
+pub mod addrspace;
+#[cfg(feature = "pipe")]
+pub mod analysis;
+#[cfg(feature = "pipe")]
+pub mod api;
+#[cfg(feature = "pipe")]
+pub mod audit;
+#[cfg(feature = "pipe")]
+pub mod backend;
+#[cfg(feature = "pipe")]
+pub mod bytes;
+#[cfg(feature = "pipe")]
+pub mod bytesig;
+#[cfg(feature = "pipe")]
+pub mod cache;
+#[cfg(feature = "pipe")]
+pub mod capabilities;
+pub mod cmd;
+#[cfg(feature = "pipe")]
+pub mod codesign;
+#[cfg(feature = "pipe")]
+pub mod config;
+#[cfg(feature = "pipe")]
+pub mod coverage;
+#[cfg(feature = "pipe")]
+pub mod debug;
+#[cfg(feature = "pipe")]
+pub mod debuginfo;
+#[cfg(feature = "pipe")]
+pub mod decompiler;
+#[cfg(feature = "pipe")]
+pub mod demangle;
+#[cfg(feature = "pipe")]
+pub mod diff;
+#[cfg(feature = "pipe")]
+pub mod disasm;
+#[cfg(feature = "pipe")]
+pub mod entropy;
+pub mod error;
+#[cfg(feature = "pipe")]
+pub mod eval;
+#[cfg(feature = "pipe")]
+pub mod events;
+#[cfg(feature = "pipe")]
+pub mod extract;
+#[cfg(feature = "pipe")]
+pub mod fat;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "pipe")]
+pub mod files;
+#[cfg(feature = "pipe")]
+pub mod fingerprint;
+#[cfg(feature = "pipe")]
+pub mod flags;
+#[cfg(feature = "pipe")]
+pub mod foreach;
+#[cfg(feature = "pipe")]
+pub mod graph;
+#[cfg(feature = "pipe")]
+pub mod hints;
+#[cfg(feature = "pipe")]
+pub mod history;
+#[cfg(feature = "pipe")]
+pub mod immediates;
+#[cfg(feature = "pipe")]
+pub mod info;
+#[cfg(feature = "pipe")]
+pub mod init;
+#[cfg(feature = "pipe")]
+pub mod interop;
+#[cfg(feature = "pipe")]
+pub mod iter;
+#[cfg(feature = "pipe")]
+pub mod jumptables;
+#[cfg(feature = "pipe")]
+pub mod metrics;
+#[cfg(feature = "pipe")]
+pub mod mock;
+#[cfg(feature = "pipe")]
+pub mod overlay;
+#[cfg(feature = "pipe")]
+pub mod plugins;
+#[cfg(feature = "pipe")]
+pub mod policy;
+#[cfg(feature = "pipe")]
+pub mod pool;
+pub mod prelude;
+#[cfg(feature = "pipe")]
+pub mod prototypes;
+#[cfg(feature = "pipe")]
 pub mod r2;
+#[cfg(feature = "pipe")]
+pub mod raw;
+#[cfg(feature = "pipe")]
+pub mod refs;
+#[cfg(feature = "pipe")]
+pub mod repl;
+#[cfg(feature = "pipe")]
+pub mod replay;
+#[cfg(feature = "pipe")]
+pub mod retry;
+#[cfg(feature = "pipe")]
+pub mod rop;
+#[cfg(feature = "pipe")]
+pub mod salvage;
+#[cfg(feature = "pipe")]
+pub mod sdb;
+#[cfg(feature = "pipe")]
+pub mod seek;
+#[cfg(feature = "pipe")]
+pub mod session;
+#[cfg(feature = "pipe")]
+pub mod shared;
+#[cfg(feature = "pipe")]
+pub mod shellcode;
+#[cfg(feature = "pipe")]
+pub mod signatures;
+#[cfg(feature = "pipe")]
+pub mod snapshot;
+#[cfg(feature = "pipe")]
+pub mod stack;
+#[cfg(feature = "pipe")]
+pub mod stats;
+#[cfg(feature = "pipe")]
+pub mod stream;
+#[cfg(feature = "pipe")]
+pub mod strings;
+pub mod structs;
+#[cfg(feature = "pipe")]
+pub mod tasks;
+#[cfg(feature = "pipe")]
+pub mod threads;
+#[cfg(feature = "pipe")]
+pub mod throttle;
+#[cfg(feature = "pipe")]
+pub mod tools;
+#[cfg(feature = "pipe")]
+mod trace;
+#[cfg(feature = "pipe")]
+pub mod warmstart;
+#[cfg(feature = "pipe")]
+pub mod workspace;
+#[cfg(feature = "pipe")]
+pub mod yara;
 
 // Rexport to bring it out one module.
+pub use self::cmd::Cmd;
+pub use self::error::Error;
+pub use self::structs::{parse_r2_number, parse_signature, parse_size};
+pub use self::structs::{
+    Addr, BasicBlock, BinInfo, BinObject, Breakpoint, CallingConvention, Comment, CoreInfo,
+    EntryPoint, FileHashes, Flag, Frame, FunctionInfo, HeapChunk, Hint, Import, Info, Instruction,
+    IoMap, IoPlugin, LineInfo, OpenFile, Param, Plugin, Resource, Section, Signature, StringEntry,
+    SubBin, SwitchCase, SwitchOp, Symbol, TracePoint,
+};
+
+#[cfg(feature = "pipe")]
+pub use self::addrspace::AddressSpace;
+#[cfg(feature = "pipe")]
+pub use self::analysis::{AnalysisLevel, AnalysisProgress, AnalysisStatus};
+#[cfg(feature = "pipe")]
+pub use self::api::R2Api;
+#[cfg(feature = "pipe")]
+pub use self::audit::AuditLog;
+#[cfg(feature = "pipe")]
+pub use self::backend::Backend;
+#[cfg(feature = "pipe")]
+pub use self::bytesig::ByteSignature;
+#[cfg(feature = "pipe")]
+pub use self::cache::CachedPipe;
+#[cfg(feature = "pipe")]
+pub use self::capabilities::{CapabilityReport, Category};
+#[cfg(feature = "pipe")]
+pub use self::config::ConfigGuard;
+#[cfg(feature = "pipe")]
+pub use self::decompiler::DecompilerBackend;
+#[cfg(feature = "pipe")]
+pub use self::demangle::Lang;
+#[cfg(feature = "pipe")]
+pub use self::diff::R2Diff;
+#[cfg(feature = "pipe")]
+pub use self::disasm::{AsmSyntax, DisasmOptions};
+#[cfg(feature = "pipe")]
+pub use self::events::{SessionEvent, SessionWatcher};
+#[cfg(feature = "pipe")]
+pub use self::flags::FlagFormat;
+#[cfg(feature = "pipe")]
+pub use self::graph::{GraphFormat, GraphKind};
+#[cfg(feature = "pipe")]
+pub use self::history::HistoryEntry;
+#[cfg(feature = "pipe")]
+pub use self::immediates::ImmediateHit;
+#[cfg(feature = "pipe")]
+pub use self::info::R2Version;
+#[cfg(feature = "pipe")]
+pub use self::interop::{InteropFormat, MetadataBundle};
+#[cfg(feature = "pipe")]
+pub use self::iter::{FunctionIter, InstructionIter, PagedCmd};
+#[cfg(feature = "pipe")]
+pub use self::metrics::FunctionMetrics;
+#[cfg(feature = "pipe")]
+pub use self::mock::MockR2Pipe;
+#[cfg(feature = "pipe")]
+pub use self::plugins::PluginKind;
+#[cfg(feature = "pipe")]
+pub use self::policy::CommandPolicy;
+#[cfg(feature = "pipe")]
+pub use self::pool::R2Pool;
+#[cfg(feature = "pipe")]
 pub use self::r2::R2;
+#[cfg(feature = "pipe")]
 pub use self::r2pipe::R2Pipe;
+#[cfg(feature = "pipe")]
+pub use self::r2pipe::R2PipeCommands;
+#[cfg(feature = "pipe")]
+pub use self::r2pipe::R2PipeHttpOptions;
+#[cfg(feature = "pipe")]
 pub use self::r2pipe::R2PipeSpawnOptions;
+#[cfg(feature = "pipe")]
+pub use self::r2pipe::ReadyInfo;
+#[cfg(feature = "pipe")]
+pub use self::r2pipe::Utf8Policy;
+#[cfg(feature = "pipe")]
+pub use self::raw::{MapSpec, RawOptions};
+#[cfg(feature = "pipe")]
+pub use self::replay::{FixturePipe, Recorder, ReplayPipe};
+#[cfg(feature = "pipe")]
+pub use self::retry::RetryPolicy;
+#[cfg(feature = "pipe")]
+pub use self::salvage::Salvaged;
+#[cfg(feature = "pipe")]
+pub use self::sdb::SdbNamespace;
+#[cfg(feature = "pipe")]
+pub use self::seek::SeekGuard;
+#[cfg(feature = "pipe")]
+pub use self::session::{LifecycleEvent, LifecycleHook, SessionId};
+#[cfg(feature = "pipe")]
+pub use self::shared::SharedR2Pipe;
+#[cfg(feature = "pipe")]
+pub use self::signatures::{FlirtResult, SimilarFunction};
+#[cfg(feature = "pipe")]
+pub use self::snapshot::SessionState;
+#[cfg(feature = "pipe")]
+pub use self::stats::PipeStats;
+#[cfg(feature = "pipe")]
+pub use self::stream::JsonStream;
+#[cfg(feature = "pipe")]
+pub use self::tasks::{TaskId, TaskState};
+#[cfg(feature = "pipe")]
+pub use self::threads::R2Threads;
+#[cfg(feature = "pipe")]
+pub use self::throttle::RateLimiter;
+#[cfg(feature = "pipe")]
+pub use self::tools::R2Tools;
+#[cfg(feature = "pipe")]
+pub use self::workspace::Workspace;
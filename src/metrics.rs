@@ -0,0 +1,132 @@
+//! Per-function metrics for every analyzed function, in bulk.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::Addr;
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// Size, shape and connectivity of a function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionMetrics {
+    pub addr: Addr,
+    pub name: String,
+    /// Bytes from the lowest to the highest address of the function.
+    pub size: u64,
+    /// Bytes of its basic blocks only.
+    pub realsz: u64,
+    pub nbbs: u64,
+    pub ninstrs: u64,
+    pub edges: u64,
+    /// Cyclomatic complexity.
+    pub cc: u64,
+    /// Number of functions calling this one.
+    pub indegree: u64,
+    /// Number of functions this one calls.
+    pub outdegree: u64,
+    /// Code references to the function.
+    pub code_xrefs: u64,
+    /// Data references to the function, e.g. from pointer tables.
+    pub data_xrefs: u64,
+    /// Calls and jumps out of the function.
+    pub call_refs: u64,
+    /// References from the function to data.
+    pub data_refs: u64,
+}
+
+/// The fields of `aflj` and `afij`, some of which only recent versions
+/// print.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RawMetrics {
+    offset: Addr,
+    name: String,
+    size: u64,
+    realsz: u64,
+    nbbs: u64,
+    ninstrs: u64,
+    edges: u64,
+    cc: u64,
+    indegree: Option<u64>,
+    outdegree: Option<u64>,
+    codexrefs: Option<Vec<Value>>,
+    dataxrefs: Option<Vec<Value>>,
+    callrefs: Option<Vec<Value>>,
+    datarefs: Option<Vec<Value>>,
+}
+
+impl RawMetrics {
+    fn is_complete(&self) -> bool {
+        self.indegree.is_some() && self.outdegree.is_some() && self.codexrefs.is_some()
+    }
+}
+
+impl From<RawMetrics> for FunctionMetrics {
+    fn from(raw: RawMetrics) -> FunctionMetrics {
+        let count = |refs: Option<Vec<Value>>| refs.map_or(0, |r| r.len() as u64);
+        FunctionMetrics {
+            addr: raw.offset,
+            name: raw.name,
+            size: raw.size,
+            realsz: raw.realsz,
+            nbbs: raw.nbbs,
+            ninstrs: raw.ninstrs,
+            edges: raw.edges,
+            cc: raw.cc,
+            indegree: raw.indegree.unwrap_or(0),
+            outdegree: raw.outdegree.unwrap_or(0),
+            code_xrefs: count(raw.codexrefs),
+            data_xrefs: count(raw.dataxrefs),
+            call_refs: count(raw.callrefs),
+            data_refs: count(raw.datarefs),
+        }
+    }
+}
+
+impl R2Pipe {
+    /// The metrics of every analyzed function, sorted by address.
+    ///
+    /// Everything comes from a single `aflj` on recent versions of r2. The
+    /// functions it lists without their degrees or xrefs are completed
+    /// with `afij`, hundreds of them per command (`@@=`), so the number of
+    /// round trips doesn't grow with the number of functions.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.cmd("aa")?;
+    /// for m in r2.function_metrics()? {
+    ///     println!("{},{},{},{}", m.name, m.cc, m.nbbs, m.indegree);
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn function_metrics(&mut self) -> Result<Vec<FunctionMetrics>, Error> {
+        let mut raw: Vec<RawMetrics> = match self.cmd_deserialize("aflj") {
+            // Nothing analyzed yet.
+            Err(ref e) if e.is_empty_response() => return Ok(Vec::new()),
+            res => res?,
+        };
+        let incomplete: Vec<Addr> = raw
+            .iter()
+            .filter(|f| !f.is_complete())
+            .map(|f| f.offset)
+            .collect();
+        if !incomplete.is_empty() {
+            let details = self.cmdj_foreach("afij", &incomplete)?;
+            for f in raw.iter_mut().filter(|f| !f.is_complete()) {
+                // afij prints a one-element array.
+                let info = details
+                    .get(&f.offset)
+                    .and_then(|v| v.get(0))
+                    .map(|v| serde_json::from_value::<RawMetrics>(v.clone()))
+                    .transpose()?;
+                if let Some(info) = info {
+                    *f = info;
+                }
+            }
+        }
+        let mut metrics: Vec<FunctionMetrics> = raw.into_iter().map(Into::into).collect();
+        metrics.sort_by_key(|m| m.addr);
+        Ok(metrics)
+    }
+}
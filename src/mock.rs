@@ -0,0 +1,168 @@
+//! A fake pipe for testing code written against `R2PipeCommands`, or
+//! against the typed API once wrapped with `R2Pipe::from_commands()`.
+
+use crate::error::Error;
+use crate::r2pipe::R2PipeCommands;
+
+use std::collections::{HashMap, VecDeque};
+
+/// Answers commands with canned replies, without r2.
+///
+/// Replies registered with `expect()` must be asked for in that order;
+/// those registered with `on()` are given every time the command is run.
+/// Any other command fails, unless the mock is `lenient()`, in which case
+/// it prints nothing.
+///
+/// ```
+/// use r2pipe::{MockR2Pipe, R2PipeCommands};
+///
+/// let mut r2 = MockR2Pipe::new()
+///     .on("?V", "5.8.8")
+///     .expect("s 0x1000", "")
+///     .expect("pdj 1", r#"[{"offset":4096}]"#);
+/// r2.cmd("s 0x1000").unwrap();
+/// assert_eq!(r2.cmd("?V").unwrap(), "5.8.8");
+/// assert_eq!(r2.cmdj("pdj 1").unwrap()[0]["offset"], 4096);
+/// assert!(r2.cmd("aaa").is_err());
+/// r2.verify().unwrap();
+/// ```
+#[derive(Default)]
+pub struct MockR2Pipe {
+    expected: VecDeque<(String, Result<String, String>)>,
+    stubs: HashMap<String, Result<String, String>>,
+    lenient: bool,
+    calls: Vec<String>,
+    closed: bool,
+}
+
+impl MockR2Pipe {
+    pub fn new() -> MockR2Pipe {
+        MockR2Pipe::default()
+    }
+
+    /// Expects `cmd` after the previously expected commands, replying `res`.
+    pub fn expect(mut self, cmd: &str, res: &str) -> MockR2Pipe {
+        self.expected
+            .push_back((cmd.trim().to_string(), Ok(res.to_string())));
+        self
+    }
+
+    /// Expects `cmd` after the previously expected commands, failing with
+    /// `Error::Other(message)`.
+    pub fn expect_err(mut self, cmd: &str, message: &str) -> MockR2Pipe {
+        self.expected
+            .push_back((cmd.trim().to_string(), Err(message.to_string())));
+        self
+    }
+
+    /// Replies `res` to `cmd` whenever it is run.
+    pub fn on(mut self, cmd: &str, res: &str) -> MockR2Pipe {
+        self.stubs
+            .insert(cmd.trim().to_string(), Ok(res.to_string()));
+        self
+    }
+
+    /// Replies nothing to unknown commands instead of failing.
+    pub fn lenient(mut self) -> MockR2Pipe {
+        self.lenient = true;
+        self
+    }
+
+    /// Commands run so far, in order.
+    pub fn calls(&self) -> &[String] {
+        &self.calls
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Fails if some expected commands were never run.
+    pub fn verify(&self) -> Result<(), Error> {
+        match self.expected.front() {
+            Some((cmd, _)) => Err(Error::Other(format!(
+                "{} expected command(s) not run, starting with `{}`",
+                self.expected.len(),
+                cmd
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl R2PipeCommands for MockR2Pipe {
+    fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let cmd = cmd.trim();
+        self.calls.push(cmd.to_string());
+        if self.closed {
+            return Err(Error::ProcessExited {
+                status: None,
+                stderr: String::new(),
+            });
+        }
+        let res = match self.expected.front() {
+            Some((expected, _)) if expected == cmd => self.expected.pop_front().map(|(_, res)| res),
+            _ => self.stubs.get(cmd).cloned(),
+        };
+        match res {
+            Some(res) => res.map_err(Error::Other),
+            None if self.lenient => Ok(String::new()),
+            None => Err(Error::Other(match self.expected.front() {
+                Some((expected, _)) => format!("Unexpected `{}`, expected `{}`", cmd, expected),
+                None => format!("Unexpected `{}`", cmd),
+            })),
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r2pipe::R2Pipe;
+
+    #[test]
+    fn expectations_in_order() {
+        let mut r2 = MockR2Pipe::new()
+            .expect("s 0x10", "")
+            .expect_err("pdf", "no function")
+            .expect("pdf", "ok");
+        assert!(r2.cmd("pdf").is_err());
+        assert_eq!(r2.cmd(" s 0x10\n").unwrap(), "");
+        assert!(matches!(r2.cmd("pdf"), Err(Error::Other(ref e)) if e == "no function"));
+        assert!(r2.verify().is_err());
+        assert_eq!(r2.cmd("pdf").unwrap(), "ok");
+        r2.verify().unwrap();
+        assert_eq!(r2.calls(), ["pdf", "s 0x10", "pdf", "pdf"]);
+    }
+
+    #[test]
+    fn stubs_and_lenience() {
+        let mut r2 = MockR2Pipe::new().on("?V", "5.8.8").expect("?V", "5.9.0");
+        assert_eq!(r2.cmd("?V").unwrap(), "5.9.0");
+        assert_eq!(r2.cmd("?V").unwrap(), "5.8.8");
+        assert_eq!(r2.cmd("?V").unwrap(), "5.8.8");
+        assert!(r2.cmd("aaa").is_err());
+
+        let mut r2 = MockR2Pipe::new().lenient();
+        assert_eq!(r2.cmd("aaa").unwrap(), "");
+        r2.close();
+        assert!(r2.is_closed());
+        assert!(matches!(r2.cmd("aaa"), Err(Error::ProcessExited { .. })));
+    }
+
+    #[test]
+    fn answers_the_typed_api() {
+        let mock = MockR2Pipe::new().expect("aflj", r#"[{"offset": 4096, "name": "main"}]"#);
+        let mut r2 = R2Pipe::from_commands(mock);
+        let fns: Vec<crate::structs::FunctionInfo> = r2.cmd_deserialize("aflj").unwrap();
+        assert_eq!(fns[0].name, "main");
+        assert_eq!(fns[0].offset, crate::structs::Addr(0x1000));
+        let e = r2.cmd("aflj").unwrap_err();
+        assert!(matches!(e.root(), Error::Other(_)));
+        assert!(e.session().is_some());
+    }
+}
@@ -0,0 +1,115 @@
+//! Data appended to a binary past what its headers describe, as
+//! installers, self-extracting archives and droppers do.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::Addr;
+
+use std::io::{self, Read};
+use std::ops::Range;
+
+/// Bytes read per command.
+const READ_CHUNK: u64 = 1 << 20;
+
+/// Reads file offsets of the opened file, whatever `io.va` is.
+pub struct OffsetReader<'a> {
+    r2: &'a mut R2Pipe,
+    pos: u64,
+    end: u64,
+}
+
+impl<'a> OffsetReader<'a> {
+    /// The offsets left to read.
+    pub fn remaining(&self) -> Range<Addr> {
+        Addr(self.pos)..Addr(self.end)
+    }
+}
+
+impl<'a> Read for OffsetReader<'a> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let len = (out.len() as u64).min(self.end - self.pos).min(READ_CHUNK);
+        if len == 0 {
+            return Ok(0);
+        }
+        let pos = self.pos;
+        let data = self
+            .r2
+            .with_config("io.va", "false", |r2| r2.read_bytes(pos, len as usize))
+            .map_err(io::Error::other)?;
+        out[..data.len()].copy_from_slice(&data);
+        self.pos += data.len() as u64;
+        Ok(data.len())
+    }
+}
+
+impl R2Pipe {
+    /// The file offsets past the end of the last section and segment, if
+    /// the file goes on after them.
+    ///
+    /// The section header table of ELF files, which r2 doesn't list, is
+    /// counted as part of the binary. PE certificates are not, they are
+    /// appended after the sections and show up here.
+    pub fn overlay(&mut self) -> Result<Option<Range<Addr>>, Error> {
+        let size = self.eval("$s")?;
+        let mut end = self.elf_shdrs_end()?;
+        for s in self.sections()?.into_iter().chain(self.segments()?) {
+            if s.size > 0 {
+                end = end.max(s.paddr.0 + s.size);
+            }
+        }
+        // A binary r2 maps nothing of is not one it understands.
+        if end == 0 || end >= size {
+            return Ok(None);
+        }
+        Ok(Some(Addr(end)..Addr(size)))
+    }
+
+    /// Reads the bytes of `overlay()`.
+    ///
+    /// ```no_run
+    /// use std::io;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("setup.exe", None).unwrap();
+    /// if let Some(mut overlay) = r2.overlay_reader()? {
+    ///     io::copy(&mut overlay, &mut std::fs::File::create("overlay.bin")?)?;
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn overlay_reader(&mut self) -> Result<Option<OffsetReader<'_>>, Error> {
+        Ok(self.overlay()?.map(move |range| self.offset_reader(range)))
+    }
+
+    /// Reads the file offsets in `range`.
+    pub fn offset_reader(&mut self, range: Range<Addr>) -> OffsetReader<'_> {
+        OffsetReader {
+            r2: self,
+            pos: range.start.0,
+            end: range.end.0.max(range.start.0),
+        }
+    }
+
+    /// End of the section header table when the file is an ELF, else 0.
+    fn elf_shdrs_end(&mut self) -> Result<u64, Error> {
+        let h = self.with_config("io.va", "false", |r2| r2.read_bytes(0u64, 0x40))?;
+        if h.len() < 0x34 || !h.starts_with(b"\x7fELF") {
+            return Ok(0);
+        }
+        let field = |at: usize, len: usize| {
+            let bytes = h[at..at + len].iter();
+            if h[5] == 2 {
+                bytes.fold(0u64, |n, &b| n << 8 | u64::from(b))
+            } else {
+                bytes.rev().fold(0u64, |n, &b| n << 8 | u64::from(b))
+            }
+        };
+        let (shoff, entsize, num) = match h[4] {
+            1 => (field(0x20, 4), field(0x2e, 2), field(0x30, 2)),
+            2 if h.len() >= 0x40 => (field(0x28, 8), field(0x3a, 2), field(0x3c, 2)),
+            _ => return Ok(0),
+        };
+        if shoff == 0 {
+            return Ok(0);
+        }
+        Ok(shoff + entsize * num)
+    }
+}
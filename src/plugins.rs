@@ -0,0 +1,100 @@
+//! Listing r2's plugins, to check up front that a session has the ones a
+//! tool relies on (r2ghidra, r2frida, an architecture...).
+
+use crate::error::Error;
+use crate::r2pipe::{json_error, R2Pipe};
+use crate::structs::Plugin;
+
+use serde_json::Value;
+use std::fmt;
+
+/// The kinds of plugins r2 lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginKind {
+    /// Plugins adding commands (`Lcj`), e.g. r2ghidra.
+    Core,
+    /// Plugins opening URIs (`oLj`), e.g. r2frida.
+    Io,
+    /// File format parsers (`iLj`).
+    Bin,
+    /// Assemblers and disassemblers (`Laj`).
+    Arch,
+    /// Debugger backends (`Ldj`).
+    Debug,
+}
+
+impl PluginKind {
+    fn cmd(self) -> &'static str {
+        match self {
+            PluginKind::Core => "Lcj",
+            PluginKind::Io => "oLj",
+            PluginKind::Bin => "iLj",
+            PluginKind::Arch => "Laj",
+            PluginKind::Debug => "Ldj",
+        }
+    }
+}
+
+impl fmt::Display for PluginKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PluginKind::Core => "core",
+            PluginKind::Io => "io",
+            PluginKind::Bin => "bin",
+            PluginKind::Arch => "arch",
+            PluginKind::Debug => "debug",
+        };
+        f.write_str(name)
+    }
+}
+
+impl R2Pipe {
+    /// The plugins of `kind` loaded in this r2.
+    pub fn plugins(&mut self, kind: PluginKind) -> Result<Vec<Plugin>, Error> {
+        let cmd = kind.cmd();
+        let res: Value = self.cmd_deserialize(cmd)?;
+        // Lists come bare or, like `iLj`, grouped in an object.
+        let entries = match res {
+            Value::Array(list) => list,
+            Value::Object(groups) => groups
+                .into_iter()
+                .filter_map(|(_, v)| match v {
+                    Value::Array(list) => Some(list),
+                    _ => None,
+                })
+                .flatten()
+                .collect(),
+            _ => return Err(Error::UnexpectedOutput(cmd.to_string())),
+        };
+        entries
+            .into_iter()
+            .map(|p| serde_json::from_value(p).map_err(|e| json_error(cmd, &[], e)))
+            .collect()
+    }
+
+    /// Whether a plugin of `kind` named `name` is loaded.
+    pub fn has_plugin(&mut self, kind: PluginKind, name: &str) -> Result<bool, Error> {
+        Ok(self.plugins(kind)?.iter().any(|p| p.name == name))
+    }
+
+    /// Fails with `Error::Unsupported` unless a plugin of `kind` named
+    /// `name` is loaded.
+    ///
+    /// ```no_run
+    /// use r2pipe::plugins::PluginKind;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.require_plugin(PluginKind::Core, "r2ghidra")?;
+    /// r2.require_plugin(PluginKind::Arch, "arm")?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn require_plugin(&mut self, kind: PluginKind, name: &str) -> Result<(), Error> {
+        if self.has_plugin(kind, name)? {
+            return Ok(());
+        }
+        Err(Error::Unsupported(format!(
+            "No {} plugin named {} in this r2, check `r2pm -l` or install it with `r2pm -ci {}`",
+            kind, name, name
+        )))
+    }
+}
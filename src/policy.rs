@@ -0,0 +1,292 @@
+//! Restricting the commands a pipe sends, see
+//! `R2Pipe::set_command_policy()`.
+
+use crate::error::Error;
+
+/// Denied by `CommandPolicy::new()`: shell escapes, running scripts, macros
+/// or the output of commands (`.`), aliases (`$`), the io plugin commands,
+/// the `o` commands opening files (along with `OPEN_COMMANDS`), writing
+/// files (`wt`), and the variables whose value r2 runs as a command.
+const DEFAULT_DENY: &[&str] = &[
+    "!",
+    "#!",
+    ".",
+    "$",
+    "(",
+    "=!",
+    ":",
+    "on",
+    "oo",
+    "oba",
+    "obf",
+    "wt",
+    "e cmd.",
+    "e scr.pager",
+];
+
+/// The commands opening a file whose names are prefixes of harmless ones
+/// (`oj`, `om`...), so they are denied by name, whatever follows them.
+const OPEN_COMMANDS: &[&str] = &["o", "o+", "oc", "of"];
+
+/// Which commands may be sent, by prefix.
+///
+/// Meant for services building commands from untrusted input, as a
+/// second line of defense behind their own validation. Every command of a
+/// chain (`;`, `@@c:`) is checked, repeat counts (`3pd`) skipped, and
+/// temporary config (`@e:`) checked as `e` commands. Command
+/// substitutions (`` ` ``, `$(...)`), shell pipes (`|`) and redirections
+/// to files (`>`, `>>`) can't be checked, and are always denied.
+///
+/// ```no_run
+/// use r2pipe::CommandPolicy;
+///
+/// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+/// r2.set_command_policy(Some(CommandPolicy::new().allow("p").allow("af").allow("i")));
+/// assert!(r2.cmd("pd 4;!id").is_err());
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPolicy {
+    allow: Option<Vec<String>>,
+    deny: Vec<String>,
+}
+
+impl Default for CommandPolicy {
+    fn default() -> CommandPolicy {
+        CommandPolicy::new()
+    }
+}
+
+impl CommandPolicy {
+    /// Allows everything but shell escapes, scripts, macros, aliases,
+    /// opening and writing files, see `DEFAULT_DENY`.
+    pub fn new() -> CommandPolicy {
+        CommandPolicy {
+            allow: None,
+            deny: DEFAULT_DENY.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    /// Allows the commands starting with `prefix`. Once called, commands
+    /// matching no allowed prefix are denied.
+    pub fn allow(mut self, prefix: &str) -> CommandPolicy {
+        self.allow
+            .get_or_insert_with(Vec::new)
+            .push(prefix.to_string());
+        self
+    }
+
+    /// Denies the commands starting with `prefix`, even if allowed.
+    pub fn deny(mut self, prefix: &str) -> CommandPolicy {
+        self.deny.push(prefix.to_string());
+        self
+    }
+
+    /// Fails with `Error::InvalidCommand` unless every command of `cmd`
+    /// passes.
+    pub fn check(&self, cmd: &str) -> Result<(), Error> {
+        let denied = |what: &str| {
+            Err(Error::InvalidCommand(format!(
+                "Denied by the command policy: {}",
+                what
+            )))
+        };
+        if cmd.contains('`') || cmd.contains("$(") {
+            return denied("command substitution");
+        }
+        for (quoted, sub) in subcommands(cmd) {
+            if !quoted && sub.contains('|') {
+                return denied("pipe to a shell");
+            }
+            if !quoted && sub.contains('>') {
+                return denied("redirection to a file");
+            }
+            // `@e:k=v,...` sets `k` as `e k=v` would, for the denied keys.
+            let configs = sub.split("@e:").skip(1).flat_map(|c| {
+                c.split(|c: char| c.is_whitespace() || c == '@')
+                    .next()
+                    .unwrap_or("")
+                    .split(',')
+            });
+            for config in configs {
+                let set = format!("e {}", config);
+                if !quoted && self.deny.iter().any(|p| set.starts_with(p.as_str())) {
+                    return denied(&set);
+                }
+            }
+            // So that `e  cmd.x` or `o\t/path` can't slip through.
+            let sub = sub
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            if sub.is_empty() {
+                continue;
+            }
+            let starts = |p: &String| sub.starts_with(p.as_str());
+            let name = command_name(&sub);
+            if self.deny.iter().any(starts) || OPEN_COMMANDS.contains(&name) {
+                return denied(&sub);
+            }
+            if let Some(ref allow) = self.allow {
+                if !allow.iter().any(starts) {
+                    return denied(&sub);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name of the command `sub`, e.g. `o+` for `o+/tmp/file`.
+fn command_name(sub: &str) -> &str {
+    let end = sub
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '+')
+        .unwrap_or(sub.len());
+    &sub[..end]
+}
+
+/// The commands `cmd` runs: those chained with `;` or newlines, the body
+/// of quoted commands, and the commands of `@@c:` iterators. Quoted ones
+/// come with `true`.
+fn subcommands(cmd: &str) -> Vec<(bool, &str)> {
+    let mut subs = Vec::new();
+    let mut rest = cmd.trim_start();
+    while !rest.is_empty() {
+        let (sub, next) = if let Some(quoted) = rest.strip_prefix('"') {
+            // Nothing is interpreted up to the closing quote.
+            match quoted.find('"') {
+                Some(end) => {
+                    subs.push((true, &quoted[..end]));
+                    let after = &quoted[end + 1..];
+                    match after.find([';', '\n']) {
+                        Some(i) => (&after[..i], &after[i + 1..]),
+                        None => (after, ""),
+                    }
+                }
+                None => {
+                    subs.push((true, quoted));
+                    ("", "")
+                }
+            }
+        } else {
+            match rest.find([';', '\n']) {
+                Some(i) => (&rest[..i], &rest[i + 1..]),
+                None => (rest, ""),
+            }
+        };
+        match sub.find("@@c:") {
+            Some(i) => {
+                subs.push((false, &sub[..i]));
+                subs.push((false, &sub[i + 4..]));
+            }
+            None => subs.push((false, sub)),
+        }
+        rest = next.trim_start();
+    }
+    subs.into_iter().map(|(q, s)| (q, s.trim())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_chains_and_iterators() {
+        assert_eq!(
+            subcommands("pd 4; px 8\n afl"),
+            vec![(false, "pd 4"), (false, "px 8"), (false, "afl")]
+        );
+        assert_eq!(
+            subcommands("\"?e a;b\" @ 0x10; s 0"),
+            vec![(true, "?e a;b"), (false, "@ 0x10"), (false, "s 0")]
+        );
+        assert_eq!(
+            subcommands("pd 1 @@c:afl"),
+            vec![(false, "pd 1"), (false, "afl")]
+        );
+        assert_eq!(
+            subcommands("pd 1 @@= 0x10 0x20"),
+            vec![(false, "pd 1 @@= 0x10 0x20")]
+        );
+    }
+
+    #[test]
+    fn default_allows_analysis() {
+        let policy = CommandPolicy::new();
+        for cmd in [
+            "pd 4",
+            "aaa; afl",
+            "3pd 1",
+            "px 16 @ main",
+            "pd 1 @@= 0x10 0x20",
+            "pd 1 @@c:afl",
+            "e asm.bytes=false",
+            "pd 4 @e:asm.bytes=false",
+            "oj",
+            "omj",
+            "om- 1",
+        ] {
+            assert!(policy.check(cmd).is_ok(), "{}", cmd);
+        }
+    }
+
+    #[test]
+    fn default_denies_escapes() {
+        let policy = CommandPolicy::new();
+        for cmd in [
+            "!id",
+            "pd 4;!id",
+            "pd 4\n!id",
+            "2!id",
+            "pd 4 | sh",
+            "pd 4 > /tmp/x",
+            "pd 4 >> /tmp/x",
+            "?e x;pd 4>/tmp/x",
+            "wt /tmp/x",
+            "wtf /tmp/x 16",
+            "?e `!id`",
+            "pd 1 @@=`!id`",
+            "?e $(id)",
+            "pd 1 @@c:!id",
+            "#!python",
+            ". /tmp/script.r2",
+            ".!id",
+            ".(macro args)",
+            "./ /tmp/x",
+            "$alias=!id",
+            "$alias",
+            "(macro;!id)",
+            "(macro)",
+            "o /etc/passwd",
+            "o\t/etc/passwd",
+            "on /etc/passwd",
+            "oo+",
+            "o+ /tmp/x",
+            "o/etc/passwd",
+            "o+/tmp/x",
+            "oc /etc/passwd",
+            "of /etc/passwd",
+            "onn /etc/passwd",
+            "obf /etc/passwd",
+            "=!id",
+            ":cmd",
+            "e cmd.prompt=!id",
+            "e  cmd.prompt=!id",
+            "pd 4 @e:cmd.prompt=!id",
+            "pd 4 @e:asm.bytes=0,scr.pager=sh",
+        ] {
+            assert!(policy.check(cmd).is_err(), "{}", cmd);
+        }
+    }
+
+    #[test]
+    fn allow_list_covers_every_command() {
+        let policy = CommandPolicy::new().allow("p").allow("af");
+        assert!(policy.check("pd 4; afl").is_ok());
+        assert!(policy.check("pd 4; iz").is_err());
+        assert!(policy.check("pd 1 @@c:iz").is_err());
+        assert!(policy.check("pd 1").is_ok());
+        assert!(CommandPolicy::new().deny("aa").check("3aaa").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Sharing several r2 sessions between threads.
+//!
+//! `R2Pool` spawns a fixed number of sessions on the same file and lends
+//! them out to whoever calls it, blocking while all of them are busy. A
+//! session that stops answering is replaced by a new one.
+
+use crate::error::Error;
+use crate::r2pipe::{R2Pipe, R2PipeSpawnOptions};
+
+use serde_json::Value;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+struct Workers {
+    idle: Vec<R2Pipe>,
+    // Sessions alive, whether idle or lent out.
+    alive: usize,
+}
+
+/// A pool of r2 sessions on the same file, usable from several threads.
+///
+/// ```no_run
+/// use r2pipe::R2Pool;
+/// use std::thread;
+///
+/// let pool = R2Pool::new("/bin/ls", 4, None).unwrap();
+/// pool.cmd("aa").unwrap();
+/// thread::scope(|s| {
+///     for addr in &["main", "entry0"] {
+///         let pool = &pool;
+///         s.spawn(move || pool.cmd(&format!("pdf @ {}", addr)));
+///     }
+/// });
+/// ```
+///
+/// Each session has its own state: a command like `aa` only affects the
+/// session it ran on.
+pub struct R2Pool {
+    path: String,
+    opts: Option<R2PipeSpawnOptions>,
+    workers: Mutex<Workers>,
+    available: Condvar,
+}
+
+impl R2Pool {
+    /// Spawns `size` sessions on `path`.
+    pub fn new(path: &str, size: usize, opts: Option<R2PipeSpawnOptions>) -> Result<R2Pool, Error> {
+        if size == 0 {
+            return Err(Error::Other("A pool needs at least one worker".to_string()));
+        }
+        let idle = (0..size)
+            .map(|_| R2Pipe::spawn(path, opts.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(R2Pool {
+            path: path.to_string(),
+            opts,
+            workers: Mutex::new(Workers { idle, alive: size }),
+            available: Condvar::new(),
+        })
+    }
+
+    /// Runs `cmd` on the first idle session.
+    pub fn cmd(&self, cmd: &str) -> Result<String, Error> {
+        self.with(|r2| r2.cmd(cmd))
+    }
+
+    /// Runs `cmd` on the first idle session, parsing its output as JSON.
+    pub fn cmdj(&self, cmd: &str) -> Result<Value, Error> {
+        self.with(|r2| r2.cmdj(cmd))
+    }
+
+    /// Lends an idle session to `f`, for work needing several commands.
+    ///
+    /// If `f` fails and the session no longer answers, it is replaced.
+    pub fn with<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut R2Pipe) -> Result<T, Error>,
+    {
+        let mut lent = Lent {
+            pool: self,
+            r2: Some(self.checkout()?),
+        };
+        let r2 = lent.r2.as_mut().unwrap();
+        let res = f(r2);
+        if res.is_err() && r2.cmd("?e").is_err() {
+            r2.close();
+            lent.r2 = None;
+            self.respawn();
+        }
+        res
+    }
+
+    /// Number of live sessions.
+    pub fn size(&self) -> usize {
+        self.lock().alive
+    }
+
+    /// Runs `f` on every file of `files`, with up to `workers` r2 sessions
+    /// running at once.
+    ///
+    /// Results are in the same order as `files`.
+    pub fn map_files<T, F>(
+        files: &[&str],
+        workers: usize,
+        opts: Option<R2PipeSpawnOptions>,
+        f: F,
+    ) -> Vec<Result<T, Error>>
+    where
+        T: Send,
+        F: Fn(&str, &mut R2Pipe) -> Result<T, Error> + Sync,
+    {
+        let next = Mutex::new(0);
+        let results: Mutex<Vec<Option<Result<T, Error>>>> =
+            Mutex::new((0..files.len()).map(|_| None).collect());
+        thread::scope(|s| {
+            for _ in 0..workers.max(1).min(files.len()) {
+                s.spawn(|| loop {
+                    let i = {
+                        let mut next = next.lock().unwrap_or_else(|e| e.into_inner());
+                        *next += 1;
+                        *next - 1
+                    };
+                    let file = match files.get(i) {
+                        Some(file) => *file,
+                        None => break,
+                    };
+                    let res = R2Pipe::spawn(file, opts.clone()).and_then(|mut r2| {
+                        let res = f(file, &mut r2);
+                        r2.close();
+                        res
+                    });
+                    results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(res);
+                });
+            }
+        });
+        results
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .map(|r| r.unwrap_or_else(|| Err(Error::Other("File was not processed".to_string()))))
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Workers> {
+        // The list is only touched with quick pushes and pops, so it is
+        // consistent even if another thread panicked while holding it.
+        self.workers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn checkout(&self) -> Result<R2Pipe, Error> {
+        let mut workers = self.lock();
+        loop {
+            if let Some(r2) = workers.idle.pop() {
+                return Ok(r2);
+            }
+            if workers.alive == 0 {
+                return Err(Error::Other("No r2 session left in the pool".to_string()));
+            }
+            workers = self
+                .available
+                .wait(workers)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn checkin(&self, r2: R2Pipe) {
+        self.lock().idle.push(r2);
+        self.available.notify_one();
+    }
+
+    fn respawn(&self) {
+        match R2Pipe::spawn(&self.path, self.opts.clone()) {
+            Ok(r2) => self.checkin(r2),
+            Err(_) => {
+                self.lock().alive -= 1;
+                // Wake everyone up, they may have to give up now.
+                self.available.notify_all();
+            }
+        }
+    }
+}
+
+/// Gives the session back to the pool once done, even if `f` panicked.
+struct Lent<'a> {
+    pool: &'a R2Pool,
+    r2: Option<R2Pipe>,
+}
+
+impl<'a> Drop for Lent<'a> {
+    fn drop(&mut self) {
+        if let Some(r2) = self.r2.take() {
+            self.pool.checkin(r2);
+        }
+    }
+}
+
+impl Drop for R2Pool {
+    fn drop(&mut self) {
+        for r2 in self.lock().idle.iter_mut() {
+            r2.close();
+        }
+    }
+}
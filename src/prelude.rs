@@ -0,0 +1,17 @@
+//! The commonly used items, importable at once with `use r2pipe::prelude::*;`.
+
+pub use crate::cmd::Cmd;
+pub use crate::error::Error;
+pub use crate::r2cmd;
+pub use crate::structs::{Addr, BinInfo, FunctionInfo, Info, Instruction, Section};
+
+#[cfg(feature = "pipe")]
+pub use crate::analysis::{AnalysisLevel, AnalysisProgress};
+#[cfg(feature = "pipe")]
+pub use crate::api::R2Api;
+#[cfg(feature = "pipe")]
+pub use crate::decompiler::DecompilerBackend;
+#[cfg(feature = "pipe")]
+pub use crate::open_pipe;
+#[cfg(feature = "pipe")]
+pub use crate::r2pipe::{R2Pipe, R2PipeCommands, R2PipeHttpOptions, R2PipeSpawnOptions};
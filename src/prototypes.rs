@@ -0,0 +1,52 @@
+//! Reading and setting function prototypes (`afs`), which r2 uses to name
+//! and type arguments in the disassembly and the decompilers.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Signature};
+
+impl R2Pipe {
+    /// The prototype of the function at `addr` (`afsj`).
+    pub fn function_signature<A: Into<Addr>>(&mut self, addr: A) -> Result<Signature, Error> {
+        self.cmd_deserialize(&format!("afsj @ {}", addr.into()))
+    }
+
+    /// Sets the prototype of the function at `addr` (`afs`), renaming the
+    /// function to `sig.name`.
+    ///
+    /// r2 only complains on stderr about the types it doesn't know, so the
+    /// prototype is read back and this fails if the parameters didn't make
+    /// it.
+    ///
+    /// ```no_run
+    /// use r2pipe::parse_signature;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let sig = parse_signature("int main(int argc, char **argv)")?;
+    /// r2.apply_signature(0x1000u64, &sig)?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn apply_signature<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        sig: &Signature,
+    ) -> Result<(), Error> {
+        let addr = addr.into();
+        let text = sig.to_string();
+        if text.contains([';', '@', '\n']) {
+            return Err(Error::InvalidCommand(format!(
+                "Unusable prototype: {:?}",
+                text
+            )));
+        }
+        self.cmd_ok(&format!("afs {} @ {}", text, addr))?;
+        let applied = self.function_signature(addr)?;
+        if applied.args.len() != sig.args.len() {
+            return Err(Error::Other(format!(
+                "r2 kept `{}` at {}, instead of `{}`",
+                applied, addr, text
+            )));
+        }
+        Ok(())
+    }
+}
@@ -11,6 +11,7 @@
 //! contribute to the r2pipe.rs-frontend project. This aims to be a
 //! barebones implementation of the pipe concept.
 
+use crate::error::Error;
 use crate::r2pipe::R2Pipe;
 use serde_json;
 use serde_json::Value;
@@ -30,15 +31,15 @@ impl Default for R2 {
 // i.e. The ones that are not currently abstracted by the R2 API.
 // Ideally, all commonly used commands must be supported for easier use.
 impl R2 {
-    // TODO: Use an error type
-    pub fn new<T: AsRef<str>>(path: Option<T>) -> Result<R2, String> {
+    pub fn new<T: AsRef<str>>(path: Option<T>) -> Result<R2, Error> {
         if path.is_none() && !R2::in_session() {
-            let e = "No r2 session open. Please specify path!".to_owned();
-            return Err(e);
+            return Err(Error::Other(
+                "No r2 session open. Please specify path!".to_string(),
+            ));
         }
 
         // This means that path is `Some` or we have an open session.
-        let pipe = open_pipe!(path.as_ref()).unwrap();
+        let pipe = open_pipe!(path.as_ref())?;
         Ok(R2 {
             pipe,
             readin: String::new(),
@@ -2,45 +2,201 @@
 //!
 //! Please check crate level documentation for more details and example.
 
+use flate2::read::{DeflateDecoder, ZlibDecoder};
 use reqwest;
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
 
 use libc;
+use std::collections::VecDeque;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
+use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::io::IoSlice;
+use std::mem;
 use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::path::Path;
 use std::process;
 use std::process::Command;
 use std::process::Stdio;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
+use serde::de::DeserializeOwned;
 use serde_json;
+use serde_json::value::RawValue;
 use serde_json::Value;
 
+use crate::audit::AuditLog;
+use crate::backend::Backend;
+use crate::error::Error;
+use crate::history::{History, HistoryEntry, HISTORY_EXCERPT};
+use crate::policy::CommandPolicy;
+use crate::retry::{self, RetryPolicy};
+use crate::session::{LifecycleEvent, LifecycleHook, SessionId};
+use crate::stats::PipeStats;
+use crate::structs::Addr;
+use crate::throttle::RateLimiter;
+use crate::trace;
+
 /// File descriptors to the parent r2 process.
 pub struct R2PipeLang {
     read: BufReader<File>,
     write: File,
+    state: PipeState,
 }
 
 /// Stores descriptors to the spawned r2 process.
 pub struct R2PipeSpawn {
+    child: process::Child,
     read: BufReader<process::ChildStdout>,
     write: process::ChildStdin,
+    response_capacity: usize,
+    stderr: Arc<Mutex<StderrTail>>,
+    // What the stderr thread had read when the last command was sent.
+    stderr_mark: u64,
+    state: PipeState,
+    // Kept to respawn r2 after a hung command.
+    target: String,
+    opts: Option<R2PipeSpawnOptions>,
+}
+
+/// The last bytes r2 wrote to its stderr, filled by a background thread.
+#[derive(Default)]
+struct StderrTail {
+    buf: VecDeque<u8>,
+    // Bytes read since r2 started, including those dropped from `buf`.
+    read: u64,
+    closed: bool,
 }
 
 /// Stores the socket address of the r2 process.
 pub struct R2PipeTcp {
     socket_addr: SocketAddr,
+    state: PipeState,
 }
 
 pub struct R2PipeHttp {
     host: String,
+    client: reqwest::Client,
+    compression: bool,
+    state: PipeState,
+}
+
+/// Another implementation of `R2PipeCommands`, see `R2Pipe::from_commands()`.
+pub struct R2PipeCustom {
+    inner: Box<dyn R2PipeCommands + Send>,
+    state: PipeState,
+}
+
+/// What to do with command output that isn't valid UTF-8.
+///
+/// Commands such as `ps` on arbitrary memory can print any byte. Whatever
+/// the policy, `R2Pipe::cmd_bytes()` returns the output untouched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Utf8Policy {
+    /// Fail with `Error::Utf8`, which holds the offending output.
+    #[default]
+    Strict,
+    /// Replace invalid sequences with U+FFFD.
+    Lossy,
+}
+
+/// Per-pipe settings, shared by all the transports.
+#[derive(Default)]
+struct PipeState {
+    utf8: Utf8Policy,
+    strip_ansi: bool,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    keep_whitespace: bool,
+    redact: bool,
+    stats: PipeStats,
+    history: Option<History>,
+    retry: Option<RetryPolicy>,
+    limiter: Option<RateLimiter>,
+    policy: Option<CommandPolicy>,
+    backend: Backend,
+    audit: Option<AuditLog>,
+    session: SessionId,
+    lifecycle: Option<LifecycleHook>,
+}
+
+impl PipeState {
+    /// How long `cmd` may run: the timeout, or what's left until the
+    /// deadline when it comes first, which the flag tells.
+    fn time_left(&self, cmd: &str) -> Result<(Option<Duration>, bool), Error> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return Ok((self.timeout, false)),
+        };
+        let left = deadline
+            .checked_duration_since(Instant::now())
+            .filter(|left| !left.is_zero())
+            .ok_or_else(|| Error::DeadlineExceeded {
+                cmd: cmd.to_string(),
+            })?;
+        Ok(match self.timeout {
+            Some(timeout) if timeout < left => (Some(timeout), false),
+            _ => (Some(left), true),
+        })
+    }
+
+    fn emit(&self, event: LifecycleEvent) {
+        if let Some(ref hook) = self.lifecycle {
+            hook(&event);
+        }
+    }
+
+    /// Fails when the audit log can't be written.
+    fn record(
+        &mut self,
+        cmd: &str,
+        latency: Duration,
+        reply: Result<&[u8], String>,
+    ) -> Result<(), Error> {
+        let len = reply.as_ref().map_or(0, |r| r.len());
+        self.record_excerpt(cmd, latency, reply, len)
+    }
+
+    /// Same as `record()` for a reply of `len` bytes, of which `reply` may
+    /// only be the first `excerpt_bytes()`.
+    fn record_excerpt(
+        &mut self,
+        cmd: &str,
+        latency: Duration,
+        reply: Result<&[u8], String>,
+        len: usize,
+    ) -> Result<(), Error> {
+        self.stats
+            .record(cmd.len(), reply.as_ref().ok().map(|_| len), latency);
+        if let Some(ref mut audit) = self.audit {
+            audit.record(self.session, cmd, latency, reply.clone(), len)?;
+        }
+        if let Err(ref error) = reply {
+            self.emit(LifecycleEvent::CommandFailed {
+                session: self.session,
+                cmd: cmd.to_string(),
+                error: error.clone(),
+            });
+        }
+        if let Some(ref mut history) = self.history {
+            history.push(cmd, latency, reply, len);
+        }
+        Ok(())
+    }
+
+    /// Bytes of a reply the history and the audit log keep.
+    fn excerpt_bytes(&self) -> usize {
+        let audit = self.audit.as_ref().map_or(0, |a| a.excerpt_bytes());
+        let history = self.history.as_ref().map_or(0, |_| HISTORY_EXCERPT);
+        audit.max(history)
+    }
 }
 
 /// Stores thread metadata
@@ -57,6 +213,80 @@ pub struct R2PipeThread {
 pub struct R2PipeSpawnOptions {
     pub exepath: String,
     pub args: Vec<&'static str>,
+    /// Capacity of the buffer reading r2's output, 0 for the default (8 KiB).
+    pub read_buffer_size: usize,
+    /// Bytes preallocated for each reply, 0 to start from an empty buffer.
+    ///
+    /// Set it to the typical reply size when commands routinely return
+    /// megabytes of output, to avoid repeated reallocations while reading.
+    pub response_capacity: usize,
+    /// Keep the colors enabled by the user's `~/.radare2rc`.
+    ///
+    /// By default `scr.color` is turned off right after spawning, since
+    /// escape codes in the output break JSON parsing.
+    pub keep_colors: bool,
+    /// Commands run right after spawning, their output is discarded.
+    pub init_cmds: Vec<String>,
+    /// Don't drain r2's output before returning from `spawn()`.
+    ///
+    /// Some setups print prompt fragments or plugin warnings before the
+    /// first reply, which would end up in the output of the first command.
+    /// By default `spawn()` settles the pipe with `resync()`, costing one
+    /// round trip.
+    pub skip_settle: bool,
+    /// Called once r2 answered and `init_cmds` ran, before `spawn()`
+    /// returns, e.g. to report binaries that are slow to load.
+    pub on_ready: Option<ReadyHook>,
+    /// Runs r2 in its sandbox (`-S`, checked with `cfg.sandbox` once
+    /// spawned), which denies file, network and shell access to commands,
+    /// and sets `CommandPolicy::new()` on the pipe. Spawning fails with
+    /// `Error::Unsupported` when r2 doesn't enable it.
+    ///
+    /// `init_cmds` run before the policy is set.
+    pub hardened: bool,
+    /// Runs r2 in new user and network namespaces, where it has no
+    /// network at all. Linux only, and fails to spawn where unprivileged
+    /// user namespaces are disabled.
+    pub isolate_network: bool,
+    /// Called on each `LifecycleEvent` of the pipe, from spawning to
+    /// closing.
+    pub on_lifecycle: Option<LifecycleHook>,
+}
+
+/// See `R2PipeSpawnOptions::on_ready`.
+pub type ReadyHook = Arc<dyn Fn(&ReadyInfo) + Send + Sync>;
+
+/// How long a spawned r2 took to get ready, see
+/// `R2PipeSpawnOptions::on_ready`.
+#[derive(Clone, Debug)]
+pub struct ReadyInfo {
+    pub target: String,
+    /// From starting r2 to its first NUL byte, i.e. loading the file.
+    pub handshake: Duration,
+    /// Running the startup commands and draining the pipe.
+    pub settle: Duration,
+}
+
+impl ReadyInfo {
+    pub fn total(&self) -> Duration {
+        self.handshake + self.settle
+    }
+}
+
+/// Options for `R2Pipe::http_with_options()`.
+#[derive(Clone)]
+pub struct R2PipeHttpOptions {
+    /// Ask the server for gzip or deflate compressed replies, and decode them.
+    ///
+    /// Enabled by default. Disable it when r2 runs on the same host, where
+    /// compressing only costs CPU time.
+    pub compression: bool,
+}
+
+impl Default for R2PipeHttpOptions {
+    fn default() -> R2PipeHttpOptions {
+        R2PipeHttpOptions { compression: true }
+    }
 }
 
 /// Provides abstraction between the three invocation methods.
@@ -65,13 +295,40 @@ pub enum R2Pipe {
     Lang(R2PipeLang),
     Tcp(R2PipeTcp),
     Http(R2PipeHttp),
+    Custom(R2PipeCustom),
 }
 
-fn atoi(k: &str) -> i32 {
-    match k.parse::<i32>() {
-        Ok(val) => val,
-        Err(_) => -1,
+/// Command interface shared by all the pipes.
+///
+/// Libraries can be written once against this trait and accept whichever
+/// pipe (or wrapper around one) the caller happens to use. The typed
+/// wrappers are methods of `R2Pipe`: `R2Pipe::from_commands()` makes one
+/// out of any implementation, e.g. a `MockR2Pipe` or a `ReplayPipe`.
+pub trait R2PipeCommands {
+    /// Runs an r2 command and returns its output.
+    fn cmd(&mut self, cmd: &str) -> Result<String, Error>;
+
+    /// Runs an r2 command and parses its output as JSON.
+    fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        let res = self.cmd(cmd)?;
+        parse_json(cmd, res.into_bytes())
     }
+
+    /// Terminates the session.
+    fn close(&mut self);
+}
+
+/// Time given to r2 to abort a timed out command after a SIGINT.
+const INTERRUPT_GRACE: Duration = Duration::from_secs(1);
+
+/// Bytes of stderr kept by a spawned pipe.
+const STDERR_TAIL: usize = 4096;
+
+/// Maximum number of bytes written at once by `R2PipeSpawn::cmd_batch()`.
+const BATCH_BYTES: usize = 16 * 1024;
+
+fn atoi(k: &str) -> i32 {
+    k.parse::<i32>().unwrap_or(-1)
 }
 
 fn getenv(k: &str) -> i32 {
@@ -81,15 +338,484 @@ fn getenv(k: &str) -> i32 {
     }
 }
 
-fn process_result(res: Vec<u8>) -> Result<String, String> {
-    let len = res.len();
-    if len == 0 {
-        return Err("Failed".to_string());
+/// Writes each of `cmds` followed by a newline with vectored writes, so they
+/// are neither copied into a single buffer nor written one by one.
+fn write_cmds<W: Write>(w: &mut W, cmds: &[&str]) -> io::Result<()> {
+    let mut slices = Vec::with_capacity(cmds.len() * 2);
+    for cmd in cmds {
+        slices.push(IoSlice::new(cmd.as_bytes()));
+        slices.push(IoSlice::new(b"\n"));
+    }
+    let mut slices = &mut slices[..];
+    while !slices.is_empty() {
+        match w.write_vectored(slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut slices, n),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Makes `command` unshare the user and network namespaces before running.
+#[cfg(target_os = "linux")]
+fn isolate_network(command: &mut Command) -> Result<(), Error> {
+    use std::os::unix::process::CommandExt;
+
+    // unshare() is async-signal-safe, so fine between fork and exec.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn isolate_network(_: &mut Command) -> Result<(), Error> {
+    Err(Error::Unsupported(
+        "Network isolation is only available on Linux".to_string(),
+    ))
+}
+
+/// Checks that r2 will be able to open `target`, so a bad path is reported
+/// as such rather than as a failed handshake.
+///
+/// URIs such as `malloc://512` or `frida://` and anything that doesn't look
+/// like a path are left for r2 to handle.
+fn check_target(target: &str) -> Result<(), Error> {
+    if target.is_empty() || target.starts_with('-') || target.contains("://") {
+        return Ok(());
+    }
+    let denied = |e: &io::Error| e.kind() == io::ErrorKind::PermissionDenied;
+    match File::open(target) {
+        Ok(_) => Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(Error::TargetNotFound(target.to_string()))
+        }
+        Err(ref e) if denied(e) => Err(Error::PermissionDenied(target.to_string())),
+        // Directories and the like are r2's business.
+        Err(_) => Ok(()),
+    }
+}
+
+/// Explains why r2 didn't send its first NUL byte.
+///
+/// Usually r2 exited right away because it couldn't open the target, and
+/// said why on its stderr.
+fn handshake_failed(mut child: process::Child, e: io::Error, stderr: &Mutex<StderrTail>) -> Error {
+    let status = match child.try_wait() {
+        Ok(Some(status)) => status,
+        _ => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Error::Handshake(e.to_string());
+        }
+    };
+    // Give the stderr thread a moment to catch up.
+    for _ in 0..50 {
+        if stderr.lock().map(|t| t.closed).unwrap_or(true) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(2));
+    }
+    let tail = stderr.lock().unwrap_or_else(|e| e.into_inner());
+    let (a, b) = tail.buf.as_slices();
+    Error::ProcessExited {
+        status: Some(status),
+        stderr: String::from_utf8_lossy(&[a, b].concat()).into_owned(),
+    }
+}
+
+/// A command echoing a unique marker, and the marker.
+fn sync_marker() -> (String, String) {
+    static SYNCS: AtomicUsize = AtomicUsize::new(0);
+    let marker = format!(
+        "r2pipe-sync-{}-{}",
+        process::id(),
+        SYNCS.fetch_add(1, Ordering::Relaxed)
+    );
+    (format!("?e {}", marker), marker)
+}
+
+/// Keeps reading `stderr` in a thread, remembering its last `STDERR_TAIL`
+/// bytes.
+fn capture_stderr(mut stderr: process::ChildStderr) -> Arc<Mutex<StderrTail>> {
+    let tail = Arc::new(Mutex::new(StderrTail::default()));
+    let shared = tail.clone();
+    thread::spawn(move || {
+        let mut chunk = [0u8; 1024];
+        loop {
+            let n = match stderr.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            let mut tail = shared.lock().unwrap_or_else(|e| e.into_inner());
+            tail.buf.extend(&chunk[..n]);
+            tail.read += n as u64;
+            let excess = tail.buf.len().saturating_sub(STDERR_TAIL);
+            tail.buf.drain(..excess);
+        }
+        shared.lock().unwrap_or_else(|e| e.into_inner()).closed = true;
+    });
+    tail
+}
+
+/// Reads a NUL terminated reply into `buf`, without the terminator.
+///
+/// Reaching the end of the stream before the terminator means r2 is gone,
+/// maybe halfway through the reply, which is reported as
+/// `Error::ProcessExited` without a status.
+fn read_frame<R: BufRead>(read: &mut R, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.clear();
+    read.read_until(0u8, buf)?;
+    match buf.last() {
+        Some(0) => {
+            buf.pop();
+            Ok(())
+        }
+        _ => Err(eof()),
+    }
+}
+
+/// The end of r2's output, until `R2PipeSpawn::exited()` knows more.
+fn eof() -> Error {
+    Error::ProcessExited {
+        status: None,
+        stderr: String::new(),
+    }
+}
+
+/// Same as `read_frame()`, giving up once `deadline` passes.
+///
+/// Returns false on timeout, leaving what was read so far in `buf`.
+fn read_frame_by(
+    read: &mut BufReader<process::ChildStdout>,
+    buf: &mut Vec<u8>,
+    deadline: Instant,
+) -> Result<bool, Error> {
+    buf.clear();
+    loop {
+        if read.buffer().is_empty() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if !wait_readable(read.get_ref(), left)? {
+                return Ok(false);
+            }
+        }
+        let avail = read.fill_buf()?;
+        if avail.is_empty() {
+            return Err(eof());
+        }
+        match avail.iter().position(|&b| b == 0) {
+            Some(nul) => {
+                buf.extend_from_slice(&avail[..nul]);
+                read.consume(nul + 1);
+                return Ok(true);
+            }
+            None => {
+                let len = avail.len();
+                buf.extend_from_slice(avail);
+                read.consume(len);
+            }
+        }
+    }
+}
+
+/// Waits until `fd` has something to read, false if `timeout` passes first.
+#[cfg(unix)]
+fn wait_readable<F: std::os::unix::io::AsRawFd>(fd: &F, timeout: Duration) -> io::Result<bool> {
+    let mut pfd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    loop {
+        match unsafe { libc::poll(&mut pfd, 1, ms) } {
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+            n => return Ok(n > 0),
+        }
+    }
+}
+
+// No timeouts without poll(): reads just block.
+#[cfg(not(unix))]
+fn wait_readable<F>(_: &F, _: Duration) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Copies to `out` what is buffered of the current NUL terminated reply,
+/// and whether that was its end, whose terminator is then consumed.
+fn read_frame_chunk(read: &mut dyn BufRead, out: &mut [u8]) -> Result<(usize, bool), Error> {
+    let buf = read.fill_buf()?;
+    if buf.is_empty() {
+        // r2 is gone before the end of the reply.
+        return Err(eof());
+    }
+    let (len, end) = match buf.iter().position(|&b| b == 0) {
+        Some(nul) if nul <= out.len() => (nul, true),
+        _ => (buf.len().min(out.len()), false),
+    };
+    out[..len].copy_from_slice(&buf[..len]);
+    read.consume(if end { len + 1 } else { len });
+    Ok((len, end))
+}
+
+/// The reply of `R2Pipe::cmd_reader()`, read as it comes.
+///
+/// Once it ends or fails it is recorded like the reply of `cmd()`, and on
+/// spawned pipes the reads are bounded by the timeout and the deadline.
+/// Whatever is left of it is discarded on drop, so the next command starts
+/// reading at the right place even if this one wasn't fully read.
+struct StreamedReply<'a> {
+    r2: &'a mut R2Pipe,
+    cmd: String,
+    start: Instant,
+    /// When the reply must have ended, the timeout to report and whether
+    /// it comes from the deadline.
+    limit: Option<(Instant, Duration, bool)>,
+    head: Vec<u8>,
+    len: usize,
+    done: bool,
+}
+
+impl<'a> StreamedReply<'a> {
+    /// Reads the next chunk of the reply, waiting for it until the limit.
+    fn read_chunk(&mut self, out: &mut [u8]) -> Result<(usize, bool), Error> {
+        match *self.r2 {
+            R2Pipe::Pipe(ref mut x) => {
+                if let Some((by, timeout, by_deadline)) = self.limit {
+                    let left = by.saturating_duration_since(Instant::now());
+                    if x.read.buffer().is_empty() && !wait_readable(x.read.get_ref(), left)? {
+                        return Err(match x.recover(&self.cmd, timeout, &mut Vec::new()) {
+                            Error::Timeout { cmd, .. } if by_deadline => {
+                                Error::DeadlineExceeded { cmd }
+                            }
+                            e => e,
+                        });
+                    }
+                }
+                read_frame_chunk(&mut x.read, out).map_err(|e| x.exited(e))
+            }
+            R2Pipe::Lang(ref mut x) => read_frame_chunk(&mut x.read, out),
+            // The other pipes read the whole reply in `cmd_reader()`.
+            _ => Ok((0, true)),
+        }
+    }
+
+    /// Records the reply, which ended or failed with `res`.
+    fn finish(&mut self, res: Result<(), Error>) -> Result<(), Error> {
+        self.done = true;
+        let (latency, len) = (self.start.elapsed(), self.len);
+        let state = self.r2.state_mut();
+        trace::streamed(
+            &self.cmd,
+            state.session,
+            state.redact,
+            latency,
+            res.as_ref().map(|_| len),
+        );
+        let seen = match res {
+            Ok(()) => Ok(&self.head[..]),
+            Err(ref e) => Err(e.to_string()),
+        };
+        let recorded = state.record_excerpt(&self.cmd, latency, seen, len);
+        res.and(recorded).map_err(|e| self.r2.with_session(e))
+    }
+}
+
+impl<'a> Read for StreamedReply<'a> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.done || out.is_empty() {
+            return Ok(0);
+        }
+        let res = match self.read_chunk(out) {
+            Ok((len, end)) => {
+                let keep = self
+                    .r2
+                    .state()
+                    .excerpt_bytes()
+                    .saturating_sub(self.head.len());
+                self.head.extend_from_slice(&out[..len.min(keep)]);
+                self.len += len;
+                if end {
+                    self.finish(Ok(())).map(|_| len)
+                } else {
+                    Ok(len)
+                }
+            }
+            Err(e) => self.finish(Err(e)).map(|_| 0),
+        };
+        // The error is taken back by `JsonStream`.
+        res.map_err(io::Error::other)
+    }
+}
+
+impl<'a> Drop for StreamedReply<'a> {
+    fn drop(&mut self) {
+        let mut scratch = [0u8; 4096];
+        while let Ok(n) = self.read(&mut scratch) {
+            if n == 0 {
+                break;
+            }
+        }
+    }
+}
+
+/// Bytes of output kept in `Error::Json`.
+pub(crate) const JSON_EXCERPT: usize = 200;
+
+/// Deserializes the JSON reply of `cmd`, using simd-json when its feature
+/// is enabled.
+pub(crate) fn parse_json<T: DeserializeOwned>(cmd: &str, res: Vec<u8>) -> Result<T, Error> {
+    if res.iter().all(|b| b.is_ascii_whitespace()) {
+        return Err(Error::EmptyResponse(cmd.to_string()));
+    }
+    // simd-json parses in place, so keep the beginning aside.
+    let head = res[..res.len().min(JSON_EXCERPT)].to_vec();
+    parse_json_bytes(res).map_err(|e| json_error(cmd, &head, e))
+}
+
+#[cfg(feature = "simd-json")]
+fn parse_json_bytes<T: DeserializeOwned>(mut res: Vec<u8>) -> Result<T, String> {
+    simd_json::serde::from_slice(&mut res).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "simd-json"))]
+fn parse_json_bytes<T: DeserializeOwned>(res: Vec<u8>) -> Result<T, String> {
+    serde_json::from_slice(&res).map_err(|e| e.to_string())
+}
+
+/// Builds an `Error::Json` with the command and the start of its output.
+pub(crate) fn json_error<M: ToString>(cmd: &str, output: &[u8], message: M) -> Error {
+    let output = &output[..output.len().min(JSON_EXCERPT)];
+    Error::Json {
+        cmd: cmd.to_string(),
+        excerpt: String::from_utf8_lossy(output).into_owned(),
+        message: message.to_string(),
+    }
+}
+
+fn process_result(mut res: Vec<u8>, state: &PipeState) -> Result<String, Error> {
+    if state.strip_ansi {
+        strip_ansi(&mut res);
+    }
+    match String::from_utf8(res) {
+        Ok(res) => Ok(res),
+        Err(e) => match state.utf8 {
+            Utf8Policy::Strict => Err(Error::Utf8(e)),
+            Utf8Policy::Lossy => Ok(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+        },
+    }
+}
+
+/// Removes ANSI escape sequences (colors, cursor movements) from `res`.
+pub fn strip_ansi(res: &mut Vec<u8>) {
+    let mut out = 0;
+    let mut i = 0;
+    while i < res.len() {
+        if res[i] != 0x1b {
+            res[out] = res[i];
+            out += 1;
+            i += 1;
+            continue;
+        }
+        i += 1;
+        if i < res.len() && res[i] == b'[' {
+            // CSI: parameters up to a final byte in 0x40..=0x7e.
+            i += 1;
+            while i < res.len() && !(0x40..=0x7e).contains(&res[i]) {
+                i += 1;
+            }
+        } else {
+            // Other escapes: intermediate bytes, then a final one.
+            while i < res.len() && (0x20..=0x2f).contains(&res[i]) {
+                i += 1;
+            }
+        }
+        i += 1;
+    }
+    res.truncate(out);
+}
+
+/// Rejects commands that r2 would read as several lines, and so answer
+/// with several replies.
+///
+/// Newlines in arguments can be escaped with `cmd::escape_arg()`.
+fn single_line(cmd: &str) -> Result<(), Error> {
+    if cmd.contains('\n') {
+        return Err(Error::InvalidCommand(format!(
+            "Command contains a newline: {:?}",
+            cmd
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that a suffix like `@ addr` would apply to the whole of `cmd`.
+pub(crate) fn suffixable(cmd: &str) -> Result<&str, Error> {
+    let cmd = cmd.trim();
+    if cmd.is_empty() {
+        return Err(Error::InvalidCommand("Empty command".to_string()));
+    }
+    if cmd.contains('@') || cmd.contains(';') {
+        return Err(Error::InvalidCommand(format!(
+            "Command already has a seek or chain: {}",
+            cmd
+        )));
+    }
+    Ok(cmd)
+}
+
+/// Starts of the messages r2 prints on stdout when a command fails.
+const R2_ERROR_PREFIXES: &[&str] = &[
+    "Usage:",
+    "|Usage:",
+    "| Usage:",
+    "ERROR:",
+    "[ERROR]",
+    "Error:",
+    "error:",
+    "Cannot ",
+    "Invalid ",
+    "Unknown command",
+    "Missing argument",
+    "No function",
+];
+
+/// The first line of `res` when it is an error message of r2.
+pub(crate) fn r2_error_message(res: &str) -> Option<&str> {
+    let line = res.lines().map(str::trim).find(|l| !l.is_empty())?;
+    if R2_ERROR_PREFIXES.iter().any(|p| line.starts_with(p)) {
+        Some(line)
+    } else {
+        None
+    }
+}
+
+/// Rejects the paths r2 would split or interpret in a command.
+pub(crate) fn file_arg(path: &str) -> Result<(), Error> {
+    let bad = |c: char| c.is_whitespace() || ";|>@~`\"'$".contains(c);
+    if path.is_empty() || path.contains(bad) {
+        return Err(Error::InvalidCommand(format!(
+            "Unusable file name: {:?}",
+            path
+        )));
     }
-    let result = str::from_utf8(&res[..len - 1])
-        .map_err(|e| e.to_string())?
-        .to_string();
-    Ok(result)
+    Ok(())
+}
+
+fn at_suffix(addr: Addr, cmd: &str) -> Result<String, Error> {
+    Ok(format!("{} @ {}", suffixable(cmd)?, addr))
 }
 
 #[macro_export]
@@ -113,12 +839,16 @@ macro_rules! open_pipe {
 
 impl R2Pipe {
     #[cfg(not(windows))]
-    pub fn open() -> Result<R2Pipe, &'static str> {
+    pub fn open() -> Result<R2Pipe, Error> {
         use std::os::unix::io::FromRawFd;
 
         let (f_in, f_out) = match R2Pipe::in_session() {
             Some(x) => x,
-            None => return Err("Pipe not open. Please run from r2"),
+            None => {
+                return Err(Error::Other(
+                    "Pipe not open. Please run from r2".to_string(),
+                ))
+            }
         };
         let res = unsafe {
             // dup file descriptors to avoid from_raw_fd ownership issue
@@ -126,31 +856,540 @@ impl R2Pipe {
             R2PipeLang {
                 read: BufReader::new(File::from_raw_fd(d_in)),
                 write: File::from_raw_fd(d_out),
+                state: PipeState::default(),
             }
         };
+        trace::opened("lang", "", res.state.session);
         Ok(R2Pipe::Lang(res))
     }
 
     #[cfg(windows)]
-    pub fn open() -> Result<R2Pipe, &'static str> {
-        Err("`open()` is not yet supported on windows")
+    pub fn open() -> Result<R2Pipe, Error> {
+        Err(Error::Other(
+            "`open()` is not yet supported on windows".to_string(),
+        ))
+    }
+
+    /// Wraps another implementation of the commands, so the typed
+    /// wrappers can be used on it.
+    ///
+    /// Meant for testing code using the typed API without r2:
+    ///
+    /// ```
+    /// use r2pipe::{MockR2Pipe, R2Pipe};
+    ///
+    /// let mock = MockR2Pipe::new().on("e asm.arch", "x86");
+    /// let mut r2 = R2Pipe::from_commands(mock);
+    /// assert_eq!(r2.get_config("asm.arch")?, "x86");
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn from_commands<P: R2PipeCommands + Send + 'static>(pipe: P) -> R2Pipe {
+        let state = PipeState::default();
+        trace::opened("custom", "", state.session);
+        R2Pipe::Custom(R2PipeCustom {
+            inner: Box::new(pipe),
+            state,
+        })
     }
 
-    pub fn cmd(&mut self, cmd: &str) -> Result<String, String> {
+    pub fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let cmd = self.prepare(cmd)?;
+        self.instrument(
+            cmd,
+            |r2| match *r2 {
+                R2Pipe::Pipe(ref mut x) => x.cmd(cmd),
+                R2Pipe::Lang(ref mut x) => x.cmd(cmd),
+                R2Pipe::Tcp(ref mut x) => x.cmd(cmd),
+                R2Pipe::Http(ref mut x) => x.cmd(cmd),
+                R2Pipe::Custom(ref mut x) => x.cmd(cmd),
+            },
+            |res| res.as_bytes(),
+        )
+    }
+
+    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        self.cmd_deserialize(cmd)
+    }
+
+    /// Runs `cmd` for its side effects, discarding its output.
+    ///
+    /// Succeeds once r2 sent back a complete reply, whether or not it is
+    /// empty, which is what most commands changing the session print.
+    pub fn cmd_ok(&mut self, cmd: &str) -> Result<(), Error> {
+        let mut res = Vec::new();
+        self.cmd_into(cmd, &mut res)
+    }
+
+    /// Runs `cmd` like `cmd()`, but fails with `Error::R2Error` when its
+    /// output starts like one of r2's error or usage messages
+    /// (`Cannot find ...`, `Usage: ...`).
+    ///
+    /// This is a heuristic on the text: commands legitimately printing
+    /// such a line, e.g. `ps` of a string starting with `Usage:`, are
+    /// better run with `cmd()`.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// match r2.cmd_checked("afi @ 0x1234").map_err(r2pipe::Error::into_root) {
+    ///     Err(r2pipe::Error::R2Error(e)) => eprintln!("{}", e),
+    ///     res => println!("{}", res?),
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn cmd_checked(&mut self, cmd: &str) -> Result<String, Error> {
+        let res = self.cmd(cmd)?;
+        match r2_error_message(&res) {
+            Some(e) => Err(self.with_session(Error::R2Error(e.to_string()))),
+            None => Ok(res),
+        }
+    }
+
+    /// Runs `cmd`, reading its output into `buf` instead of a new `String`.
+    ///
+    /// `buf` is cleared first, so hot loops can reuse one allocation for all
+    /// their commands.
+    pub fn cmd_into(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+        let cmd = self.prepare(cmd)?;
+        // Moved in and out, so that the reply can be looked at once read.
+        let mut out = mem::take(buf);
+        let res = self.instrument(
+            cmd,
+            |r2| {
+                match *r2 {
+                    R2Pipe::Pipe(ref mut x) => x.cmd_into(cmd, &mut out),
+                    R2Pipe::Lang(ref mut x) => x.cmd_into(cmd, &mut out),
+                    R2Pipe::Tcp(ref mut x) => x.cmd_into(cmd, &mut out),
+                    R2Pipe::Http(ref mut x) => x.cmd_into(cmd, &mut out),
+                    R2Pipe::Custom(ref mut x) => x.cmd_into(cmd, &mut out),
+                }
+                .map(|_| out)
+            },
+            |out| out,
+        );
+        *buf = res?;
+        Ok(())
+    }
+
+    /// Runs `cmd` and returns its raw output, skipping UTF-8 validation.
+    ///
+    /// Meant for commands printing binary data such as `pr`. Note that on
+    /// the NUL framed pipes a NUL byte in the output ends the reply, and
+    /// the rest of it is taken as the reply of the next command: use
+    /// `read_bytes()` for arbitrary data, or `resync()` after the fact.
+    pub fn cmd_bytes(&mut self, cmd: &str) -> Result<Vec<u8>, Error> {
+        let capacity = match *self {
+            R2Pipe::Pipe(ref x) => x.response_capacity,
+            _ => 0,
+        };
+        let mut res: Vec<u8> = Vec::with_capacity(capacity);
+        self.cmd_into(cmd, &mut res)?;
+        Ok(res)
+    }
+
+    /// Runs all the `cmds`, returning their outputs in the same order.
+    ///
+    /// On spawned pipes the commands are written together and the replies
+    /// read afterwards, saving a round trip per command. Other pipes run
+    /// them one after the other.
+    ///
+    /// The batch stops at the first command failing, with an `Error::Batch`
+    /// telling which one (see `Error::batch_index()`). On spawned pipes the
+    /// replies of the commands already written after it are dropped, so
+    /// that they aren't taken for those of the next commands. After
+    /// `Error::Recovered` nothing of the batch is left in the new session.
+    pub fn cmd_batch(&mut self, cmds: &[&str]) -> Result<Vec<String>, Error> {
+        let cmds = cmds
+            .iter()
+            .enumerate()
+            .map(|(i, c)| self.prepare(c).map_err(|e| e.in_batch(i)))
+            .collect::<Result<Vec<_>, _>>()?;
         match *self {
-            R2Pipe::Pipe(ref mut x) => x.cmd(cmd.trim()),
-            R2Pipe::Lang(ref mut x) => x.cmd(cmd.trim()),
-            R2Pipe::Tcp(ref mut x) => x.cmd(cmd.trim()),
-            R2Pipe::Http(ref mut x) => x.cmd(cmd.trim()),
+            R2Pipe::Pipe(ref mut x) => {
+                let start = Instant::now();
+                let res = trace::batch(cmds.len(), x.state.session, || x.cmd_batch(&cmds));
+                // The replies come back together, so share the time evenly.
+                let latency = start.elapsed() / cmds.len().max(1) as u32;
+                for (i, cmd) in cmds.iter().enumerate() {
+                    let reply = match res {
+                        Ok(ref res) => Ok(res[i].as_bytes()),
+                        Err(ref e) => Err(e.to_string()),
+                    };
+                    x.state.record(cmd, latency, reply)?;
+                }
+                res.map_err(|e| self.with_session(e))
+            }
+            _ => cmds
+                .iter()
+                .enumerate()
+                .map(|(i, c)| self.cmd(c).map_err(|e| e.in_batch(i)))
+                .collect(),
         }
     }
 
-    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, String> {
+    /// Runs `f`, the command `cmd`, with tracing and the stats updated.
+    fn instrument<T, F, S>(&mut self, cmd: &str, f: F, reply: S) -> Result<T, Error>
+    where
+        F: FnOnce(&mut R2Pipe) -> Result<T, Error>,
+        S: Fn(&T) -> &[u8],
+    {
+        let (session, redact) = (self.state().session, self.state().redact);
+        let start = Instant::now();
+        let res = trace::command(cmd, session, redact, || f(self), |res| reply(res).len());
+        let latency = start.elapsed();
+        let seen = match res {
+            Ok(ref res) => Ok(reply(res)),
+            Err(ref e) => Err(e.to_string()),
+        };
+        self.state_mut()
+            .record(cmd, latency, seen)
+            .map_err(|e| self.with_session(e))?;
+        res.map_err(|e| self.with_session(e))
+    }
+
+    /// `e` wrapped in an `Error::Session` for this pipe, unless it already
+    /// is.
+    pub(crate) fn with_session(&self, e: Error) -> Error {
+        match e {
+            Error::Session { .. } => e,
+            e => Error::Session {
+                session: self.state().session,
+                stderr: match *self {
+                    R2Pipe::Pipe(ref x) if !matches!(e, Error::ProcessExited { .. }) => {
+                        x.stderr_since_mark()
+                    }
+                    _ => String::new(),
+                },
+                error: Box::new(e),
+            },
+        }
+    }
+
+    /// Keeps the last `capacity` commands, with an excerpt of their
+    /// replies, for `history()`. 0 turns the history off, which is the
+    /// default.
+    pub fn set_history(&mut self, capacity: usize) {
+        self.state_mut().history = match capacity {
+            0 => None,
+            n => Some(History::new(n)),
+        };
+    }
+
+    /// Logs every command from now on to `log`, `None` to stop.
+    ///
+    /// Commands are logged whole, even with `set_redact_commands()`. When
+    /// the log can't be written, the command fails with `Error::Io`
+    /// although r2 ran it.
+    pub fn set_audit_log(&mut self, log: Option<AuditLog>) {
+        self.state_mut().audit = log;
+    }
+
+    /// Identifies this pipe in the tracing spans, the audit log, the
+    /// lifecycle events and its errors (`Error::Session`). It is kept when
+    /// r2 is spawned again.
+    pub fn session_id(&self) -> SessionId {
+        self.state().session
+    }
+
+    /// Calls `hook` on each `LifecycleEvent` from now on, `None` to stop.
+    pub fn set_lifecycle_hook(&mut self, hook: Option<LifecycleHook>) {
+        self.state_mut().lifecycle = hook;
+    }
+
+    /// The last commands run, oldest first.
+    ///
+    /// Meant for diagnosing how a long automated session got to its
+    /// current state.
+    pub fn history(&self) -> impl Iterator<Item = &HistoryEntry> + '_ {
+        self.state().history.iter().flat_map(|h| h.entries())
+    }
+
+    /// Counters of the commands run so far.
+    pub fn stats(&self) -> &PipeStats {
+        &self.state().stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.state_mut().stats = PipeStats::default();
+    }
+
+    /// Runs `cmd` and deserializes its JSON output into `T`.
+    ///
+    /// Mostly used with the models in `r2pipe::structs`.
+    ///
+    /// `T` is read straight from the bytes of the reply, without a
+    /// `String` or a `Value` in between, which saves copying the large
+    /// outputs of `aflj` or `pdj`. Only rizin's replies go through a
+    /// `Value`, see `Backend`.
+    pub fn cmd_deserialize<T: DeserializeOwned>(&mut self, cmd: &str) -> Result<T, Error> {
+        let res = self.cmd_json_bytes(cmd)?;
+        match self.state().backend {
+            Backend::Radare2 => parse_json(cmd, res),
+            backend => parse_json::<Value>(cmd, res).and_then(|v| backend.deserialize(v)),
+        }
+        .map_err(|e| self.with_session(e))
+    }
+
+    /// The output of `cmd` as bytes, without colors when asked by
+    /// `set_strip_ansi()`.
+    pub(crate) fn cmd_json_bytes(&mut self, cmd: &str) -> Result<Vec<u8>, Error> {
+        let mut res = self.cmd_bytes(cmd)?;
+        if self.state().strip_ansi {
+            strip_ansi(&mut res);
+        }
+        Ok(res)
+    }
+
+    /// Runs `cmd` and validates its output as JSON without parsing it.
+    ///
+    /// Cheaper than `cmdj()` for outputs that are only forwarded or
+    /// selectively deserialized later.
+    pub fn cmd_raw_json(&mut self, cmd: &str) -> Result<Box<RawValue>, Error> {
+        let res = self.cmd(cmd)?;
+        if res.trim().is_empty() {
+            return Err(self.with_session(Error::EmptyResponse(cmd.to_string())));
+        }
+        let head = res.as_bytes()[..res.len().min(JSON_EXCERPT)].to_vec();
+        RawValue::from_string(res).map_err(|e| self.with_session(json_error(cmd, &head, e)))
+    }
+
+    /// Runs `cmd` and returns a reader over its reply.
+    ///
+    /// On the NUL framed pipes the reply is read while it is consumed; other
+    /// pipes read it all first.
+    pub(crate) fn cmd_reader(&mut self, cmd: &str) -> Result<Box<dyn Read + '_>, Error> {
+        let cmd = self.prepare(cmd)?;
+        single_line(cmd).map_err(|e| self.with_session(e))?;
+        let (timeout, by_deadline) = self
+            .state()
+            .time_left(cmd)
+            .map_err(|e| self.with_session(e))?;
+        let start = Instant::now();
+        let written = match *self {
+            R2Pipe::Pipe(ref mut x) => {
+                x.mark_stderr();
+                write_cmds(&mut x.write, &[cmd]).map_err(|e| x.exited(e.into()))
+            }
+            R2Pipe::Lang(ref mut x) => x.write.write_all(cmd.as_bytes()).map_err(Error::from),
+            _ => {
+                let res = self.cmd_bytes(cmd)?;
+                return Ok(Box::new(io::Cursor::new(res)));
+            }
+        };
+        let limit = match *self {
+            R2Pipe::Pipe(_) => timeout.map(|t| (start + t, t, by_deadline)),
+            _ => None,
+        };
+        let mut reply = StreamedReply {
+            r2: self,
+            cmd: cmd.to_string(),
+            start,
+            limit,
+            head: Vec::new(),
+            len: 0,
+            done: false,
+        };
+        if let Err(e) = written {
+            return Err(reply.finish(Err(e)).unwrap_err());
+        }
+        Ok(Box::new(reply))
+    }
+
+    /// Discards any pending output, so the next reply read is the one of
+    /// the next command.
+    ///
+    /// A reply with an embedded NUL byte is split in two by the framing, and
+    /// every later command gets the reply of the previous one. This echoes a
+    /// unique marker and drops everything up to it. Pipes with a connection
+    /// per command can't get out of sync, so this does nothing on them.
+    ///
+    /// On spawned pipes the wait is bounded by the timeout and the deadline:
+    /// when they pass, r2 is interrupted in case a command is still running,
+    /// and fails with `Error::Desync` if the marker doesn't come shortly
+    /// after.
+    pub fn resync(&mut self) -> Result<(), Error> {
+        let (cmd, marker) = sync_marker();
+        let mut buf = Vec::new();
+        match *self {
+            R2Pipe::Pipe(ref mut x) => x
+                .state
+                .time_left(&cmd)
+                .and_then(|(timeout, _)| x.resync(&cmd, &marker, timeout)),
+            R2Pipe::Lang(ref mut x) => (|| -> Result<(), Error> {
+                x.write.write_all(cmd.as_bytes())?;
+                loop {
+                    read_frame(&mut x.read, &mut buf)?;
+                    if buf.trim_ascii_end().ends_with(marker.as_bytes()) {
+                        return Ok(());
+                    }
+                }
+            })(),
+            _ => Ok(()),
+        }
+        .map_err(|e| self.with_session(e))
+    }
+
+    /// Runs `cmd` with the cursor temporarily at `addr` (`cmd @ 0x...`).
+    ///
+    /// The command must not contain its own temporary seek or a `;` chain,
+    /// since the suffix would then apply to only part of it.
+    pub fn cmd_at<A: Into<Addr>>(&mut self, addr: A, cmd: &str) -> Result<String, Error> {
+        let cmd = at_suffix(addr.into(), cmd)?;
+        self.cmd(&cmd)
+    }
+
+    /// Same as `cmd_at()` but parses the output as JSON.
+    pub fn cmdj_at<A: Into<Addr>>(&mut self, addr: A, cmd: &str) -> Result<Value, Error> {
+        let cmd = at_suffix(addr.into(), cmd)?;
+        self.cmdj(&cmd)
+    }
+
+    /// Sets how output that isn't valid UTF-8 is handled, `Strict` by
+    /// default.
+    pub fn set_utf8_policy(&mut self, policy: Utf8Policy) {
+        self.state_mut().utf8 = policy;
+    }
+
+    /// Makes commands fail once they run for longer than `timeout`.
+    ///
+    /// On spawned pipes a timed out command is interrupted, giving
+    /// `Error::Timeout`. If r2 doesn't respond to that either, it is
+    /// spawned again, losing the state of the session, and the command fails
+    /// with `Error::Recovered`. TCP pipes time out with `Error::Timeout`,
+    /// other pipes ignore the setting.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.state_mut().timeout = timeout;
+    }
+
+    /// Makes commands fail with `Error::DeadlineExceeded` from `deadline`
+    /// on, bounding the time of a whole workflow rather than of each
+    /// command.
+    ///
+    /// A command still running at the deadline times out as with
+    /// `set_timeout()`, on the pipes supporting it.
+    ///
+    /// ```no_run
+    /// use std::time::{Duration, Instant};
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.set_deadline(Some(Instant::now() + Duration::from_secs(300)));
+    /// match r2.analyze(r2pipe::AnalysisLevel::Deep) {
+    ///     Err(ref e) if matches!(e.root(), r2pipe::Error::DeadlineExceeded { .. }) => {
+    ///         eprintln!("skipped")
+    ///     }
+    ///     res => res?,
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.state_mut().deadline = deadline;
+    }
+
+    /// Removes ANSI escape codes from the output of `cmd()` and `cmdj()`.
+    ///
+    /// Useful on pipes not spawned by this crate, where colors may be
+    /// enabled by the user's configuration.
+    pub fn set_strip_ansi(&mut self, strip: bool) {
+        self.state_mut().strip_ansi = strip;
+    }
+
+    /// Sends commands exactly as given, instead of trimming them first.
+    ///
+    /// Needed when leading or trailing whitespace is part of the command,
+    /// as in `w ` followed by a string ending with spaces.
+    pub fn set_keep_whitespace(&mut self, keep: bool) {
+        self.state_mut().keep_whitespace = keep;
+    }
+
+    /// Retries the commands failing for transient reasons, according to
+    /// `policy`.
+    ///
+    /// Only the TCP and HTTP pipes retry, since they open a connection per
+    /// command: on the other pipes a failed command may have left its reply
+    /// behind, or killed r2. Commands are sent again as they are, so avoid
+    /// retrying commands that aren't idempotent.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.state_mut().retry = policy;
+    }
+
+    /// Throttles the commands sent with `limiter`.
+    ///
+    /// Only the TCP and HTTP pipes are throttled, each attempt of a retried
+    /// command counting as one command.
+    pub fn set_rate_limiter(&mut self, limiter: Option<RateLimiter>) {
+        self.state_mut().limiter = limiter;
+    }
+
+    /// Only log the name of the commands, not their arguments.
+    ///
+    /// Has no effect without the `tracing` feature.
+    pub fn set_redact_commands(&mut self, redact: bool) {
+        self.state_mut().redact = redact;
+    }
+
+    /// Checks every command against `policy` before sending it.
+    ///
+    /// The typed wrappers go through the same check, so a restrictive
+    /// policy also makes those using denied commands fail.
+    pub fn set_command_policy(&mut self, policy: Option<CommandPolicy>) {
+        self.state_mut().policy = policy;
+    }
+
+    /// The program answering the commands, guessed from the executable
+    /// name on spawned pipes, radare2 for the others.
+    pub fn backend(&self) -> Backend {
+        self.state().backend
+    }
+
+    /// Tells which program answers the commands, e.g. for a rizin reached
+    /// over HTTP.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.state_mut().backend = backend;
+    }
+
+    /// Trims `cmd`, unless told not to by `set_keep_whitespace()`, and
+    /// checks it against the command policy.
+    fn prepare<'a>(&self, cmd: &'a str) -> Result<&'a str, Error> {
+        let state = self.state();
+        if let Some(ref policy) = state.policy {
+            policy.check(cmd).map_err(|e| self.with_session(e))?;
+        }
+        state.time_left(cmd).map_err(|e| self.with_session(e))?;
+        Ok(if state.keep_whitespace {
+            // Still drop the line ending, which is added when writing.
+            cmd.strip_suffix('\n').unwrap_or(cmd)
+        } else {
+            cmd.trim()
+        })
+    }
+
+    fn state(&self) -> &PipeState {
+        match *self {
+            R2Pipe::Pipe(ref x) => &x.state,
+            R2Pipe::Lang(ref x) => &x.state,
+            R2Pipe::Tcp(ref x) => &x.state,
+            R2Pipe::Http(ref x) => &x.state,
+            R2Pipe::Custom(ref x) => &x.state,
+        }
+    }
+
+    fn state_mut(&mut self) -> &mut PipeState {
         match *self {
-            R2Pipe::Pipe(ref mut x) => x.cmdj(cmd.trim()),
-            R2Pipe::Lang(ref mut x) => x.cmdj(cmd.trim()),
-            R2Pipe::Tcp(ref mut x) => x.cmdj(cmd.trim()),
-            R2Pipe::Http(ref mut x) => x.cmdj(cmd.trim()),
+            R2Pipe::Pipe(ref mut x) => &mut x.state,
+            R2Pipe::Lang(ref mut x) => &mut x.state,
+            R2Pipe::Tcp(ref mut x) => &mut x.state,
+            R2Pipe::Http(ref mut x) => &mut x.state,
+            R2Pipe::Custom(ref mut x) => &mut x.state,
+        }
+    }
+
+    /// The last few KiB r2 wrote to its stderr, on spawned pipes.
+    ///
+    /// r2 reports problems such as a missing plugin or an unreadable file
+    /// there, while the command itself returns no output.
+    pub fn last_stderr(&self) -> Option<String> {
+        match *self {
+            R2Pipe::Pipe(ref x) => Some(x.last_stderr()),
+            _ => None,
         }
     }
 
@@ -160,6 +1399,54 @@ impl R2Pipe {
             R2Pipe::Lang(ref mut x) => x.close(),
             R2Pipe::Tcp(ref mut x) => x.close(),
             R2Pipe::Http(ref mut x) => x.close(),
+            R2Pipe::Custom(ref mut x) => x.close(),
+        }
+        let session = self.state().session;
+        trace::closed(self.kind(), session);
+        self.state().emit(LifecycleEvent::Closed { session });
+    }
+
+    /// Whether r2 runs on this machine, sharing its filesystem.
+    pub(crate) fn is_local(&self) -> bool {
+        matches!(*self, R2Pipe::Pipe(_) | R2Pipe::Lang(_))
+    }
+
+    /// Runs `cmd`, writing `path`, and when r2 is local checks the size
+    /// of what was written with `ok`.
+    pub(crate) fn dump_to(
+        &mut self,
+        path: &str,
+        cmd: &str,
+        ok: impl Fn(u64) -> bool,
+    ) -> Result<(), Error> {
+        let local = self.is_local();
+        if local {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        self.cmd_ok(cmd)?;
+        if local {
+            let written = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            if !ok(written) {
+                return Err(Error::Other(format!(
+                    "{} wrote {} bytes to {}",
+                    cmd, written, path
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of the transport, for the logs.
+    fn kind(&self) -> &'static str {
+        match *self {
+            R2Pipe::Pipe(_) => "spawn",
+            R2Pipe::Lang(_) => "lang",
+            R2Pipe::Tcp(_) => "tcp",
+            R2Pipe::Http(_) => "http",
+            R2Pipe::Custom(_) => "custom",
         }
     }
 
@@ -181,61 +1468,187 @@ impl R2Pipe {
     }
 
     /// Creates a new R2PipeSpawn.
+    ///
+    /// Two environment variables set defaults for every program using this
+    /// crate: `R2PIPE_R2` is the r2 executable to run when
+    /// `R2PipeSpawnOptions::exepath` is empty, and the whitespace separated
+    /// flags in `R2PIPE_R2_ARGS` (e.g. `-2 -e bin.cache=true`) are passed
+    /// before `R2PipeSpawnOptions::args`.
     pub fn spawn<T: AsRef<str>>(
         name: T,
         opts: Option<R2PipeSpawnOptions>,
-    ) -> Result<R2Pipe, &'static str> {
+    ) -> Result<R2Pipe, Error> {
         if name.as_ref() == "" && R2Pipe::in_session().is_some() {
             return R2Pipe::open();
         }
+        check_target(name.as_ref())?;
+        let hook = opts.as_ref().and_then(|o| o.on_lifecycle.clone());
+        R2Pipe::spawn_session(name.as_ref(), opts, SessionId::next(), hook)
+    }
 
+    /// Spawns r2 for the pipe `session`, see `spawn()`.
+    fn spawn_session(
+        name: &str,
+        opts: Option<R2PipeSpawnOptions>,
+        session: SessionId,
+        lifecycle: Option<LifecycleHook>,
+    ) -> Result<R2Pipe, Error> {
         let exepath = match opts {
-            Some(ref opt) => opt.exepath.clone(),
-            _ => "r2".to_owned(),
+            Some(ref opt) if !opt.exepath.is_empty() => opt.exepath.clone(),
+            _ => env::var("R2PIPE_R2")
+                .ok()
+                .filter(|p| !p.is_empty())
+                .unwrap_or_else(|| "r2".to_owned()),
         };
+        let env_args = env::var("R2PIPE_R2_ARGS").unwrap_or_default();
         let args = match opts {
             Some(ref opt) => opt.args.clone(),
             _ => vec![],
         };
-        let path = Path::new(name.as_ref());
-        let child = Command::new(exepath)
+        let (read_buffer_size, response_capacity) = match opts {
+            Some(ref opt) => (opt.read_buffer_size, opt.response_capacity),
+            _ => (0, 0),
+        };
+        let path = Path::new(name);
+        let (hardened, isolated) = match opts {
+            Some(ref opt) => (opt.hardened, opt.isolate_network),
+            _ => (false, false),
+        };
+        let mut command = Command::new(&exepath);
+        command
             .arg("-q0")
-            .args(&args)
+            .args(env_args.split_whitespace())
+            .args(&args);
+        if hardened {
+            command.arg("-S");
+        }
+        if isolated {
+            isolate_network(&mut command)?;
+        }
+        let start = Instant::now();
+        let mut child = command
             .arg(path)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()
-            .map_err(|_| "Unable to spawn r2.")?;
+            .map_err(|source| Error::Spawn {
+                exepath: exepath.clone(),
+                source,
+            })?;
 
-        let sin = child.stdin.unwrap();
-        let mut sout = child.stdout.unwrap();
+        let (sin, mut sout, serr) =
+            match (child.stdin.take(), child.stdout.take(), child.stderr.take()) {
+                (Some(sin), Some(sout), Some(serr)) => (sin, sout, serr),
+                _ => {
+                    let _ = child.kill();
+                    return Err(Error::Handshake("r2's stdio is not piped".to_string()));
+                }
+            };
+        let stderr = capture_stderr(serr);
+        let state = PipeState {
+            backend: Backend::from_exepath(&exepath),
+            session,
+            lifecycle,
+            ..PipeState::default()
+        };
+        state.emit(LifecycleEvent::Spawned {
+            session,
+            target: name.to_string(),
+        });
 
         // flush out the initial null byte.
         let mut w = [0; 1];
-        sout.read_exact(&mut w).unwrap();
+        if let Err(e) = sout.read_exact(&mut w) {
+            return Err(handshake_failed(child, e, &stderr));
+        }
+        let handshake = start.elapsed();
 
+        let read = match read_buffer_size {
+            0 => BufReader::new(sout),
+            n => BufReader::with_capacity(n, sout),
+        };
         let res = R2PipeSpawn {
-            read: BufReader::new(sout),
+            child,
+            read,
             write: sin,
+            response_capacity,
+            stderr,
+            stderr_mark: 0,
+            state,
+            target: name.to_string(),
+            opts: opts.clone(),
+        };
+        let mut r2 = R2Pipe::Pipe(res);
+        let opts = opts.unwrap_or_default();
+        r2.settle(&opts)?;
+        trace::opened("spawn", name, session);
+        let info = ReadyInfo {
+            target: name.to_string(),
+            handshake,
+            settle: start.elapsed() - handshake,
         };
+        if let Some(ref on_ready) = opts.on_ready {
+            on_ready(&info);
+        }
+        r2.state().emit(LifecycleEvent::Ready { session, info });
+        Ok(r2)
+    }
 
-        Ok(R2Pipe::Pipe(res))
+    /// Gets a freshly spawned r2 ready for the first command.
+    fn settle(&mut self, opts: &R2PipeSpawnOptions) -> Result<(), Error> {
+        if !opts.keep_colors {
+            self.cmd_ok("e scr.color=0")?;
+        }
+        for cmd in &opts.init_cmds {
+            self.cmd_ok(cmd)?;
+        }
+        if opts.hardened {
+            self.cmd_ok("e cfg.sandbox=true")?;
+            if self.get_config("cfg.sandbox")? != "true" {
+                return Err(Error::Unsupported(
+                    "r2 didn't enable its sandbox".to_string(),
+                ));
+            }
+            self.set_command_policy(Some(CommandPolicy::new()));
+        }
+        if !opts.skip_settle {
+            self.resync()?;
+        }
+        Ok(())
     }
 
     /// Creates a new R2PipeTcp
-    pub fn tcp<A: ToSocketAddrs>(addr: A) -> Result<R2Pipe, &'static str> {
+    pub fn tcp<A: ToSocketAddrs>(addr: A) -> Result<R2Pipe, Error> {
         // use `connect` to figure out which socket address works
-        let stream = TcpStream::connect(addr).map_err(|_| "Unable to connect TCP stream")?;
-        let addr = stream
-            .peer_addr()
-            .map_err(|_| "Unable to get peer address")?;
-        Ok(R2Pipe::Tcp(R2PipeTcp { socket_addr: addr }))
+        let stream = TcpStream::connect(addr)?;
+        let addr = stream.peer_addr()?;
+        let state = PipeState::default();
+        trace::opened("tcp", &addr.to_string(), state.session);
+        Ok(R2Pipe::Tcp(R2PipeTcp {
+            socket_addr: addr,
+            state,
+        }))
     }
 
     /// Creates a new R2PipeHttp
-    pub fn http(host: &str) -> Result<R2Pipe, &'static str> {
+    pub fn http(host: &str) -> Result<R2Pipe, Error> {
+        R2Pipe::http_with_options(host, R2PipeHttpOptions::default())
+    }
+
+    /// Creates a new R2PipeHttp with the given options.
+    pub fn http_with_options(host: &str, opts: R2PipeHttpOptions) -> Result<R2Pipe, Error> {
+        let client = reqwest::Client::builder()
+            .gzip(opts.compression)
+            .build()
+            .map_err(|e| Error::Http(e.to_string()))?;
+        let state = PipeState::default();
+        trace::opened("http", host, state.session);
         Ok(R2Pipe::Http(R2PipeHttp {
             host: host.to_string(),
+            client,
+            compression: opts.compression,
+            state,
         }))
     }
 
@@ -256,19 +1669,27 @@ impl R2Pipe {
 
         for n in 0..names.len() {
             let (htx, rx) = mpsc::channel();
-            let (tx, hrx) = mpsc::channel();
+            let (tx, hrx) = mpsc::channel::<String>();
             let name = names[n];
             let opt = opts[n].clone();
             let cb = callback.clone();
             let t = thread::spawn(move || {
-                let mut r2 = R2Pipe::spawn(name, opt).unwrap();
-                loop {
-                    let cmd: String = hrx.recv().unwrap();
+                // On errors the thread ends, and so `recv()` fails.
+                let mut r2 = match R2Pipe::spawn(name, opt) {
+                    Ok(r2) => r2,
+                    Err(_) => return,
+                };
+                while let Ok(cmd) = hrx.recv() {
                     if cmd == "q" {
                         break;
                     }
-                    let res = r2.cmdj(&cmd).unwrap().to_string();
-                    htx.send(res.clone()).unwrap();
+                    let res = match r2.cmdj(&cmd) {
+                        Ok(res) => res.to_string(),
+                        Err(_) => break,
+                    };
+                    if htx.send(res.clone()).is_err() {
+                        break;
+                    }
                     if let Some(cbs) = cb.clone() {
                         thread::spawn(move || {
                             cbs(n as u16, res);
@@ -301,44 +1722,284 @@ impl R2PipeThread {
 }
 
 impl R2PipeSpawn {
-    pub fn cmd(&mut self, cmd: &str) -> Result<String, String> {
-        let cmd = cmd.to_owned() + "\n";
-        self.write
-            .write_all(cmd.as_bytes())
-            .map_err(|e| e.to_string())?;
+    pub fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let mut res: Vec<u8> = Vec::with_capacity(self.response_capacity);
+        self.cmd_into(cmd, &mut res)?;
+        process_result(res, &self.state)
+    }
 
-        let mut res: Vec<u8> = Vec::new();
-        self.read
-            .read_until(0u8, &mut res)
-            .map_err(|e| e.to_string())?;
-        process_result(res)
+    pub fn cmd_into(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+        single_line(cmd)?;
+        self.mark_stderr();
+        if let Err(e) = write_cmds(&mut self.write, &[cmd]) {
+            return Err(self.exited(e.into()));
+        }
+        self.read_reply(cmd, buf)
     }
 
-    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, String> {
-        let result = self.cmd(cmd)?;
-        if result == "" {
-            return Err("Empty JSON".to_string());
+    /// Reads the reply of `cmd`, recovering the session if it times out.
+    fn read_reply(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+        let (timeout, by_deadline) = self.state.time_left(cmd)?;
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return read_frame(&mut self.read, buf).map_err(|e| self.exited(e)),
+        };
+        match read_frame_by(&mut self.read, buf, Instant::now() + timeout) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(match self.recover(cmd, timeout, buf) {
+                Error::Timeout { cmd, .. } if by_deadline => Error::DeadlineExceeded { cmd },
+                e => e,
+            }),
+            Err(e) => Err(self.exited(e)),
         }
-        serde_json::from_str(&result).map_err(|e| e.to_string())
+    }
+
+    /// Gets the session back after `cmd` timed out.
+    ///
+    /// r2 is first asked to abort the command with a SIGINT. If it doesn't
+    /// end its reply shortly after, it is killed and spawned again with the
+    /// same target and options.
+    fn recover(&mut self, cmd: &str, after: Duration, buf: &mut Vec<u8>) -> Error {
+        if self.interrupt() {
+            match read_frame_by(&mut self.read, buf, Instant::now() + INTERRUPT_GRACE) {
+                Ok(true) => {
+                    return Error::Timeout {
+                        cmd: cmd.to_string(),
+                        after,
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => return self.exited(e),
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let session = self.state.session;
+        self.state.emit(LifecycleEvent::Killed { session });
+        let lifecycle = self.state.lifecycle.clone();
+        match R2Pipe::spawn_session(&self.target, self.opts.clone(), session, lifecycle) {
+            Ok(R2Pipe::Pipe(mut fresh)) => {
+                mem::swap(&mut fresh.state, &mut self.state);
+                *self = fresh;
+                Error::Recovered {
+                    cmd: cmd.to_string(),
+                    after,
+                }
+            }
+            Ok(_) => Error::Other("Respawning r2 opened a different pipe".to_string()),
+            Err(e) => e,
+        }
+    }
+
+    /// Drops the replies up to the one of `cmd`, which echoes `marker`,
+    /// interrupting r2 if it doesn't come within `timeout`.
+    fn resync(&mut self, cmd: &str, marker: &str, timeout: Option<Duration>) -> Result<(), Error> {
+        if let Err(e) = write_cmds(&mut self.write, &[cmd]) {
+            return Err(self.exited(e.into()));
+        }
+        let mut by = timeout.map(|t| Instant::now() + t);
+        let mut interrupted = false;
+        let mut buf = Vec::new();
+        loop {
+            let read = match by {
+                Some(by) => read_frame_by(&mut self.read, &mut buf, by),
+                None => read_frame(&mut self.read, &mut buf).map(|_| true),
+            };
+            match read {
+                Ok(true) if buf.trim_ascii_end().ends_with(marker.as_bytes()) => return Ok(()),
+                Ok(true) => {}
+                Ok(false) if !interrupted && self.interrupt() => {
+                    interrupted = true;
+                    by = Some(Instant::now() + INTERRUPT_GRACE);
+                }
+                Ok(false) => {
+                    return Err(Error::Desync(format!(
+                        "r2 didn't echo the marker within {:?}",
+                        timeout.unwrap_or_default()
+                    )))
+                }
+                Err(e) => return Err(self.exited(e)),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn interrupt(&mut self) -> bool {
+        unsafe { libc::kill(self.child.id() as libc::pid_t, libc::SIGINT) == 0 }
+    }
+
+    #[cfg(not(unix))]
+    fn interrupt(&mut self) -> bool {
+        false
+    }
+
+    /// Writes the commands with vectored writes, then reads a reply for each
+    /// of them.
+    pub fn cmd_batch(&mut self, cmds: &[&str]) -> Result<Vec<String>, Error> {
+        for (i, cmd) in cmds.iter().enumerate() {
+            single_line(cmd).map_err(|e| e.in_batch(i))?;
+        }
+        self.mark_stderr();
+        let mut results = Vec::with_capacity(cmds.len());
+        let mut start = 0;
+        while start < cmds.len() {
+            // r2 stops reading its stdin while its stdout is full, so don't
+            // write more than the pipe buffer is guaranteed to hold.
+            let mut len = 0;
+            let mut end = start;
+            while end < cmds.len() && (end == start || len + cmds[end].len() < BATCH_BYTES) {
+                len += cmds[end].len() + 1;
+                end += 1;
+            }
+            if let Err(e) = write_cmds(&mut self.write, &cmds[start..end]) {
+                return Err(self.exited(e.into()).in_batch(start));
+            }
+            for (i, cmd) in cmds.iter().enumerate().take(end).skip(start) {
+                let mut res: Vec<u8> = Vec::with_capacity(self.response_capacity);
+                let reply = self
+                    .read_reply(cmd, &mut res)
+                    .and_then(|()| process_result(res, &self.state));
+                match reply {
+                    Ok(reply) => results.push(reply),
+                    Err(e) => return Err(self.abort_batch(i, end, e)),
+                }
+            }
+            start = end;
+        }
+        Ok(results)
+    }
+
+    /// Stops a batch on the error `e` of its command `index`.
+    ///
+    /// The commands up to `written` were already sent: unless r2 is gone
+    /// or was spawned again, their replies are dropped with a resync,
+    /// interrupting r2 if they take longer than the timeout, which becomes
+    /// the error if it fails.
+    fn abort_batch(&mut self, index: usize, written: usize, e: Error) -> Error {
+        let gone = matches!(e, Error::Recovered { .. } | Error::ProcessExited { .. });
+        if index + 1 == written || gone {
+            return e.in_batch(index);
+        }
+        let (cmd, marker) = sync_marker();
+        let timeout = self.state.timeout.unwrap_or(INTERRUPT_GRACE);
+        match self.resync(&cmd, &marker, Some(timeout)) {
+            Ok(()) => e,
+            Err(desync) => desync,
+        }
+        .in_batch(index)
+    }
+
+    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        let mut res: Vec<u8> = Vec::with_capacity(self.response_capacity);
+        self.cmd_into(cmd, &mut res)?;
+        parse_json(cmd, res)
     }
 
     pub fn close(&mut self) {
         let _ = self.cmd("q!");
     }
+
+    /// The last few KiB r2 wrote to its stderr.
+    pub fn last_stderr(&self) -> String {
+        let tail = self.stderr.lock().unwrap_or_else(|e| e.into_inner());
+        let (a, b) = tail.buf.as_slices();
+        String::from_utf8_lossy(&[a, b].concat()).into_owned()
+    }
+
+    /// What r2 wrote to its stderr since the last command was sent, as far
+    /// as it is still in the tail.
+    fn stderr_since_mark(&self) -> String {
+        let read = || self.stderr.lock().map(|t| t.read).unwrap_or(0);
+        // r2 writes it right before the reply, let the stderr thread catch
+        // up until it stops getting more.
+        let mut seen = read();
+        for _ in 0..10 {
+            thread::sleep(Duration::from_millis(1));
+            match read() {
+                n if n == seen => break,
+                n => seen = n,
+            }
+        }
+        let tail = self.stderr.lock().unwrap_or_else(|e| e.into_inner());
+        let new = (tail.read - self.stderr_mark).min(tail.buf.len() as u64) as usize;
+        let bytes: Vec<u8> = tail
+            .buf
+            .iter()
+            .skip(tail.buf.len() - new)
+            .copied()
+            .collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    fn mark_stderr(&mut self) {
+        self.stderr_mark = self.stderr.lock().unwrap_or_else(|e| e.into_inner()).read;
+    }
+
+    /// Forgets what r2 wrote to its stderr so far.
+    pub fn clear_stderr(&mut self) {
+        self.stderr
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .buf
+            .clear();
+    }
+
+    /// Turns an I/O failure into `Error::ProcessExited` if r2 is gone.
+    ///
+    /// A dying r2 shows up as a broken pipe or an early end of its output,
+    /// which alone says nothing on what happened to it.
+    fn exited(&mut self, e: Error) -> Error {
+        let eof = match e {
+            Error::ProcessExited { .. } => true,
+            Error::Io(ref e) => e.kind() == io::ErrorKind::BrokenPipe,
+            _ => false,
+        };
+        if !eof {
+            return e;
+        }
+        // The output is closed right before exiting, give it a moment.
+        let mut status = None;
+        for _ in 0..50 {
+            match self.child.try_wait() {
+                Ok(Some(s)) => {
+                    status = Some(s);
+                    break;
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(2)),
+                Err(_) => break,
+            }
+        }
+        // Let the stderr thread catch up with the last messages.
+        for _ in 0..50 {
+            if self.stderr.lock().map(|t| t.closed).unwrap_or(true) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        Error::ProcessExited {
+            status,
+            stderr: self.last_stderr(),
+        }
+    }
 }
 
 impl R2PipeLang {
-    pub fn cmd(&mut self, cmd: &str) -> Result<String, String> {
-        self.write.write_all(cmd.as_bytes()).unwrap();
+    pub fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
         let mut res: Vec<u8> = Vec::new();
-        self.read.read_until(0u8, &mut res).unwrap();
-        process_result(res)
+        self.cmd_into(cmd, &mut res)?;
+        process_result(res, &self.state)
     }
 
-    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, String> {
-        let res = self.cmd(cmd)?;
+    pub fn cmd_into(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+        single_line(cmd)?;
+        self.write.write_all(cmd.as_bytes())?;
+        read_frame(&mut self.read, buf)
+    }
 
-        serde_json::from_str(&res).map_err(|e| e.to_string())
+    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        let mut res: Vec<u8> = Vec::new();
+        self.cmd_into(cmd, &mut res)?;
+        parse_json(cmd, res)
     }
 
     pub fn close(&mut self) {
@@ -348,42 +2009,229 @@ impl R2PipeLang {
 }
 
 impl R2PipeHttp {
-    pub fn cmd(&mut self, cmd: &str) -> Result<String, String> {
+    pub fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let mut res: Vec<u8> = Vec::new();
+        self.cmd_into(cmd, &mut res)?;
+        process_result(res, &self.state)
+    }
+
+    pub fn cmd_into(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+        single_line(cmd)?;
+        let policy = self.state.retry.clone();
+        let limiter = self.state.limiter.clone();
+        retry::run(policy.as_ref(), || {
+            let _permit = limiter.as_ref().map(|l| l.acquire());
+            self.request(cmd, buf)
+        })
+    }
+
+    fn request(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
         let url = format!("http://{}/cmd/{}", self.host, cmd);
-        let res = reqwest::get(&url).unwrap();
-        let bytes = res.bytes().filter_map(|e| e.ok()).collect::<Vec<_>>();
-        str::from_utf8(bytes.as_slice())
-            .map(|s| s.to_string())
-            .map_err(|err| err.to_string())
+        let encodings = if self.compression {
+            "gzip, deflate"
+        } else {
+            "identity"
+        };
+        let mut res = self
+            .client
+            .get(&url)
+            .header(ACCEPT_ENCODING, encodings)
+            .send()
+            .map_err(|e| Error::Http(e.to_string()))?;
+        // gzip is decoded by reqwest, deflate is left to us.
+        let deflate = res
+            .headers()
+            .get(CONTENT_ENCODING)
+            .is_some_and(|e| e == "deflate");
+        buf.clear();
+        if deflate {
+            let mut raw = Vec::new();
+            res.read_to_end(&mut raw)?;
+            // "deflate" should be zlib wrapped, but some servers send it raw.
+            if ZlibDecoder::new(&raw[..]).read_to_end(buf).is_err() {
+                buf.clear();
+                DeflateDecoder::new(&raw[..]).read_to_end(buf)?;
+            }
+        } else {
+            res.read_to_end(buf)?;
+        }
+        Ok(())
     }
 
-    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, String> {
-        let res = self.cmd(cmd)?;
-        serde_json::from_str(&res).map_err(|e| format!("Unable to parse json: {}", e))
+    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        let mut res: Vec<u8> = Vec::new();
+        self.cmd_into(cmd, &mut res)?;
+        parse_json(cmd, res)
     }
 
     pub fn close(&mut self) {}
 }
 
 impl R2PipeTcp {
-    pub fn cmd(&mut self, cmd: &str) -> Result<String, String> {
-        let mut stream = TcpStream::connect(self.socket_addr)
-            .map_err(|e| format!("Unable to connect TCP stream: {}", e))?;
-        stream
-            .write_all(cmd.as_bytes())
-            .map_err(|e| format!("Unable to write to TCP stream: {}", e))?;
+    pub fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
         let mut res: Vec<u8> = Vec::new();
-        stream
-            .read_to_end(&mut res)
-            .map_err(|e| format!("Unable to read from TCP stream: {}", e))?;
-        res.push(0);
-        process_result(res)
+        self.cmd_into(cmd, &mut res)?;
+        process_result(res, &self.state)
     }
 
-    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, String> {
-        let res = self.cmd(cmd)?;
-        serde_json::from_str(&res).map_err(|e| format!("Unable to parse json: {}", e))
+    pub fn cmd_into(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+        single_line(cmd)?;
+        let policy = self.state.retry.clone();
+        let limiter = self.state.limiter.clone();
+        retry::run(policy.as_ref(), || {
+            let _permit = limiter.as_ref().map(|l| l.acquire());
+            self.request(cmd, buf)
+        })
+    }
+
+    fn request(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+        let (timeout, by_deadline) = self.state.time_left(cmd)?;
+        let timed_out = |e: io::Error| match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut if by_deadline => {
+                Error::DeadlineExceeded {
+                    cmd: cmd.to_string(),
+                }
+            }
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::Timeout {
+                cmd: cmd.to_string(),
+                after: timeout.unwrap_or_default(),
+            },
+            _ => Error::Io(e),
+        };
+        let mut stream = match timeout {
+            Some(t) => TcpStream::connect_timeout(&self.socket_addr, t),
+            None => TcpStream::connect(self.socket_addr),
+        }
+        .map_err(timed_out)?;
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        stream.write_all(cmd.as_bytes()).map_err(timed_out)?;
+        buf.clear();
+        stream.read_to_end(buf).map_err(timed_out)?;
+        Ok(())
+    }
+
+    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        let mut res: Vec<u8> = Vec::new();
+        self.cmd_into(cmd, &mut res)?;
+        parse_json(cmd, res)
     }
 
     pub fn close(&mut self) {}
 }
+
+impl R2PipeCustom {
+    pub fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let mut res: Vec<u8> = Vec::new();
+        self.cmd_into(cmd, &mut res)?;
+        process_result(res, &self.state)
+    }
+
+    pub fn cmd_into(&mut self, cmd: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+        single_line(cmd)?;
+        *buf = self.inner.cmd(cmd)?.into_bytes();
+        Ok(())
+    }
+
+    pub fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        let mut res: Vec<u8> = Vec::new();
+        self.cmd_into(cmd, &mut res)?;
+        parse_json(cmd, res)
+    }
+
+    pub fn close(&mut self) {
+        self.inner.close();
+    }
+}
+
+macro_rules! impl_r2pipe_commands {
+    ($($t:ty),*) => {
+        $(
+            impl R2PipeCommands for $t {
+                fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+                    <$t>::cmd(self, cmd)
+                }
+
+                fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+                    <$t>::cmdj(self, cmd)
+                }
+
+                fn close(&mut self) {
+                    <$t>::close(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_r2pipe_commands!(
+    R2Pipe,
+    R2PipeSpawn,
+    R2PipeLang,
+    R2PipeHttp,
+    R2PipeTcp,
+    R2PipeCustom
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_end_at_nul() {
+        let mut read = io::Cursor::new(b"first\0second\0".to_vec());
+        let mut buf = Vec::new();
+        read_frame(&mut read, &mut buf).unwrap();
+        assert_eq!(buf, b"first");
+        read_frame(&mut read, &mut buf).unwrap();
+        assert_eq!(buf, b"second");
+    }
+
+    #[test]
+    fn cut_frame_is_an_exit() {
+        for output in [&b""[..], b"{\"half\":"] {
+            let mut read = io::Cursor::new(output.to_vec());
+            let mut buf = Vec::new();
+            match read_frame(&mut read, &mut buf) {
+                Err(Error::ProcessExited { status: None, .. }) => {}
+                res => panic!("{:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn frame_chunks_stop_at_nul() {
+        let mut read = io::BufReader::with_capacity(2, io::Cursor::new(b"one\0two".to_vec()));
+        let mut out = [0u8; 8];
+        assert_eq!(read_frame_chunk(&mut read, &mut out).unwrap(), (2, false));
+        assert_eq!(read_frame_chunk(&mut read, &mut out).unwrap(), (1, true));
+        assert_eq!(
+            read_frame_chunk(&mut read, &mut out[..1]).unwrap(),
+            (1, false)
+        );
+        assert_eq!(read_frame_chunk(&mut read, &mut out).unwrap(), (1, false));
+        assert_eq!(read_frame_chunk(&mut read, &mut out).unwrap(), (1, false));
+        match read_frame_chunk(&mut read, &mut out) {
+            Err(Error::ProcessExited { status: None, .. }) => {}
+            res => panic!("{:?}", res),
+        }
+    }
+
+    #[test]
+    fn strips_ansi_codes() {
+        let mut res = b"\x1b[31mred\x1b[0m \x1b(Bplain".to_vec();
+        strip_ansi(&mut res);
+        assert_eq!(res, b"red plain");
+    }
+
+    #[test]
+    fn batch_reports_the_failed_command() {
+        let mock = crate::mock::MockR2Pipe::new()
+            .expect("?e a", "a\n")
+            .expect_err("pdf", "no function");
+        let mut r2 = R2Pipe::from_commands(mock);
+        let e = r2.cmd_batch(&["?e a", "pdf", "?e b"]).unwrap_err();
+        assert_eq!(e.batch_index(), Some(1));
+        assert!(matches!(e.root(), Error::Other(_)));
+    }
+}
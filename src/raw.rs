@@ -0,0 +1,114 @@
+//! Opening flat binaries, such as firmware or memory dumps, that r2 has no
+//! format to load them with.
+
+use crate::error::Error;
+use crate::files::check_map;
+use crate::r2pipe::{R2Pipe, R2PipeSpawnOptions};
+use crate::structs::Addr;
+
+/// A range of the file mapped in the address space (`om`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapSpec {
+    pub addr: Addr,
+    pub size: u64,
+    /// Offset in the file of the first mapped byte.
+    pub offset: u64,
+    /// e.g. `rw-`, empty for `r-x`.
+    pub perms: String,
+}
+
+/// How to open a raw file, see `R2Pipe::spawn_raw()`.
+#[derive(Default, Clone)]
+pub struct RawOptions {
+    /// `asm.arch`, empty to keep r2's default.
+    pub arch: String,
+    /// `asm.bits`, 0 to keep r2's default.
+    pub bits: u32,
+    /// Where the whole file is mapped when `maps` is empty.
+    pub base: Addr,
+    pub maps: Vec<MapSpec>,
+    pub spawn: R2PipeSpawnOptions,
+}
+
+impl R2Pipe {
+    /// Opens `path` as raw bytes (`-n`), with the architecture and the
+    /// mappings of `raw`, and seeks to the first mapping.
+    ///
+    /// The mappings are checked once applied (`omj`), as is the
+    /// architecture, which fails with `Error::Unsupported` when this r2
+    /// doesn't have it.
+    ///
+    /// ```no_run
+    /// use r2pipe::{Addr, MapSpec, R2Pipe, RawOptions};
+    ///
+    /// let r2 = R2Pipe::spawn_raw(
+    ///     "flash.bin",
+    ///     RawOptions {
+    ///         arch: "arm".to_string(),
+    ///         bits: 16,
+    ///         maps: vec![
+    ///             MapSpec { addr: Addr(0x0800_0000), size: 0x10000, offset: 0, ..Default::default() },
+    ///             MapSpec { addr: Addr(0x2000_0000), size: 0x5000, offset: 0x10000, perms: "rw-".to_string() },
+    ///         ],
+    ///         ..Default::default()
+    ///     },
+    /// )?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn spawn_raw(path: &str, raw: RawOptions) -> Result<R2Pipe, Error> {
+        for map in &raw.maps {
+            check_map(map)?;
+        }
+        let mut opts = raw.spawn.clone();
+        opts.args.push("-n");
+        let mut r2 = R2Pipe::spawn(path, Some(opts))?;
+        match r2.apply_raw(&raw) {
+            Ok(()) => Ok(r2),
+            Err(e) => {
+                r2.close();
+                Err(e)
+            }
+        }
+    }
+
+    fn apply_raw(&mut self, raw: &RawOptions) -> Result<(), Error> {
+        if !raw.arch.is_empty() {
+            self.set_config("asm.arch", &raw.arch)?;
+            if self.get_config("asm.arch")? != raw.arch {
+                return Err(Error::Unsupported(format!(
+                    "No {} arch plugin in this r2",
+                    raw.arch
+                )));
+            }
+        }
+        if raw.bits != 0 {
+            self.set_config("asm.bits", &raw.bits.to_string())?;
+        }
+        let files = self.open_files()?;
+        let file = files
+            .iter()
+            .find(|f| f.raised)
+            .or_else(|| files.first())
+            .ok_or_else(|| Error::Other("r2 opened no file".to_string()))?;
+        let maps = if raw.maps.is_empty() {
+            vec![MapSpec {
+                addr: raw.base,
+                size: file.size,
+                ..Default::default()
+            }]
+        } else {
+            raw.maps.clone()
+        };
+        self.remove_all_maps()?;
+        for map in &maps {
+            let applied = self.add_map(file.fd, map)?;
+            if applied.from != map.addr || applied.to.0 + 1 < map.addr.0 + map.size {
+                return Err(Error::Other(format!(
+                    "r2 didn't map {:#x} bytes at {}",
+                    map.size, map.addr
+                )));
+            }
+        }
+        self.cmd_ok(&format!("s {}", maps[0].addr))
+    }
+}
@@ -0,0 +1,124 @@
+//! What functions reference: the strings and data they use, with the
+//! values found there.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::Addr;
+
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+
+/// How an instruction refers to its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataRefKind {
+    /// A string r2 knows of.
+    String,
+    /// Any other data.
+    Data,
+}
+
+/// What is at the target of a reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolved {
+    String(String),
+    /// The pointer-sized value at the target.
+    Value(u64),
+    /// The target couldn't be read.
+    Unknown,
+}
+
+/// A reference from an instruction of a function to data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataRef {
+    /// The referencing instruction.
+    pub at: Addr,
+    pub target: Addr,
+    pub kind: DataRefKind,
+    /// Flag at the target, e.g. `str.Hello` or `obj.table`, may be empty.
+    pub name: String,
+    pub resolved: Resolved,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct XrefEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    at: Addr,
+    #[serde(rename = "ref")]
+    target: Addr,
+    name: String,
+}
+
+impl R2Pipe {
+    /// The data referenced by the function at `addr` (`axffj`), in
+    /// instruction order, with the strings (`izj`) and values they point
+    /// to. Calls and jumps are left out.
+    ///
+    /// ```no_run
+    /// use r2pipe::refs::Resolved;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.cmd("aa")?;
+    /// for r in r2.function_data_refs(0x1000u64)? {
+    ///     if let Resolved::String(s) = r.resolved {
+    ///         println!("{} uses {:?}", r.at, s);
+    ///     }
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn function_data_refs<A: Into<Addr>>(&mut self, addr: A) -> Result<Vec<DataRef>, Error> {
+        let xrefs: Vec<XrefEntry> = match self.cmd_deserialize(&format!("axffj @ {}", addr.into()))
+        {
+            Err(ref e) if e.is_empty_response() => return Ok(Vec::new()),
+            res => res?,
+        };
+        let xrefs: Vec<XrefEntry> = xrefs
+            .into_iter()
+            .filter(|x| x.kind != "CALL" && x.kind != "CODE")
+            .collect();
+        if xrefs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let strings: HashMap<Addr, String> = self
+            .strings()?
+            .into_iter()
+            .map(|s| (s.vaddr, s.string))
+            .collect();
+        let bin = self.bin_info()?.bin;
+        let width = (bin.bits / 8).clamp(1, 8) as usize;
+        let mut refs = Vec::new();
+        for x in xrefs {
+            let (kind, resolved) = match strings.get(&x.target) {
+                Some(s) => (DataRefKind::String, Resolved::String(s.clone())),
+                None => {
+                    let value = match self.read_bytes(x.target, width) {
+                        Ok(bytes) if bytes.len() == width => {
+                            Resolved::Value(read_uint(&bytes, bin.endian == "big"))
+                        }
+                        _ => Resolved::Unknown,
+                    };
+                    (DataRefKind::Data, value)
+                }
+            };
+            refs.push(DataRef {
+                at: x.at,
+                target: x.target,
+                kind,
+                name: x.name,
+                resolved,
+            });
+        }
+        refs.sort_by_key(|r| r.at);
+        Ok(refs)
+    }
+}
+
+fn read_uint(bytes: &[u8], big_endian: bool) -> u64 {
+    let fold = |acc: u64, b: &u8| acc << 8 | u64::from(*b);
+    if big_endian {
+        bytes.iter().fold(0, fold)
+    } else {
+        bytes.iter().rev().fold(0, fold)
+    }
+}
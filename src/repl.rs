@@ -0,0 +1,54 @@
+//! Handing the session over to the user for a while.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+
+use std::io::{BufRead, Write};
+
+impl R2Pipe {
+    /// Runs the lines read from `input` as commands and prints their
+    /// output to `output`, like r2's own prompt.
+    ///
+    /// Returns on end of input or on `q`, which gives control back to the
+    /// program instead of quitting r2. The session keeps whatever the user
+    /// did to it (seek, flags, analysis...). Failed commands are printed,
+    /// only I/O errors on `input` or `output` end the loop early.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.cmd("aa")?;
+    /// let stdin = std::io::stdin();
+    /// r2.repl(stdin.lock(), std::io::stdout())?;
+    /// let fns = r2.cmdj("aflj")?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> Result<(), Error> {
+        let mut lines = input.lines();
+        loop {
+            match self.current_offset() {
+                Ok(addr) => write!(output, "[{}]> ", addr)?,
+                Err(_) => output.write_all(b"> ")?,
+            }
+            output.flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => {
+                    // Leave the terminal on a new line after ^D.
+                    writeln!(output)?;
+                    break;
+                }
+            };
+            let cmd = line.trim();
+            match cmd {
+                "" => continue,
+                "q" | "q!" | "exit" => break,
+                _ => {}
+            }
+            match self.cmd(cmd) {
+                Ok(res) => output.write_all(res.as_bytes())?,
+                Err(e) => writeln!(output, "{}", e)?,
+            }
+        }
+        Ok(())
+    }
+}
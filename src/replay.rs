@@ -0,0 +1,301 @@
+//! Recording sessions and replaying them without r2.
+//!
+//! `Recorder` wraps any pipe and writes every command with its reply to a
+//! file, one JSON object per line. `ReplayPipe` reads such a file back and
+//! answers the same commands in the same order, so tests and bug reports
+//! don't need radare2 installed. `FixturePipe` serves a whole directory of
+//! recordings, in any order. Wrapped with `R2Pipe::from_commands()`, they
+//! also answer the typed API.
+//!
+//! ```no_run
+//! use r2pipe::{R2Pipe, R2PipeCommands, Recorder, ReplayPipe};
+//!
+//! let r2 = R2Pipe::spawn("/bin/ls", None)?;
+//! let mut rec = Recorder::create(r2, "ls.r2rec")?;
+//! rec.cmd("aa")?;
+//! let fns = rec.cmdj("aflj")?;
+//! rec.close();
+//!
+//! let mut replay = ReplayPipe::open("ls.r2rec")?;
+//! replay.cmd("aa")?;
+//! assert_eq!(replay.cmdj("aflj")?, fns);
+//! # Ok::<(), r2pipe::Error>(())
+//! ```
+
+use crate::error::Error;
+use crate::r2pipe::R2PipeCommands;
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// A line of a recording.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    cmd: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    res: Option<String>,
+    /// Set instead of `res` when the command failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    err: Option<String>,
+}
+
+impl Entry {
+    fn reply(&self) -> Result<String, Error> {
+        match self.err {
+            Some(ref e) => Err(Error::Other(e.clone())),
+            None => Ok(self.res.clone().unwrap_or_default()),
+        }
+    }
+}
+
+fn read_entries<R: BufRead>(reader: R) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes the commands run on `P` and their replies to `W`.
+///
+/// `cmdj()` records the raw reply, so replays go through the same parsing.
+pub struct Recorder<P, W: Write = BufWriter<File>> {
+    pipe: P,
+    out: W,
+}
+
+impl<P: R2PipeCommands> Recorder<P> {
+    /// Records to the file at `path`, truncating it.
+    pub fn create<T: AsRef<Path>>(pipe: P, path: T) -> Result<Recorder<P>, Error> {
+        Ok(Recorder::new(pipe, BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<P: R2PipeCommands, W: Write> Recorder<P, W> {
+    pub fn new(pipe: P, out: W) -> Recorder<P, W> {
+        Recorder { pipe, out }
+    }
+
+    /// Stops recording, returning the pipe.
+    pub fn into_inner(mut self) -> P {
+        let _ = self.out.flush();
+        self.pipe
+    }
+
+    fn record(&mut self, cmd: &str, res: &Result<String, Error>) -> Result<(), Error> {
+        let entry = Entry {
+            cmd: cmd.to_string(),
+            res: res.as_ref().ok().cloned(),
+            err: res.as_ref().err().map(|e| e.to_string()),
+        };
+        serde_json::to_writer(&mut self.out, &entry)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+impl<P: R2PipeCommands, W: Write> R2PipeCommands for Recorder<P, W> {
+    fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let res = self.pipe.cmd(cmd);
+        self.record(cmd, &res)?;
+        res
+    }
+
+    fn close(&mut self) {
+        self.pipe.close();
+        let _ = self.out.flush();
+    }
+}
+
+/// Answers commands from a recording made by `Recorder`.
+///
+/// Commands must come in the order they were recorded: any other command
+/// fails with `Error::Desync`, since its reply can't be known.
+pub struct ReplayPipe {
+    entries: VecDeque<Entry>,
+}
+
+impl ReplayPipe {
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<ReplayPipe, Error> {
+        ReplayPipe::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<ReplayPipe, Error> {
+        Ok(ReplayPipe {
+            entries: read_entries(reader)?.into(),
+        })
+    }
+
+    /// Number of recorded commands not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+impl R2PipeCommands for ReplayPipe {
+    fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let cmd = cmd.trim();
+        match self.entries.pop_front() {
+            Some(entry) if entry.cmd.trim() == cmd => entry.reply(),
+            Some(entry) => {
+                let e = format!("Recording expects `{}`, got `{}`", entry.cmd, cmd);
+                self.entries.push_front(entry);
+                Err(Error::Desync(e))
+            }
+            None => Err(Error::Desync(format!("Recording is over, got `{}`", cmd))),
+        }
+    }
+
+    fn close(&mut self) {}
+}
+
+/// Answers commands from recordings made by `Recorder`, in any order.
+///
+/// The n-th time a command is run it gets the n-th reply recorded for it,
+/// then the last one again, so the same commands always get the same
+/// replies. Commands never recorded fail with `Error::Other`.
+///
+/// ```no_run
+/// use r2pipe::{FixturePipe, R2PipeCommands};
+///
+/// // Recordings of real runs, checked in with the tests.
+/// let mut r2 = FixturePipe::load_dir("tests/fixtures")?;
+/// let fns = r2.cmdj("aflj")?;
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+#[derive(Default)]
+pub struct FixturePipe {
+    // Replies of each command, and how many were served.
+    replies: HashMap<String, (Vec<Entry>, usize)>,
+}
+
+impl FixturePipe {
+    pub fn new() -> FixturePipe {
+        FixturePipe::default()
+    }
+
+    /// Loads every `.r2rec` file of `dir`, in file name order.
+    pub fn load_dir<T: AsRef<Path>>(dir: T) -> Result<FixturePipe, Error> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "r2rec") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        let mut fixtures = FixturePipe::new();
+        for path in paths {
+            fixtures.add_reader(BufReader::new(File::open(path)?))?;
+        }
+        Ok(fixtures)
+    }
+
+    /// Adds the replies of a recording, after those already loaded.
+    pub fn add_reader<R: BufRead>(&mut self, reader: R) -> Result<(), Error> {
+        for entry in read_entries(reader)? {
+            let cmd = entry.cmd.trim().to_string();
+            self.replies.entry(cmd).or_default().0.push(entry);
+        }
+        Ok(())
+    }
+
+    /// The recorded commands, sorted, e.g. to generate test inputs from.
+    pub fn commands(&self) -> Vec<&str> {
+        let mut cmds: Vec<&str> = self.replies.keys().map(|c| c.as_str()).collect();
+        cmds.sort_unstable();
+        cmds
+    }
+
+    /// Starts serving every command from its first reply again.
+    pub fn reset(&mut self) {
+        for (_, served) in self.replies.values_mut() {
+            *served = 0;
+        }
+    }
+}
+
+impl R2PipeCommands for FixturePipe {
+    fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        let cmd = cmd.trim();
+        let (entries, served) = self
+            .replies
+            .get_mut(cmd)
+            .ok_or_else(|| Error::Other(format!("No fixture for `{}`", cmd)))?;
+        let entry = &entries[(*served).min(entries.len() - 1)];
+        *served += 1;
+        entry.reply()
+    }
+
+    fn close(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+    use crate::r2pipe::R2Pipe;
+
+    fn recording() -> Vec<u8> {
+        let mock = MockR2Pipe::new()
+            .expect("aa", "")
+            .expect("?v 1+1", "0x2\n")
+            .expect_err("pdf", "no function")
+            .expect("pdj 1", r#"[{"offset":4096}]"#);
+        let mut rec = Recorder::new(mock, Vec::new());
+        rec.cmd("aa").unwrap();
+        rec.cmd("?v 1+1").unwrap();
+        assert!(rec.cmd("pdf").is_err());
+        assert_eq!(rec.cmdj("pdj 1").unwrap()[0]["offset"], 4096);
+        rec.out
+    }
+
+    #[test]
+    fn replays_in_order() {
+        let rec = recording();
+        assert_eq!(rec.iter().filter(|&&b| b == b'\n').count(), 4);
+        let mut replay = ReplayPipe::from_reader(&rec[..]).unwrap();
+        assert_eq!(replay.remaining(), 4);
+        assert!(matches!(replay.cmd("?v 1+1"), Err(Error::Desync(_))));
+        assert_eq!(replay.cmd("aa").unwrap(), "");
+        assert_eq!(replay.cmd("?v 1+1").unwrap(), "0x2\n");
+        assert!(matches!(replay.cmd("pdf"), Err(Error::Other(ref e)) if e == "no function"));
+        assert_eq!(replay.cmdj("pdj 1").unwrap()[0]["offset"], 4096);
+        assert_eq!(replay.remaining(), 0);
+        assert!(matches!(replay.cmd("aa"), Err(Error::Desync(_))));
+    }
+
+    #[test]
+    fn fixtures_in_any_order() {
+        let mut fixtures = FixturePipe::new();
+        fixtures
+            .add_reader(
+                &b"{\"cmd\":\"?V\",\"res\":\"5.8.8\"}\n\n{\"cmd\":\"?V\",\"res\":\"5.9.0\"}\n"[..],
+            )
+            .unwrap();
+        fixtures.add_reader(&recording()[..]).unwrap();
+        assert_eq!(fixtures.commands(), ["?V", "?v 1+1", "aa", "pdf", "pdj 1"]);
+        assert_eq!(fixtures.cmd("?v 1+1").unwrap(), "0x2\n");
+        assert_eq!(fixtures.cmd("?V").unwrap(), "5.8.8");
+        assert_eq!(fixtures.cmd("?V").unwrap(), "5.9.0");
+        assert_eq!(fixtures.cmd("?V").unwrap(), "5.9.0");
+        fixtures.reset();
+        assert_eq!(fixtures.cmd("?V").unwrap(), "5.8.8");
+        assert!(fixtures.cmd("aaa").is_err());
+
+        let mut r2 = R2Pipe::from_commands(fixtures);
+        let version = r2.cmd("?V").unwrap();
+        assert_eq!(version, "5.9.0");
+    }
+
+    #[test]
+    fn bad_recordings() {
+        assert!(ReplayPipe::from_reader(&b"{\"cmd\": \"aa\"\n"[..]).is_err());
+    }
+}
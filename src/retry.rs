@@ -0,0 +1,62 @@
+//! Retrying commands that failed for transient reasons, see
+//! `R2Pipe::set_retry_policy()`.
+
+use crate::error::Error;
+
+use std::thread;
+use std::time::Duration;
+
+/// When and how often to retry a failed command.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included.
+    pub attempts: u32,
+    /// Wait before the first retry, doubled after each failed attempt.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Which errors are worth retrying, `is_transient()` by default.
+    pub retryable: fn(&Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+            retryable: is_transient,
+        }
+    }
+}
+
+/// Connection problems and timeouts, which may not happen again.
+pub fn is_transient(e: &Error) -> bool {
+    matches!(
+        *e.root(),
+        Error::Io(_) | Error::Http(_) | Error::Timeout { .. }
+    )
+}
+
+/// Runs `f` until it succeeds, fails with an error `policy` doesn't
+/// retry, or runs out of attempts.
+pub(crate) fn run<T, F>(policy: Option<&RetryPolicy>, mut f: F) -> Result<T, Error>
+where
+    F: FnMut() -> Result<T, Error>,
+{
+    let policy = match policy {
+        Some(policy) => policy,
+        None => return f(),
+    };
+    let mut backoff = policy.backoff;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(ref e) if attempt < policy.attempts && (policy.retryable)(e) => {
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(policy.max_backoff);
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
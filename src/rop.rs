@@ -0,0 +1,202 @@
+//! Checking ROP chains by emulating them with ESIL (`ae*`).
+//!
+//! The chain is written on the stack of the ESIL VM (`aeim`) and its
+//! first gadget jumped to, as if a `ret` had just popped it. Emulation
+//! then steps (`aes`) until the last gadget returns past the end of the
+//! chain, or a gadget lands somewhere that isn't code.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{parse_r2_number, Addr, IoMap};
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Instructions emulated before giving up on a chain.
+const MAX_STEPS: u64 = 4096;
+
+/// Registers and memory to start emulating from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmulationState {
+    /// Values of the ESIL registers, by name (`rdi`, `r0`...).
+    pub regs: BTreeMap<String, u64>,
+    /// Bytes to write before running, e.g. strings the chain points to.
+    pub memory: Vec<(Addr, Vec<u8>)>,
+}
+
+/// How the emulation of a chain ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainOutcome {
+    /// The last gadget reached a `ret` with nothing left of the chain.
+    Completed,
+    /// The gadget at `index` of the chain went to `pc`, which isn't
+    /// executable or doesn't decode.
+    Fault { index: usize, pc: Addr },
+    /// The gadget at `index` was still running at `pc` after too many
+    /// instructions, e.g. in a loop.
+    StepLimit { index: usize, pc: Addr },
+}
+
+/// The state the chain left the VM in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainResult {
+    pub outcome: ChainOutcome,
+    /// Instructions emulated.
+    pub steps: u64,
+    pub regs: BTreeMap<String, u64>,
+    /// The ranges of `EmulationState::memory`, read back.
+    pub memory: Vec<(Addr, Vec<u8>)>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Op {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl R2Pipe {
+    /// Emulates `chain`, gadget addresses and the values they pop, from
+    /// `initial`.
+    ///
+    /// The ESIL VM is initialized again (`aei`, `aeim`), and writes go to
+    /// `io.cache`, so the file isn't modified.
+    ///
+    /// ```no_run
+    /// use r2pipe::rop::{ChainOutcome, EmulationState};
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// // pop rdi; ret, then the value for rdi, then a ret.
+    /// let res = r2.verify_chain(&[0x401236, 0x1337, 0x40101a], &EmulationState::default())?;
+    /// assert_eq!(res.outcome, ChainOutcome::Completed);
+    /// assert_eq!(res.regs["rdi"], 0x1337);
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn verify_chain(
+        &mut self,
+        chain: &[u64],
+        initial: &EmulationState,
+    ) -> Result<ChainResult, Error> {
+        if chain.is_empty() {
+            return Err(Error::InvalidCommand("Empty ROP chain".to_string()));
+        }
+        for name in initial.regs.keys() {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(Error::InvalidCommand(format!("Not a register: {:?}", name)));
+            }
+        }
+        self.with_config("io.cache", "true", |r2| r2.run_chain(chain, initial))
+    }
+
+    fn run_chain(&mut self, chain: &[u64], initial: &EmulationState) -> Result<ChainResult, Error> {
+        self.cmd_ok("aei")?;
+        self.cmd_ok("aeim")?;
+        for (name, value) in &initial.regs {
+            self.cmd_ok(&format!("aer {}={}", name, value))?;
+        }
+        for (addr, data) in &initial.memory {
+            self.write_bytes(*addr, data)?;
+        }
+        let pc_reg = self.reg_role("PC")?;
+        let sp_reg = self.reg_role("SP")?;
+        let width = (parse_r2_number(&self.get_config("asm.bits")?).unwrap_or(64) / 8).clamp(2, 8);
+        let big = self.get_config("cfg.bigendian")? == "true";
+
+        // chain[0] is the first gadget, the rest is on the stack.
+        let base = self.esil_regs()?.get(&sp_reg).copied().unwrap_or(0);
+        let mut stack = Vec::new();
+        for &word in &chain[1..] {
+            let bytes = if big {
+                word.to_be_bytes()
+            } else {
+                word.to_le_bytes()
+            };
+            let bytes = if big {
+                &bytes[8 - width as usize..]
+            } else {
+                &bytes[..width as usize]
+            };
+            stack.extend_from_slice(bytes);
+        }
+        self.write_bytes(base, &stack)?;
+        let end = base + stack.len() as u64;
+        self.cmd_ok(&format!("aer {}={}", pc_reg, chain[0]))?;
+
+        let maps = self.io_maps()?;
+        let mut index = 0;
+        let mut steps = 0;
+        let mut returned = false;
+        let outcome = loop {
+            let regs = self.esil_regs()?;
+            let pc = regs.get(&pc_reg).copied().unwrap_or(0);
+            let sp = regs.get(&sp_reg).copied().unwrap_or(0);
+            if returned && sp > base && sp <= end {
+                // The word just popped.
+                index = ((sp - base) / width) as usize;
+            }
+            if !executable(&maps, pc) {
+                break ChainOutcome::Fault {
+                    index,
+                    pc: Addr(pc),
+                };
+            }
+            let ops: Vec<Op> = self.cmd_deserialize(&format!("aoj 1 @ {}", pc))?;
+            let kind = ops.into_iter().next().unwrap_or_default().kind;
+            if kind.is_empty() || kind == "invalid" || kind == "ill" {
+                break ChainOutcome::Fault {
+                    index,
+                    pc: Addr(pc),
+                };
+            }
+            returned = kind.ends_with("ret");
+            if returned && sp >= end {
+                break ChainOutcome::Completed;
+            }
+            if steps == MAX_STEPS {
+                break ChainOutcome::StepLimit {
+                    index,
+                    pc: Addr(pc),
+                };
+            }
+            self.cmd_ok("aes")?;
+            steps += 1;
+        };
+        let mut memory = Vec::new();
+        for (addr, data) in &initial.memory {
+            memory.push((*addr, self.read_bytes(*addr, data.len())?));
+        }
+        Ok(ChainResult {
+            outcome,
+            steps,
+            regs: self.esil_regs()?,
+            memory,
+        })
+    }
+
+    /// Name of the ESIL register with `role` (`arn`).
+    fn reg_role(&mut self, role: &str) -> Result<String, Error> {
+        let name = self.cmd(&format!("arn {}", role))?.trim().to_string();
+        if name.is_empty() {
+            return Err(Error::Unsupported(format!(
+                "No {} register for asm.arch",
+                role
+            )));
+        }
+        Ok(name)
+    }
+
+    /// The ESIL registers (`aerj`).
+    fn esil_regs(&mut self) -> Result<BTreeMap<String, u64>, Error> {
+        let regs: BTreeMap<String, Value> = self.cmd_deserialize("aerj")?;
+        Ok(regs
+            .into_iter()
+            .filter_map(|(name, v)| v.as_u64().map(|v| (name, v)))
+            .collect())
+    }
+}
+
+fn executable(maps: &[IoMap], addr: u64) -> bool {
+    maps.iter()
+        .any(|m| m.from.0 <= addr && addr <= m.to.0 && m.perm.contains('x'))
+}
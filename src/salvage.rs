@@ -0,0 +1,168 @@
+//! Keeping what can be read of JSON listings cut short.
+//!
+//! r2 sometimes stops printing a large listing (`aflj`, `pdj`...) when it
+//! runs low on memory, and the whole reply fails to parse. The elements
+//! printed entirely before the cut are still good.
+
+use crate::error::Error;
+use crate::r2pipe::{json_error, R2Pipe};
+
+use serde::de::{DeserializeOwned, SeqAccess, Visitor};
+use serde::Deserializer as _;
+use std::fmt;
+
+/// The elements of a JSON array read by
+/// `R2Pipe::cmd_deserialize_salvaged()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Salvaged<T> {
+    pub items: Vec<T>,
+    /// The output was cut, `items` are the elements before the cut.
+    pub truncated: bool,
+    /// The command `items` come from, the one given or a narrower one.
+    pub cmd: String,
+}
+
+/// Pushes the elements of an array until its end or the first error.
+struct Prefix<'a, T>(&'a mut Vec<T>);
+
+impl<'de, 'a, T: DeserializeOwned> Visitor<'de> for Prefix<'a, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(item) = seq.next_element()? {
+            self.0.push(item);
+        }
+        Ok(())
+    }
+}
+
+impl R2Pipe {
+    /// Runs `cmd`, whose output is a JSON array, and keeps the elements
+    /// before the cut when the output is truncated instead of failing.
+    ///
+    /// A truncated command ending with a count, e.g. `pdj 4000`, is run
+    /// once more with half the count. What it prints is used when
+    /// complete, else the elements of the first try.
+    ///
+    /// ```no_run
+    /// use r2pipe::structs::Instruction;
+    ///
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let res = r2.cmd_deserialize_salvaged::<Instruction>("pdj 4000")?;
+    /// if res.truncated {
+    ///     eprintln!("`{}` was cut after {} items", res.cmd, res.items.len());
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn cmd_deserialize_salvaged<T: DeserializeOwned>(
+        &mut self,
+        cmd: &str,
+    ) -> Result<Salvaged<T>, Error> {
+        let first = self.salvage(cmd)?;
+        if !first.truncated {
+            return Ok(first);
+        }
+        let narrower = match cmd.split_whitespace().collect::<Vec<_>>()[..] {
+            [name, count] => match count.parse::<u64>() {
+                Ok(n) if n > 1 => format!("{} {}", name, n / 2),
+                _ => return Ok(first),
+            },
+            _ => return Ok(first),
+        };
+        match self.salvage(&narrower) {
+            Ok(retry) if !retry.truncated => Ok(retry),
+            _ => Ok(first),
+        }
+    }
+
+    fn salvage<T: DeserializeOwned>(&mut self, cmd: &str) -> Result<Salvaged<T>, Error> {
+        let res = self.cmd_json_bytes(cmd)?;
+        if res.iter().all(|b| b.is_ascii_whitespace()) {
+            return Err(self.with_session(Error::EmptyResponse(cmd.to_string())));
+        }
+        let mut items = Vec::new();
+        let mut de = serde_json::Deserializer::from_slice(&res);
+        let truncated = match de.deserialize_seq(Prefix(&mut items)) {
+            Ok(()) => {
+                de.end()
+                    .map_err(|e| self.with_session(json_error(cmd, &res, e)))?;
+                false
+            }
+            Err(ref e) if e.is_eof() => {
+                // A number at the cut may be missing digits.
+                if res.trim_ascii_end().last().is_some_and(u8::is_ascii_digit) {
+                    items.pop();
+                }
+                true
+            }
+            Err(e) => return Err(self.with_session(json_error(cmd, &res, e))),
+        };
+        Ok(Salvaged {
+            items,
+            truncated,
+            cmd: cmd.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+    use serde_json::{json, Value};
+
+    fn salvaged(mock: MockR2Pipe, cmd: &str) -> Result<Salvaged<Value>, Error> {
+        R2Pipe::from_commands(mock).cmd_deserialize_salvaged(cmd)
+    }
+
+    #[test]
+    fn complete_output() {
+        let res = salvaged(
+            MockR2Pipe::new().expect("aflj", r#"[{"a": 1}, {"a": 2}]"#),
+            "aflj",
+        )
+        .unwrap();
+        assert_eq!(res.items, vec![json!({"a": 1}), json!({"a": 2})]);
+        assert!(!res.truncated);
+        assert_eq!(res.cmd, "aflj");
+    }
+
+    #[test]
+    fn keeps_the_prefix() {
+        let mock = MockR2Pipe::new()
+            .expect("pdj 4", r#"[{"a": 1}, {"a": 2}, {"a""#)
+            .expect("pdj 2", r#"[{"a": 1}, {"#);
+        let res = salvaged(mock, "pdj 4").unwrap();
+        assert_eq!(res.items, vec![json!({"a": 1}), json!({"a": 2})]);
+        assert!(res.truncated);
+        assert_eq!(res.cmd, "pdj 4");
+
+        let res = salvaged(MockR2Pipe::new().expect("aflj", "[1, 22, 33"), "aflj").unwrap();
+        assert_eq!(res.items, vec![json!(1), json!(22)]);
+    }
+
+    #[test]
+    fn retries_narrower() {
+        let mock = MockR2Pipe::new()
+            .expect("pdj 4", r#"[{"a": 1}, {"a""#)
+            .expect("pdj 2", r#"[{"a": 1}, {"a": 2}]"#);
+        let res = salvaged(mock, "pdj 4").unwrap();
+        assert_eq!(res.items.len(), 2);
+        assert!(!res.truncated);
+        assert_eq!(res.cmd, "pdj 2");
+    }
+
+    #[test]
+    fn other_errors() {
+        let res = salvaged(MockR2Pipe::new().expect("aflj", "[1, x]"), "aflj");
+        assert!(matches!(res.unwrap_err().root(), Error::Json { .. }));
+        let res = salvaged(MockR2Pipe::new().expect("aflj", " \n"), "aflj");
+        assert!(res.unwrap_err().is_empty_response());
+        let res = salvaged(MockR2Pipe::new().expect("aflj", "[1] [2]"), "aflj");
+        assert!(matches!(res.unwrap_err().root(), Error::Json { .. }));
+    }
+}
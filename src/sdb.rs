@@ -0,0 +1,148 @@
+//! Reading r2's key-value databases (sdb, `k`).
+//!
+//! Much of what the analysis knows is only kept there: types, calling
+//! conventions, noreturn functions, syscalls... Namespaces are separated
+//! with `/`, e.g. `anal/types`.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Namespaces whose layout is the same across r2 versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdbNamespace {
+    /// Every namespace of the analysis.
+    Analysis,
+    /// C types, function prototypes and their noreturn attribute.
+    Types,
+    /// Calling conventions of the architecture.
+    CallingConventions,
+    /// Syscalls of the OS and architecture.
+    Syscalls,
+    /// Information of the binary plugin, per opened file.
+    Bin,
+}
+
+impl fmt::Display for SdbNamespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            SdbNamespace::Analysis => "anal",
+            SdbNamespace::Types => "anal/types",
+            SdbNamespace::CallingConventions => "anal/cc",
+            SdbNamespace::Syscalls => "syscall",
+            SdbNamespace::Bin => "bin",
+        })
+    }
+}
+
+/// What a name of the types database is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SdbTypeKind {
+    Struct,
+    Union,
+    Enum,
+    Typedef,
+    /// A builtin type, e.g. `int`.
+    Type,
+    /// A function prototype.
+    Func,
+    Other(String),
+}
+
+impl R2Pipe {
+    /// Runs a query on the database at `namespace` (`k namespace/query`):
+    /// `key` reads a key, `key=value` sets it, `*` lists them all and `**`
+    /// the namespaces below.
+    ///
+    /// The command is quoted like `eval()`'s, so values can hold `;` or
+    /// `@`.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let ret = r2.sdb_query("anal/types", "func.strlen.ret")?;
+    /// assert_eq!(ret.trim(), "size_t");
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn sdb_query(&mut self, namespace: &str, query: &str) -> Result<String, Error> {
+        let namespace = namespace.trim().trim_matches('/');
+        let query = query.trim();
+        if query.is_empty() || format!("{}{}", namespace, query).contains(['"', '\n', '\0']) {
+            return Err(Error::InvalidCommand(format!(
+                "Invalid sdb query {:?} in {:?}",
+                query, namespace
+            )));
+        }
+        if namespace.is_empty() {
+            self.cmd(&format!("\"k {}\"", query))
+        } else {
+            self.cmd(&format!("\"k {}/{}\"", namespace, query))
+        }
+    }
+
+    /// The value of `key` in `namespace`, `None` when unset.
+    pub fn sdb_get(&mut self, namespace: &str, key: &str) -> Result<Option<String>, Error> {
+        let res = self.sdb_query(namespace, key)?;
+        match res.trim_end_matches('\n') {
+            "" => Ok(None),
+            value => Ok(Some(value.to_string())),
+        }
+    }
+
+    /// The keys of `namespace` and their values (`k namespace/*`),
+    /// without those of the namespaces below.
+    pub fn sdb_dump(&mut self, namespace: &str) -> Result<BTreeMap<String, String>, Error> {
+        let res = self.sdb_query(namespace, "*")?;
+        Ok(res
+            .lines()
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect())
+    }
+
+    /// The namespaces right below `namespace` (`k namespace/**`).
+    pub fn sdb_namespaces(&mut self, namespace: &str) -> Result<Vec<String>, Error> {
+        let res = self.sdb_query(namespace, "**")?;
+        Ok(res
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// The keys of one of the well known namespaces, see `sdb_dump()`.
+    pub fn sdb_dump_namespace(
+        &mut self,
+        namespace: SdbNamespace,
+    ) -> Result<BTreeMap<String, String>, Error> {
+        self.sdb_dump(&namespace.to_string())
+    }
+
+    /// The names of the types database and what they are, structs,
+    /// typedefs, prototypes...
+    ///
+    /// Each name is a key of `anal/types` valued with its kind, its
+    /// details are in `kind.name.*` keys, e.g. `struct.stat.st_size`.
+    pub fn sdb_types(&mut self) -> Result<BTreeMap<String, SdbTypeKind>, Error> {
+        let types = self.sdb_dump_namespace(SdbNamespace::Types)?;
+        Ok(types
+            .into_iter()
+            // Details have the kind and name in their key.
+            .filter(|(name, _)| !name.contains('.'))
+            .map(|(name, kind)| {
+                let kind = match kind.as_str() {
+                    "struct" => SdbTypeKind::Struct,
+                    "union" => SdbTypeKind::Union,
+                    "enum" => SdbTypeKind::Enum,
+                    "typedef" => SdbTypeKind::Typedef,
+                    "type" => SdbTypeKind::Type,
+                    "func" => SdbTypeKind::Func,
+                    _ => SdbTypeKind::Other(kind),
+                };
+                (name, kind)
+            })
+            .collect())
+    }
+}
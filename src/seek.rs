@@ -0,0 +1,133 @@
+//! Typed access to r2's seek (current offset) and seek history.
+//!
+//! Every method returns the offset r2 reports after the operation, so callers
+//! always know where the cursor actually is.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::Addr;
+
+use std::ops::{Deref, DerefMut};
+
+/// Restores the previous offset when dropped.
+///
+/// Created by `R2Pipe::seek_guard()`, it derefs to the underlying pipe so
+/// commands can be issued while the temporary seek is active.
+pub struct SeekGuard<'a> {
+    pipe: &'a mut R2Pipe,
+    previous: Addr,
+}
+
+impl<'a> SeekGuard<'a> {
+    /// Offset that will be restored on drop.
+    pub fn previous(&self) -> Addr {
+        self.previous
+    }
+}
+
+impl<'a> Deref for SeekGuard<'a> {
+    type Target = R2Pipe;
+
+    fn deref(&self) -> &R2Pipe {
+        self.pipe
+    }
+}
+
+impl<'a> DerefMut for SeekGuard<'a> {
+    fn deref_mut(&mut self) -> &mut R2Pipe {
+        self.pipe
+    }
+}
+
+impl<'a> Drop for SeekGuard<'a> {
+    fn drop(&mut self) {
+        let _ = self.pipe.cmd(&format!("s {}", self.previous));
+    }
+}
+
+impl R2Pipe {
+    /// Seeks to `addr` (`s addr`).
+    pub fn seek<A: Into<Addr>>(&mut self, addr: A) -> Result<Addr, Error> {
+        self.seek_cmd(&format!("s {}", addr.into()))
+    }
+
+    /// Moves the cursor `delta` bytes forward (or backward if negative).
+    pub fn seek_relative(&mut self, delta: i64) -> Result<Addr, Error> {
+        if delta == 0 {
+            return self.current_offset();
+        }
+        let cmd = if delta > 0 {
+            format!("s+ {}", delta)
+        } else {
+            format!("s- {}", delta.unsigned_abs())
+        };
+        self.seek_cmd(&cmd)
+    }
+
+    /// Returns the current offset (`s`).
+    pub fn current_offset(&mut self) -> Result<Addr, Error> {
+        Ok(self.cmd("s")?.parse()?)
+    }
+
+    /// Goes back to the previous entry in the seek history (`s-`).
+    pub fn seek_undo(&mut self) -> Result<Addr, Error> {
+        self.seek_cmd("s-")
+    }
+
+    /// Goes forward in the seek history after an undo (`s+`).
+    pub fn seek_redo(&mut self) -> Result<Addr, Error> {
+        self.seek_cmd("s+")
+    }
+
+    /// Returns the addresses in the seek history, oldest first (`sj`).
+    pub fn seek_history(&mut self) -> Result<Vec<Addr>, Error> {
+        let json = self.cmdj("sj")?;
+        let history = json
+            .as_array()
+            .ok_or_else(|| Error::UnexpectedOutput("sj".to_string()))?
+            .iter()
+            .filter_map(|e| serde_json::from_value::<Addr>(e["offset"].clone()).ok())
+            .collect();
+        Ok(history)
+    }
+
+    /// Seeks to `addr` until the returned guard goes out of scope.
+    pub fn seek_guard<A: Into<Addr>>(&mut self, addr: A) -> Result<SeekGuard<'_>, Error> {
+        let previous = self.current_offset()?;
+        self.seek(addr)?;
+        Ok(SeekGuard {
+            pipe: self,
+            previous,
+        })
+    }
+
+    /// Runs `f` with the cursor at `addr`, then restores the previous offset.
+    ///
+    /// The offset is restored even if `f` returns an error or panics.
+    pub fn with_seek<A, T, F>(&mut self, addr: A, f: F) -> Result<T, Error>
+    where
+        A: Into<Addr>,
+        F: FnOnce(&mut R2Pipe) -> Result<T, Error>,
+    {
+        let mut guard = self.seek_guard(addr)?;
+        f(&mut guard)
+    }
+
+    /// Runs a seek command and reads back the resulting offset.
+    fn seek_cmd(&mut self, cmd: &str) -> Result<Addr, Error> {
+        Ok(self.cmd(&format!("{};s", cmd))?.parse()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    #[test]
+    fn history_reads_hex_strings() {
+        let sj = r#"[{"offset":4096},{"offset":"0x2000"},{"name":"bad"}]"#;
+        let mut r2 = R2Pipe::from_commands(MockR2Pipe::new().on("sj", sj));
+        assert_eq!(r2.seek_history().unwrap(), vec![Addr(0x1000), Addr(0x2000)]);
+    }
+}
@@ -0,0 +1,98 @@
+//! Identifying pipes and following their life, for programs running many
+//! sessions at once.
+
+use crate::r2pipe::ReadyInfo;
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Identifier of a pipe, unique in the process and kept for its whole
+/// life, respawns after a hung command included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SessionId(pub u64);
+
+impl SessionId {
+    /// A new identifier, never handed out before in this process.
+    pub fn next() -> SessionId {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        SessionId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for SessionId {
+    /// Same as `next()`, so every pipe gets its own.
+    fn default() -> SessionId {
+        SessionId::next()
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "r2-{}", self.0)
+    }
+}
+
+/// Something that happened to a pipe, see
+/// `R2PipeSpawnOptions::on_lifecycle` and `R2Pipe::set_lifecycle_hook()`.
+#[derive(Debug, Clone)]
+pub enum LifecycleEvent {
+    /// The r2 process started.
+    Spawned {
+        session: SessionId,
+        target: String,
+    },
+    /// r2 answered and is ready for commands.
+    Ready {
+        session: SessionId,
+        info: ReadyInfo,
+    },
+    /// A command failed.
+    CommandFailed {
+        session: SessionId,
+        cmd: String,
+        error: String,
+    },
+    /// r2 was killed after ignoring the interrupt of a hung command, and
+    /// spawned again.
+    Killed {
+        session: SessionId,
+    },
+    Closed {
+        session: SessionId,
+    },
+}
+
+impl LifecycleEvent {
+    pub fn session(&self) -> SessionId {
+        match *self {
+            LifecycleEvent::Spawned { session, .. }
+            | LifecycleEvent::Ready { session, .. }
+            | LifecycleEvent::CommandFailed { session, .. }
+            | LifecycleEvent::Killed { session }
+            | LifecycleEvent::Closed { session } => session,
+        }
+    }
+}
+
+/// Receives the `LifecycleEvent`s of a pipe. To get them on a channel,
+/// send them from the hook:
+///
+/// ```no_run
+/// use r2pipe::{R2Pipe, R2PipeSpawnOptions};
+/// use std::sync::{mpsc, Arc};
+///
+/// let (tx, rx) = mpsc::channel();
+/// let opts = R2PipeSpawnOptions {
+///     on_lifecycle: Some(Arc::new(move |e| {
+///         let _ = tx.send(e.clone());
+///     })),
+///     ..Default::default()
+/// };
+/// let r2 = R2Pipe::spawn("/bin/ls", Some(opts))?;
+/// for event in rx.try_iter() {
+///     println!("{}: {:?}", event.session(), event);
+/// }
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+pub type LifecycleHook = Arc<dyn Fn(&LifecycleEvent) + Send + Sync>;
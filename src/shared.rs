@@ -0,0 +1,99 @@
+//! One session shared by several threads.
+
+use crate::error::Error;
+use crate::r2pipe::{R2Pipe, R2PipeCommands};
+
+use serde_json::Value;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A cheap to clone handle on an `R2Pipe`, usable from any thread.
+///
+/// Commands from different handles run one at a time, each finishing
+/// before the next starts, so replies can't get mixed up. Unlike `R2Pool`
+/// every handle sees the same session, seek and flags included.
+///
+/// ```no_run
+/// use r2pipe::R2Pipe;
+/// use std::thread;
+///
+/// let r2 = R2Pipe::spawn("/bin/ls", None)?.into_shared();
+/// let handles: Vec<_> = ["main", "entry0"]
+///     .iter()
+///     .map(|f| {
+///         let r2 = r2.clone();
+///         thread::spawn(move || r2.cmdj(&format!("pdfj @ {}", f)))
+///     })
+///     .collect();
+/// for h in handles {
+///     println!("{:?}", h.join().unwrap()?);
+/// }
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct SharedR2Pipe {
+    pipe: Arc<Mutex<R2Pipe>>,
+}
+
+impl R2Pipe {
+    /// Wraps the pipe for sharing between threads.
+    pub fn into_shared(self) -> SharedR2Pipe {
+        SharedR2Pipe {
+            pipe: Arc::new(Mutex::new(self)),
+        }
+    }
+}
+
+impl SharedR2Pipe {
+    pub fn cmd(&self, cmd: &str) -> Result<String, Error> {
+        self.lock().cmd(cmd)
+    }
+
+    pub fn cmdj(&self, cmd: &str) -> Result<Value, Error> {
+        self.lock().cmdj(cmd)
+    }
+
+    /// Runs `f` with exclusive access to the session, for sequences of
+    /// commands that depend on each other (seek, then print...).
+    ///
+    /// Commands from other handles wait until `f` returns, so `f` must not
+    /// use another handle on the same pipe: that would wait forever.
+    pub fn with<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut R2Pipe) -> T,
+    {
+        f(&mut self.lock())
+    }
+
+    /// Closes the session for every handle.
+    pub fn close(&self) {
+        self.lock().close();
+    }
+
+    /// Gives the pipe back, if this is the last handle.
+    pub fn try_unwrap(self) -> Result<R2Pipe, SharedR2Pipe> {
+        match Arc::try_unwrap(self.pipe) {
+            Ok(pipe) => Ok(pipe.into_inner().unwrap_or_else(|e| e.into_inner())),
+            Err(pipe) => Err(SharedR2Pipe { pipe }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, R2Pipe> {
+        // A panic in `with()` may leave a sequence half done, but commands
+        // always run to completion, so the pipe itself is still usable.
+        self.pipe.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl R2PipeCommands for SharedR2Pipe {
+    fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
+        SharedR2Pipe::cmd(self, cmd)
+    }
+
+    fn cmdj(&mut self, cmd: &str) -> Result<Value, Error> {
+        SharedR2Pipe::cmdj(self, cmd)
+    }
+
+    fn close(&mut self) {
+        SharedR2Pipe::close(self);
+    }
+}
@@ -0,0 +1,119 @@
+//! Looking at a shellcode in one call, without a file to open.
+
+use crate::analysis::AnalysisLevel;
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::raw::{MapSpec, RawOptions};
+use crate::structs::{Addr, Instruction, StringEntry};
+
+use serde_derive::Deserialize;
+
+/// A call or system call made by the shellcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellcodeCall {
+    pub addr: Addr,
+    /// Direct call target, `None` for indirect calls and syscalls.
+    pub target: Option<Addr>,
+    /// The flag at the target, or the syscall name, empty if unknown.
+    pub name: String,
+}
+
+/// What `R2Pipe::analyze_shellcode()` found, addresses starting at 0.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShellcodeAnalysis {
+    /// Linear disassembly of all the bytes.
+    pub instructions: Vec<Instruction>,
+    pub strings: Vec<StringEntry>,
+    pub calls: Vec<ShellcodeCall>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct SyscallHit {
+    #[serde(alias = "offset")]
+    addr: Addr,
+    name: String,
+}
+
+impl R2Pipe {
+    /// Spawns r2 over `malloc://` with `bytes` mapped at 0 as `arch` and
+    /// `bits`, analyzes them (`aaa`) and collects the instructions, the
+    /// strings (`izzj`), the calls and the syscalls (`/asj`).
+    ///
+    /// r2 is closed afterwards, use `spawn_raw()` on a file to keep
+    /// working on the session.
+    ///
+    /// ```no_run
+    /// use r2pipe::R2Pipe;
+    ///
+    /// let sc = std::fs::read("payload.bin")?;
+    /// let res = R2Pipe::analyze_shellcode(&sc, "x86", 32)?;
+    /// for call in res.calls {
+    ///     println!("{} {}", call.addr, call.name);
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn analyze_shellcode(
+        bytes: &[u8],
+        arch: &str,
+        bits: u32,
+    ) -> Result<ShellcodeAnalysis, Error> {
+        if bytes.is_empty() {
+            return Err(Error::InvalidCommand("Empty shellcode".to_string()));
+        }
+        let size = bytes.len() as u64;
+        let mut r2 = R2Pipe::spawn_raw(
+            &format!("malloc://{}", size),
+            RawOptions {
+                arch: arch.to_string(),
+                bits,
+                maps: vec![MapSpec {
+                    addr: Addr(0),
+                    size,
+                    offset: 0,
+                    perms: "rwx".to_string(),
+                }],
+                ..Default::default()
+            },
+        )?;
+        let res = r2.shellcode_analysis(bytes);
+        r2.close();
+        res
+    }
+
+    fn shellcode_analysis(&mut self, bytes: &[u8]) -> Result<ShellcodeAnalysis, Error> {
+        self.write_bytes(0u64, bytes)?;
+        self.cmd_ok("af @ 0")?;
+        self.analyze(AnalysisLevel::Auto)?;
+        let instructions = self
+            .iter_instructions(0u64, bytes.len() as u64)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut calls = Vec::new();
+        for ins in instructions.iter().filter(|i| i.kind.ends_with("call")) {
+            let name = match ins.jump {
+                Some(target) => self.cmd(&format!("fd @ {}", target))?,
+                None => String::new(),
+            };
+            calls.push(ShellcodeCall {
+                addr: ins.offset,
+                target: ins.jump,
+                name: name.trim().trim_end_matches(" + 0").to_string(),
+            });
+        }
+        let syscalls: Vec<SyscallHit> = match self.cmd_deserialize("/asj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        calls.extend(syscalls.into_iter().map(|s| ShellcodeCall {
+            addr: s.addr,
+            target: None,
+            name: s.name,
+        }));
+        calls.sort_by_key(|c| c.addr);
+        Ok(ShellcodeAnalysis {
+            instructions,
+            strings: self.all_strings()?,
+            calls,
+        })
+    }
+}
@@ -0,0 +1,145 @@
+//! Library function identification through FLIRT signatures, and
+//! function similarity through zignatures (`z*`).
+
+use crate::error::Error;
+use crate::r2pipe::{file_arg, parse_json, R2Pipe};
+use crate::structs::Addr;
+
+use serde::de::IgnoredAny;
+use serde_derive::Deserialize;
+
+/// Outcome of applying a FLIRT `.sig` file to the current session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlirtResult {
+    /// Number of functions renamed after a signature match.
+    pub renamed: usize,
+    /// Raw output printed by r2 while scanning.
+    pub output: String,
+}
+
+/// A function resembling another one, as scored by `zbj`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimilarFunction {
+    pub name: String,
+    /// `None` when the name doesn't resolve any more.
+    pub addr: Option<Addr>,
+    /// From 0 to 1, 1 for identical functions.
+    pub similarity: f64,
+    /// Similarity of the bytes alone.
+    pub bytes: f64,
+    /// Similarity of the control flow graphs alone.
+    pub graph: f64,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BestMatch {
+    name: String,
+    similarity: f64,
+    byte: f64,
+    graph: f64,
+}
+
+impl R2Pipe {
+    /// Applies the FLIRT signatures in `path` (`zfs`) to the analyzed functions.
+    ///
+    /// Functions must already be analyzed for any match to be found.
+    pub fn apply_flirt(&mut self, path: &str) -> Result<FlirtResult, Error> {
+        file_arg(path)?;
+        let before = self.count_flirt_functions()?;
+        let output = self.cmd(&format!("zfs {}", path))?;
+        let after = self.count_flirt_functions()?;
+        Ok(FlirtResult {
+            renamed: after.saturating_sub(before),
+            output,
+        })
+    }
+
+    /// Dumps the content of the FLIRT signature file in `path` (`zfd`).
+    pub fn dump_flirt(&mut self, path: &str) -> Result<String, Error> {
+        file_arg(path)?;
+        let res = self.cmd(&format!("zfd {}", path))?;
+        if res.trim().is_empty() {
+            return Err(Error::Other(format!(
+                "Unable to dump FLIRT signatures from {}",
+                path
+            )));
+        }
+        Ok(res)
+    }
+
+    fn zignature_count(&mut self) -> Result<usize, Error> {
+        match self.cmd_deserialize::<Vec<IgnoredAny>>("zj") {
+            Err(ref e) if e.is_empty_response() => Ok(0),
+            res => Ok(res?.len()),
+        }
+    }
+
+    /// Counts the functions renamed by a FLIRT match (`flirt.` prefix).
+    fn count_flirt_functions(&mut self) -> Result<usize, Error> {
+        let res = self.cmd("aflj")?;
+        if res.trim().is_empty() {
+            return Ok(0);
+        }
+        let fns: serde_json::Value = parse_json("aflj", res.into_bytes())?;
+        let count = fns
+            .as_array()
+            .map(|fns| {
+                fns.iter()
+                    .filter_map(|f| f["name"].as_str())
+                    .filter(|name| name.starts_with("flirt."))
+                    .count()
+            })
+            .unwrap_or(0);
+        Ok(count)
+    }
+
+    /// The functions resembling the one at `addr` with a similarity of at
+    /// least `threshold` (0 to 1), best first (`zbj`).
+    ///
+    /// Zignatures are generated for all the functions (`zg`) when there
+    /// are none yet, so analyze first. Zignatures loaded before are
+    /// searched too.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.cmd("aa")?;
+    /// for f in r2.find_similar_functions(0x1000u64, 0.9)? {
+    ///     println!("{} ({:.2})", f.name, f.similarity);
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn find_similar_functions<A: Into<Addr>>(
+        &mut self,
+        addr: A,
+        threshold: f64,
+    ) -> Result<Vec<SimilarFunction>, Error> {
+        let addr = addr.into();
+        if self.zignature_count()? == 0 {
+            self.cmd_ok("zg")?;
+        }
+        let name = self.cmd(&format!("afn @ {}", addr))?.trim().to_string();
+        let count = self.zignature_count()?.max(1);
+        let matches: Vec<BestMatch> =
+            match self.cmd_deserialize(&format!("zbj {} @ {}", count, addr)) {
+                Err(ref e) if e.is_empty_response() => return Ok(Vec::new()),
+                res => res?,
+            };
+        let mut similar = Vec::new();
+        for m in matches {
+            if m.similarity < threshold || m.name == name {
+                continue;
+            }
+            let addr = self.eval(&m.name).ok().filter(|&a| a != 0).map(Addr);
+            similar.push(SimilarFunction {
+                name: m.name,
+                addr,
+                similarity: m.similarity,
+                bytes: m.byte,
+                graph: m.graph,
+            });
+        }
+        similar.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        Ok(similar)
+    }
+}
@@ -0,0 +1,101 @@
+//! Saving the state of a session to go back to it later, for undo or
+//! exploring alternatives in the same r2.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, Flag};
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// What `R2Pipe::snapshot()` saves.
+///
+/// Analysis, comments and the file itself aren't part of it, only what's
+/// cheap to read and put back.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionState {
+    pub seek: Addr,
+    /// Every configuration variable (`ej`).
+    pub config: BTreeMap<String, String>,
+    pub flags: Vec<Flag>,
+    /// The writes kept in `io.cache`, as the commands redoing them
+    /// (`wc*`).
+    pub write_cache: Vec<String>,
+}
+
+impl R2Pipe {
+    /// Saves the seek, the configuration, the flags and the write cache.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// r2.set_config("io.cache", "true")?;
+    /// let before = r2.snapshot()?;
+    /// r2.write_bytes(0x1000u64, &[0xcc])?;
+    /// r2.cmd("f try.1 @ 0x1000")?;
+    /// r2.restore(&before)?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn snapshot(&mut self) -> Result<SessionState, Error> {
+        let config: BTreeMap<String, Value> = self.cmd_deserialize("ej")?;
+        let flags = match self.cmd_deserialize("fj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        let write_cache = self
+            .cmd("wc*")?
+            .lines()
+            .map(str::trim)
+            .filter(|l| l.starts_with("wx ") && !l.contains(';'))
+            .map(str::to_string)
+            .collect();
+        Ok(SessionState {
+            seek: self.current_offset()?,
+            config: config
+                .into_iter()
+                .map(|(k, v)| (k, config_value(v)))
+                .collect(),
+            flags,
+            write_cache,
+        })
+    }
+
+    /// Goes back to `state`: sets the variables that changed since, the
+    /// cached writes, the flags and the seek.
+    ///
+    /// Flags are all put back in the current flagspace. Variables that
+    /// can't be set back, e.g. read only ones, are skipped.
+    pub fn restore(&mut self, state: &SessionState) -> Result<(), Error> {
+        let now = self.snapshot()?;
+        for (key, value) in &state.config {
+            if now.config.get(key) == Some(value) || value.contains(['"', '\n']) {
+                continue;
+            }
+            self.cmd_ok(&format!("\"e {}={}\"", key, value))?;
+        }
+        if now.write_cache != state.write_cache {
+            self.cmd_ok("wc-*")?;
+            for write in &state.write_cache {
+                self.cmd_ok(write)?;
+            }
+        }
+        for flag in &now.flags {
+            if !state.flags.iter().any(|f| f.name == flag.name) {
+                self.cmd_ok(&format!("f-{}", flag.name))?;
+            }
+        }
+        for flag in &state.flags {
+            if !now.flags.contains(flag) {
+                self.cmd_ok(&format!("f {} {} @ {}", flag.name, flag.size, flag.offset))?;
+            }
+        }
+        self.cmd_ok(&format!("s {}", state.seek))
+    }
+}
+
+/// `ej` values as `e` prints them.
+fn config_value(v: Value) -> String {
+    match v {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,165 @@
+//! The stack frame of a function as one structure: its size, the
+//! registers it saves and where its variables live (`afij`, `afvj`).
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::{Addr, FunctionInfo, Instruction};
+
+use serde_derive::Deserialize;
+
+/// A variable or argument on the stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackVar {
+    pub name: String,
+    /// C type, as r2 guessed or was told.
+    pub ty: String,
+    /// Register the offset is relative to, e.g. `rbp` or `sp`.
+    pub base: String,
+    pub offset: i64,
+    /// Bytes up to the next variable on the same base, or to the base
+    /// itself for the last one below it; 0 when unknown.
+    ///
+    /// This is the room writes to the variable have before clobbering
+    /// something else, whatever its type says.
+    pub size: u64,
+    /// Arguments passed on the stack, rather than locals.
+    pub is_arg: bool,
+}
+
+/// An argument passed in a register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegArg {
+    pub name: String,
+    pub ty: String,
+    pub reg: String,
+}
+
+/// The stack layout of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub addr: Addr,
+    /// Bytes the function reserves (`stackframe` of `afij`).
+    pub size: u64,
+    /// Registers pushed by the prologue, in order. Only `push` instructions
+    /// are recognized, not `stp` or `mov` to the stack.
+    pub saved_regs: Vec<String>,
+    /// Sorted by base, then offset.
+    pub vars: Vec<StackVar>,
+    pub reg_args: Vec<RegArg>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Vars {
+    reg: Vec<RegVar>,
+    sp: Vec<MemVar>,
+    bp: Vec<MemVar>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RegVar {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(rename = "ref")]
+    reg: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct MemVar {
+    name: String,
+    kind: String,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(rename = "ref")]
+    at: MemRef,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct MemRef {
+    base: String,
+    offset: i64,
+}
+
+/// Instructions looked at for the prologue.
+const PROLOGUE_LEN: usize = 16;
+
+impl R2Pipe {
+    /// The stack frame of the function at `addr`, which must have been
+    /// analyzed.
+    pub fn stack_frame<A: Into<Addr>>(&mut self, addr: A) -> Result<StackFrame, Error> {
+        let addr = addr.into();
+        let info: Vec<FunctionInfo> = self.cmd_deserialize(&format!("afij @ {}", addr))?;
+        let info = info
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::Other(format!("No function at {}", addr)))?;
+        let vars: Vars = self.cmd_deserialize(&format!("afvj @ {}", info.offset))?;
+        let prologue: Vec<Instruction> =
+            self.cmd_deserialize(&format!("pdj {} @ {}", PROLOGUE_LEN, info.offset))?;
+
+        let mut stack: Vec<StackVar> = vars
+            .bp
+            .into_iter()
+            .chain(vars.sp)
+            .map(|v| StackVar {
+                is_arg: v.kind == "arg",
+                name: v.name,
+                ty: v.ty,
+                base: v.at.base,
+                offset: v.at.offset,
+                size: 0,
+            })
+            .collect();
+        stack.sort_by(|a, b| (&a.base, a.offset).cmp(&(&b.base, b.offset)));
+        for i in 0..stack.len() {
+            let end = match stack.get(i + 1) {
+                Some(next) if next.base == stack[i].base => next.offset,
+                // Locals below the frame pointer end where it points.
+                _ if stack[i].offset < 0 => 0,
+                _ => continue,
+            };
+            stack[i].size = (end - stack[i].offset) as u64;
+        }
+        Ok(StackFrame {
+            addr: info.offset,
+            size: info.stackframe,
+            saved_regs: saved_regs(&prologue),
+            vars: stack,
+            reg_args: vars
+                .reg
+                .into_iter()
+                .map(|v| RegArg {
+                    name: v.name,
+                    ty: v.ty,
+                    reg: v.reg,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Registers of the `push` instructions starting the function, past any
+/// `endbr64`-like padding.
+fn saved_regs(prologue: &[Instruction]) -> Vec<String> {
+    let mut regs = Vec::new();
+    for ins in prologue {
+        let mut words = ins.disasm.splitn(2, ' ');
+        match (words.next(), words.next()) {
+            (Some("push"), Some(ops)) => regs.extend(
+                ops.trim_matches(|c| c == '{' || c == '}' || c == ' ')
+                    .split(',')
+                    .map(|r| r.trim().to_string())
+                    .filter(|r| !r.is_empty()),
+            ),
+            (Some("endbr64"), _) | (Some("endbr32"), _) | (Some("nop"), _) => {}
+            // `mov rbp, rsp` sits between the pushes on x86.
+            (Some("mov"), Some(ops)) if regs.len() == 1 && ops.ends_with("sp") => {}
+            _ => break,
+        }
+    }
+    regs
+}
@@ -0,0 +1,57 @@
+//! Counters kept by every pipe, see `R2Pipe::stats()`.
+
+use std::time::Duration;
+
+/// Upper bounds of the buckets of `PipeStats::latency_histogram`, the last
+/// bucket counting everything slower.
+pub const LATENCY_BUCKETS: [Duration; 5] = [
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+    Duration::from_secs(10),
+];
+
+/// What went through a pipe since it was opened, or since the last
+/// `R2Pipe::reset_stats()`.
+#[derive(Clone, Debug, Default)]
+pub struct PipeStats {
+    /// Commands run, failed ones included.
+    pub commands: u64,
+    pub errors: u64,
+    /// Bytes of commands sent, line endings excluded.
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+    /// Number of commands per latency bucket, see `LATENCY_BUCKETS`.
+    pub latency_histogram: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+impl PipeStats {
+    /// Average time a command took, zero before the first one.
+    pub fn mean_latency(&self) -> Duration {
+        match self.commands {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos((self.total_latency.as_nanos() / u128::from(n)) as u64),
+        }
+    }
+
+    /// Accounts for one command of `sent` bytes, which replied `received`
+    /// bytes (`None` on errors) after `latency`.
+    pub(crate) fn record(&mut self, sent: usize, received: Option<usize>, latency: Duration) {
+        self.commands += 1;
+        self.bytes_sent += sent as u64;
+        match received {
+            Some(n) => self.bytes_received += n as u64,
+            None => self.errors += 1,
+        }
+        self.total_latency += latency;
+        self.max_latency = self.max_latency.max(latency);
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|b| latency <= *b)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency_histogram[bucket] += 1;
+    }
+}
@@ -0,0 +1,235 @@
+//! Streaming deserialization of commands printing large JSON arrays.
+//!
+//! `cmdj_stream()` yields the elements of the top-level array one at a time
+//! while the reply is still being read, so peak memory is bounded by the
+//! largest element instead of the whole output of e.g. `izzj` or `aflj`.
+
+use crate::error::Error;
+use crate::r2pipe::{json_error, R2Pipe, JSON_EXCERPT};
+use crate::session::SessionId;
+
+use serde::de::DeserializeOwned;
+use serde_json::de::IoRead;
+use serde_json::{Deserializer, StreamDeserializer};
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::rc::Rc;
+
+/// Turns a JSON array into a whitespace separated sequence of its elements,
+/// which `StreamDeserializer` can read one by one.
+struct ArrayItems<R> {
+    inner: R,
+    /// The beginning of the array, for the errors.
+    head: Rc<RefCell<Vec<u8>>>,
+    started: bool,
+    finished: bool,
+    depth: usize,
+    in_string: bool,
+    escape: bool,
+}
+
+impl<R: Read> ArrayItems<R> {
+    fn new(inner: R) -> ArrayItems<R> {
+        ArrayItems {
+            inner,
+            head: Rc::default(),
+            started: false,
+            finished: false,
+            depth: 0,
+            in_string: false,
+            escape: false,
+        }
+    }
+
+    fn head(&self) -> Rc<RefCell<Vec<u8>>> {
+        Rc::clone(&self.head)
+    }
+}
+
+impl<R: Read> Read for ArrayItems<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(out)?;
+        let mut head = self.head.borrow_mut();
+        let keep = JSON_EXCERPT.saturating_sub(head.len());
+        head.extend_from_slice(&out[..n.min(keep)]);
+        for b in out[..n].iter_mut() {
+            if self.finished {
+                continue;
+            }
+            if !self.started {
+                match *b {
+                    b'[' => {
+                        self.started = true;
+                        self.depth = 1;
+                        *b = b' ';
+                    }
+                    c if c.is_ascii_whitespace() => {}
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "output is not a JSON array",
+                        ))
+                    }
+                }
+                continue;
+            }
+            if self.in_string {
+                match *b {
+                    _ if self.escape => self.escape = false,
+                    b'\\' => self.escape = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match *b {
+                b'"' => self.in_string = true,
+                b'[' | b'{' => self.depth += 1,
+                b']' if self.depth == 1 => {
+                    self.finished = true;
+                    *b = b' ';
+                }
+                b']' | b'}' => self.depth = self.depth.saturating_sub(1),
+                b',' if self.depth == 1 => *b = b' ',
+                _ => {}
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Iterator over the elements of a JSON array printed by a command.
+///
+/// Dropping it early discards the rest of the reply.
+pub struct JsonStream<'a, T> {
+    inner: StreamDeserializer<'a, IoRead<ArrayItems<Box<dyn Read + 'a>>>, T>,
+    cmd: String,
+    session: SessionId,
+    head: Rc<RefCell<Vec<u8>>>,
+    failed: bool,
+}
+
+impl<'a, T> JsonStream<'a, T> {
+    /// The error of the reply itself when reading it failed, or `e` with
+    /// the beginning of the reply.
+    fn error(&self, e: serde_json::Error) -> Error {
+        let message = if e.is_io() {
+            match io::Error::from(e).downcast::<Error>() {
+                Ok(e) => return e,
+                Err(e) => e.to_string(),
+            }
+        } else {
+            e.to_string()
+        };
+        Error::Session {
+            session: self.session,
+            stderr: String::new(),
+            error: Box::new(json_error(&self.cmd, &self.head.borrow(), message)),
+        }
+    }
+}
+
+impl<'a, T: DeserializeOwned> Iterator for JsonStream<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.inner.next()? {
+            Ok(item) => Some(Ok(item)),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(self.error(e)))
+            }
+        }
+    }
+}
+
+impl R2Pipe {
+    /// Runs `cmd` and lazily deserializes each element of its JSON array.
+    pub fn cmdj_stream<T: DeserializeOwned>(
+        &mut self,
+        cmd: &str,
+    ) -> Result<JsonStream<'_, T>, Error> {
+        let session = self.session_id();
+        let reader = self.cmd_reader(cmd)?;
+        let items = ArrayItems::new(reader);
+        let head = items.head();
+        Ok(JsonStream {
+            inner: Deserializer::from_reader(items).into_iter(),
+            cmd: cmd.to_string(),
+            session,
+            head,
+            failed: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockR2Pipe;
+
+    /// Hands out a byte per read, to cross every state between reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), out.first_mut()) {
+                (Some((b, rest)), Some(o)) => {
+                    *o = *b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn splits_array_items() {
+        let json = br#" [{"a": [1, 2]}, "x,]\"}", 3, [[]]] trailing"#;
+        let mut out = String::new();
+        ArrayItems::new(Trickle(json))
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, r#"  {"a": [1, 2]}  "x,]\"}"  3  [[]]  trailing"#);
+        let json = br#"[{"a": [1, 2]},"x,]"]"#;
+        let items: Vec<serde_json::Value> = Deserializer::from_reader(ArrayItems::new(&json[..]))
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(items, vec![serde_json::json!({"a": [1, 2]}), "x,]".into()]);
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        let mut out = String::new();
+        let e = ArrayItems::new(&b"  {\"a\": 1}"[..])
+            .read_to_string(&mut out)
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn streams_a_reply() {
+        let mock = MockR2Pipe::new().expect("aflj", r#"[{"name": "main"}, {"name": "exit"}, 7]"#);
+        let mut r2 = R2Pipe::from_commands(mock);
+        let mut names = r2
+            .cmdj_stream::<crate::structs::FunctionInfo>("aflj")
+            .unwrap();
+        assert_eq!(names.next().unwrap().unwrap().name, "main");
+        assert_eq!(names.next().unwrap().unwrap().name, "exit");
+        let e = names.next().unwrap().unwrap_err();
+        assert!(e.session().is_some());
+        match e.root() {
+            Error::Json { cmd, excerpt, .. } => {
+                assert_eq!(cmd, "aflj");
+                assert!(excerpt.starts_with(r#"[{"name": "main"}"#));
+            }
+            e => panic!("{:?}", e),
+        }
+        assert!(names.next().is_none());
+    }
+}
@@ -0,0 +1,151 @@
+//! Sorting the strings of a binary into what they likely are, a usual
+//! first step when triaging a sample.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+use crate::structs::StringEntry;
+
+/// What a string looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StringKind {
+    /// `scheme://...` or `www.`.
+    Url,
+    /// Unix, Windows or UNC path.
+    Path,
+    /// A long run of base64 characters.
+    Base64,
+    /// Has printf conversions, e.g. `%s` or `%08x`.
+    FormatString,
+    /// Encoded in UTF-16 or UTF-32.
+    Wide,
+}
+
+/// A string with all the kinds it matches, `kinds` being never empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassifiedString {
+    pub entry: StringEntry,
+    pub kinds: Vec<StringKind>,
+}
+
+/// Base64 runs shorter than this are too likely to be plain words.
+const MIN_BASE64_LEN: usize = 16;
+
+impl R2Pipe {
+    /// The strings of the whole file (`izzj`) matching at least one
+    /// `StringKind`.
+    pub fn classify_strings(&mut self) -> Result<Vec<ClassifiedString>, Error> {
+        Ok(self
+            .all_strings()?
+            .into_iter()
+            .filter_map(|entry| {
+                let kinds = classify(&entry);
+                if kinds.is_empty() {
+                    None
+                } else {
+                    Some(ClassifiedString { entry, kinds })
+                }
+            })
+            .collect())
+    }
+}
+
+/// The kinds `entry` matches, possibly none.
+///
+/// ```
+/// use r2pipe::strings::{classify, StringKind};
+/// use r2pipe::structs::StringEntry;
+///
+/// let entry = StringEntry {
+///     string: "GET %s HTTP/1.1".to_string(),
+///     ..Default::default()
+/// };
+/// assert_eq!(classify(&entry), vec![StringKind::FormatString]);
+/// ```
+pub fn classify(entry: &StringEntry) -> Vec<StringKind> {
+    let s = entry.string.trim();
+    let checks: [(StringKind, bool); 5] = [
+        (StringKind::Url, is_url(s)),
+        (StringKind::Path, is_path(s)),
+        (StringKind::Base64, is_base64(s)),
+        (StringKind::FormatString, is_format_string(s)),
+        (
+            StringKind::Wide,
+            entry.kind.starts_with("utf16")
+                || entry.kind.starts_with("utf32")
+                || entry.kind == "wide",
+        ),
+    ];
+    checks
+        .iter()
+        .filter(|(_, hit)| *hit)
+        .map(|(kind, _)| *kind)
+        .collect()
+}
+
+fn is_url(s: &str) -> bool {
+    if s.starts_with("www.") {
+        return true;
+    }
+    s.find("://").is_some_and(|i| {
+        let scheme = s[..i]
+            .rsplit(|c: char| !c.is_ascii_alphanumeric())
+            .next()
+            .unwrap_or("");
+        scheme.len() >= 2
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && s.len() > i + 3
+    })
+}
+
+fn is_path(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let drive = bytes.len() > 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+    let unix = (s.starts_with('/') || s.starts_with("~/") || s.starts_with("./"))
+        && s.len() > 2
+        && s[1..].contains('/')
+        && !s.contains(char::is_whitespace);
+    drive || unix || s.starts_with("\\\\") || (s.starts_with('%') && s.contains("%\\"))
+}
+
+fn is_base64(s: &str) -> bool {
+    let body = s.trim_end_matches('=');
+    if s.len() < MIN_BASE64_LEN || !s.len().is_multiple_of(4) || s.len() - body.len() > 2 {
+        return false;
+    }
+    let alphabet = |c: char| c.is_ascii_alphanumeric() || c == '+' || c == '/';
+    // Plain identifiers are valid base64 too, real blobs mix cases and digits.
+    body.chars().all(alphabet)
+        && body.contains(|c: char| c.is_ascii_uppercase())
+        && body.contains(|c: char| c.is_ascii_lowercase())
+        && body.contains(|c: char| c.is_ascii_digit() || c == '+' || c == '/')
+}
+
+fn is_format_string(s: &str) -> bool {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            continue;
+        }
+        // Flags, width, precision and length, then the conversion.
+        while chars
+            .peek()
+            .is_some_and(|c| "-+ #0123456789.*hlLqjzt".contains(*c))
+        {
+            chars.next();
+        }
+        if chars
+            .peek()
+            .is_some_and(|c| "diouxXeEfFgGaAcspn".contains(*c))
+        {
+            return true;
+        }
+    }
+    false
+}
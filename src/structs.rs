@@ -0,0 +1,1010 @@
+//! Data models for the JSON output of r2 commands.
+//!
+//! Fields r2 may omit default to zero/empty, so the same structs work across
+//! r2 versions. This module doesn't depend on the pipe machinery and is
+//! available with `default-features = false`, to deserialize stored r2 JSON
+//! dumps without spawning anything.
+//!
+//! With the `serde-serialize` feature the models also implement `Serialize`,
+//! so analysis results can be cached or sent over the wire as they are.
+//!
+//! JSON already at hand, e.g. from `cmdj()`, converts with `TryFrom<Value>`,
+//! and back with `From<_> for Value` with `serde-serialize`:
+//!
+//! ```
+//! use r2pipe::Section;
+//! use std::convert::TryFrom;
+//!
+//! let json = serde_json::json!({"name": ".text", "vaddr": 4096, "perm": "-r-x"});
+//! let section = Section::try_from(json)?;
+//! assert_eq!(section.vaddr.0, 0x1000);
+//! # Ok::<(), r2pipe::Error>(())
+//! ```
+
+use crate::error::Error;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde_derive::Deserialize;
+#[cfg(feature = "serde-serialize")]
+use serde_derive::Serialize;
+use serde_json::Value;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// An address, displayed as `0x` prefixed hex like r2 does.
+///
+/// r2 reports addresses either as JSON numbers or as hex/decimal strings
+/// depending on the command; `Addr` deserializes from all of them.
+///
+/// Fields named `paddr` hold file offsets, all the others virtual
+/// addresses. `AddressSpace` converts between the two.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Addr(pub u64);
+
+impl Addr {
+    pub fn value(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl From<u64> for Addr {
+    fn from(addr: u64) -> Addr {
+        Addr(addr)
+    }
+}
+
+impl From<Addr> for u64 {
+    fn from(addr: Addr) -> u64 {
+        addr.0
+    }
+}
+
+impl FromStr for Addr {
+    type Err = String;
+
+    /// Parses a number as `parse_r2_number()` does.
+    fn from_str(s: &str) -> Result<Addr, String> {
+        parse_r2_number(s)
+            .map(Addr)
+            .map_err(|_| format!("Invalid address: {}", s.trim()))
+    }
+}
+
+/// Parses a number as r2 prints them outside of JSON: `0x` prefixed hex,
+/// `0b` binary, `0o` octal or plain decimal.
+///
+/// ```
+/// assert_eq!(r2pipe::parse_r2_number("0x1f")?, 31);
+/// assert_eq!(r2pipe::parse_r2_number(" 42\n")?, 42);
+/// assert!(r2pipe::parse_r2_number("0xzz").is_err());
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+pub fn parse_r2_number(s: &str) -> Result<u64, Error> {
+    let s = s.trim();
+    let prefixed = |p: &str| {
+        s.strip_prefix(p)
+            .or_else(|| s.strip_prefix(&p.to_ascii_uppercase()))
+    };
+    let res = if let Some(hex) = prefixed("0x") {
+        u64::from_str_radix(hex, 16)
+    } else if let Some(bin) = prefixed("0b") {
+        u64::from_str_radix(bin, 2)
+    } else if let Some(oct) = prefixed("0o") {
+        u64::from_str_radix(oct, 8)
+    } else {
+        s.parse()
+    };
+    res.map_err(|_| Error::UnexpectedOutput(format!("Not a number: {:?}", s)))
+}
+
+/// Parses a size as r2 prints them for humans (`humansz`, `?u`): a number
+/// with an optional `K`, `M`, `G` or `T` unit of 1024, as in `724`,
+/// `5.8K`, `1.2MB` or `4 GiB`.
+///
+/// Fractional sizes are rounded to the nearest byte.
+///
+/// ```
+/// assert_eq!(r2pipe::parse_size("5.5K")?, 5632);
+/// assert_eq!(r2pipe::parse_size("2 MiB")?, 2 << 20);
+/// assert_eq!(r2pipe::parse_size("0x200")?, 512);
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+pub fn parse_size(s: &str) -> Result<u64, Error> {
+    let s = s.trim();
+    let invalid = || Error::UnexpectedOutput(format!("Not a size: {:?}", s));
+    if let Ok(n) = parse_r2_number(s) {
+        return Ok(n);
+    }
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (num, unit) = (&s[..split], s[split..].trim());
+    let shift = match unit
+        .trim_end_matches(['B', 'b'])
+        .trim_end_matches('i')
+        .to_ascii_uppercase()
+        .as_str()
+    {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(invalid()),
+    };
+    let num: f64 = num.parse().map_err(|_| invalid())?;
+    Ok((num * (1u64 << shift) as f64).round() as u64)
+}
+
+/// Serialized as a plain number.
+#[cfg(feature = "serde-serialize")]
+impl serde::Serialize for Addr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Addr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Addr, D::Error> {
+        struct AddrVisitor;
+
+        impl<'de> Visitor<'de> for AddrVisitor {
+            type Value = Addr;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an address as a number or a hex string")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Addr, E> {
+                Ok(Addr(v))
+            }
+
+            // r2 prints some unset addresses as -1
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Addr, E> {
+                Ok(Addr(v as u64))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Addr, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(AddrVisitor)
+    }
+}
+
+/// A function as listed by `aflj` or `afij`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct FunctionInfo {
+    #[serde(alias = "addr")]
+    pub offset: Addr,
+    pub name: String,
+    pub size: u64,
+    pub realsz: u64,
+    pub nbbs: u64,
+    #[serde(alias = "ninstr")]
+    pub ninstrs: u64,
+    /// Cyclomatic complexity.
+    pub cc: u64,
+    pub edges: u64,
+    pub calltype: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub nargs: u64,
+    pub nlocals: u64,
+    /// Bytes of stack the function reserves.
+    pub stackframe: u64,
+}
+
+/// A disassembled instruction as printed by `pdj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Instruction {
+    #[serde(alias = "addr")]
+    pub offset: Addr,
+    pub size: u64,
+    pub opcode: String,
+    pub disasm: String,
+    pub bytes: String,
+    pub family: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub esil: String,
+    pub jump: Option<Addr>,
+    pub fail: Option<Addr>,
+    pub ptr: Option<Addr>,
+    /// Immediate operand, if any.
+    pub val: Option<u64>,
+}
+
+/// What the analysis was told about an address, as listed by `ahj`.
+///
+/// Fields are `None` when the hint doesn't set them.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Hint {
+    #[serde(alias = "from")]
+    pub addr: Addr,
+    pub arch: Option<String>,
+    pub bits: Option<u64>,
+    /// Size of the instruction.
+    pub size: Option<u64>,
+    /// Text replacing the disassembly of the instruction.
+    pub opcode: Option<String>,
+    pub esil: Option<String>,
+    /// Type replacing the one of the instruction, e.g. `call`.
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    /// Base the immediates are shown in.
+    #[serde(alias = "immbase")]
+    pub immediate_base: Option<u64>,
+    pub jump: Option<Addr>,
+    pub fail: Option<Addr>,
+}
+
+/// Output of `ij`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Info {
+    pub core: CoreInfo,
+    pub bin: BinInfo,
+}
+
+/// The `core` object of `ij`, describing the opened file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct CoreInfo {
+    pub file: String,
+    pub fd: i64,
+    pub size: u64,
+    pub humansz: String,
+    pub iorw: bool,
+    pub mode: String,
+    pub block: u64,
+    pub format: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// The `bin` object of `ij`, describing the loaded binary.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct BinInfo {
+    pub arch: String,
+    pub baddr: Addr,
+    pub binsz: u64,
+    pub bintype: String,
+    pub bits: u64,
+    pub canary: bool,
+    pub class: String,
+    pub compiled: String,
+    pub compiler: String,
+    pub crypto: bool,
+    pub dbg_file: String,
+    pub endian: String,
+    pub havecode: bool,
+    pub intrp: String,
+    pub lang: String,
+    pub machine: String,
+    pub nx: bool,
+    pub os: String,
+    pub pic: bool,
+    pub relocs: bool,
+    /// Whether the binary carries a code signature.
+    pub signed: bool,
+    #[serde(rename = "static")]
+    pub is_static: bool,
+    pub stripped: bool,
+    pub subsys: String,
+    pub va: bool,
+}
+
+/// A section as listed by `iSj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Section {
+    pub name: String,
+    pub size: u64,
+    pub vsize: u64,
+    pub perm: String,
+    /// Offset in the file, `size` bytes long.
+    pub paddr: Addr,
+    /// Address once loaded, `vsize` bytes long.
+    pub vaddr: Addr,
+}
+
+/// A resource of a PE binary, as listed by `iRj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Resource {
+    pub name: String,
+    pub index: u64,
+    /// E.g. `ICON`, `MANIFEST` or `RCDATA`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub vaddr: Addr,
+    pub size: u64,
+    pub lang: String,
+}
+
+/// A flag as listed by `fj`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Flag {
+    pub name: String,
+    /// The name before r2 made it flag safe, e.g. demangled.
+    pub realname: String,
+    #[serde(alias = "addr")]
+    pub offset: Addr,
+    pub size: u64,
+}
+
+/// A comment as listed by `CCj`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Comment {
+    #[serde(alias = "addr")]
+    pub offset: Addr,
+    #[serde(rename = "name")]
+    pub text: String,
+}
+
+/// A breakpoint or watchpoint as listed by `dbj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Breakpoint {
+    pub addr: Addr,
+    pub size: u64,
+    /// Accesses triggering it, e.g. `--x` or `rw-`.
+    pub perm: String,
+    pub hw: bool,
+    pub trace: bool,
+    pub enabled: bool,
+    pub valid: bool,
+    pub name: String,
+    pub cmd: String,
+    pub cond: String,
+}
+
+/// A stack frame as listed by `dbtj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Frame {
+    pub idx: u64,
+    #[serde(alias = "pc")]
+    pub addr: Addr,
+    pub sp: Addr,
+    pub frame_size: u64,
+    /// Empty when no function is known at `addr`.
+    #[serde(alias = "fname")]
+    pub func_name: String,
+    /// Location of `addr`, e.g. `main+20` or `libc.so.6+0x29d90`.
+    pub desc: String,
+}
+
+/// A basic block as listed by `afbj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct BasicBlock {
+    pub addr: Addr,
+    pub size: u64,
+    /// Target of the jump ending the block, if any.
+    pub jump: Option<Addr>,
+    /// Next block when a conditional jump isn't taken.
+    pub fail: Option<Addr>,
+    pub ninstr: u64,
+    pub inputs: u64,
+    pub outputs: u64,
+    pub traced: bool,
+    /// The switch ending the block, when r2 recovered its jump table.
+    pub switch_op: Option<SwitchOp>,
+}
+
+/// A switch recovered by r2, the `switch_op` of `afbj` blocks.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct SwitchOp {
+    /// The indirect branch.
+    pub addr: Addr,
+    pub min_val: u64,
+    pub max_val: u64,
+    /// Value of the default case.
+    pub def_val: u64,
+    pub cases: Vec<SwitchCase>,
+}
+
+/// A case of a `SwitchOp`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct SwitchCase {
+    /// The table entry of the case.
+    pub addr: Addr,
+    /// Code run for the case.
+    pub jump: Addr,
+    pub value: u64,
+}
+
+impl BasicBlock {
+    pub fn contains(&self, addr: Addr) -> bool {
+        addr >= self.addr && addr.0 - self.addr.0 < self.size
+    }
+}
+
+/// An address recorded by the debugger trace, as listed by `dtj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct TracePoint {
+    pub addr: Addr,
+    /// Number of times the address was hit.
+    pub times: u64,
+    pub count: u64,
+    pub size: u64,
+}
+
+/// A source line of the debug info, as listed by `idj`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct LineInfo {
+    #[serde(alias = "offset")]
+    pub addr: Addr,
+    #[serde(alias = "path")]
+    pub file: String,
+    pub line: u64,
+    /// 0 when unknown.
+    #[serde(alias = "col")]
+    pub column: u64,
+}
+
+/// A heap chunk as listed by `dmhj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct HeapChunk {
+    pub addr: Addr,
+    pub size: u64,
+    pub prev_size: u64,
+    /// `allocated` or `free`.
+    pub status: String,
+    /// Free list pointers, zero for allocated chunks.
+    pub fd: Addr,
+    pub bk: Addr,
+}
+
+/// An IO plugin as listed by `oLj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct IoPlugin {
+    pub name: String,
+    pub description: String,
+    /// e.g. `rwd`, `d` for plugins able to debug.
+    pub permissions: String,
+    pub license: String,
+    /// Comma separated schemes, e.g. `gdb://`. Older r2 don't list them.
+    pub uris: String,
+}
+
+impl IoPlugin {
+    /// Whether URIs starting with `scheme://` are opened by this plugin.
+    pub fn handles(&self, scheme: &str) -> bool {
+        self.name == scheme
+            || self
+                .uris
+                .split(',')
+                .any(|u| u.trim().strip_suffix("://") == Some(scheme))
+    }
+}
+
+/// A file opened in the session, as listed by `oj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct OpenFile {
+    pub fd: u64,
+    pub uri: String,
+    /// Whether this is the current file.
+    pub raised: bool,
+    pub writable: bool,
+    pub from: Addr,
+    pub size: u64,
+}
+
+/// A binary loaded from an opened file, as listed by `obj`.
+///
+/// Files holding several binaries, such as fat Mach-O, have one per
+/// loaded slice.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct BinObject {
+    #[serde(alias = "bfid")]
+    pub id: u64,
+    /// The file descriptor of the file it was loaded from.
+    #[serde(alias = "iofd")]
+    pub fd: u64,
+    #[serde(alias = "file")]
+    pub name: String,
+    pub arch: String,
+    pub bits: u64,
+    /// Where the binary starts in the file.
+    #[serde(alias = "binoffset", alias = "boffset")]
+    pub offset: u64,
+    /// Size of the binary, less than the file's for a slice.
+    pub size: u64,
+}
+
+/// A binary of a container holding several, e.g. a fat Mach-O, as listed
+/// by `iAj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct SubBin {
+    pub arch: String,
+    pub bits: u64,
+    /// Where the binary starts in the file.
+    pub offset: u64,
+    pub size: u64,
+    /// The CPU variant, e.g. `arm64e`.
+    pub machine: String,
+}
+
+/// A plugin as listed by `Lcj`, `iLj`, `Laj`...
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Plugin {
+    pub name: String,
+    #[serde(alias = "desc")]
+    pub description: String,
+    pub license: String,
+    pub author: String,
+    pub version: String,
+}
+
+/// A mapping of a file in the address space, as listed by `omj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct IoMap {
+    #[serde(alias = "map")]
+    pub id: u64,
+    pub fd: u64,
+    /// Offset in the file of `from`.
+    pub delta: u64,
+    pub from: Addr,
+    /// Last mapped address, included.
+    pub to: Addr,
+    pub perm: String,
+    pub name: String,
+}
+
+/// An entry point, constructor or destructor, as listed by `iej` or
+/// `ieej`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct EntryPoint {
+    pub vaddr: Addr,
+    pub paddr: Addr,
+    /// `program`, `init`, `fini`, `preinit`, `tls`...
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// An imported symbol, as listed by `iij`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Import {
+    pub ordinal: u64,
+    pub name: String,
+    /// Library it is imported from, when the format records it (PE).
+    #[serde(alias = "lib")]
+    pub libname: String,
+    pub bind: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub plt: Addr,
+}
+
+/// A symbol of the binary, as listed by `isj`, or an exported one by
+/// `iEj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Symbol {
+    pub ordinal: u64,
+    pub name: String,
+    /// Demangled name, empty when the name isn't mangled.
+    pub demname: String,
+    /// Name of the flag r2 set at the symbol.
+    pub flagname: String,
+    pub bind: String,
+    /// `FUNC`, `OBJECT`, `NOTYPE`...
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub size: u64,
+    pub vaddr: Addr,
+    pub paddr: Addr,
+    pub is_imported: bool,
+}
+
+/// Hashes of the whole file, as listed by `itj`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct FileHashes {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+/// A string found in the binary, as listed by `izj` or `izzj`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct StringEntry {
+    pub vaddr: Addr,
+    pub paddr: Addr,
+    pub ordinal: u64,
+    /// In bytes.
+    pub size: u64,
+    /// In characters.
+    pub length: u64,
+    pub section: String,
+    /// Encoding: `ascii`, `utf8`, `utf16le`, `utf32le`...
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub string: String,
+}
+
+/// How a function passes arguments, as shown by `afcrj`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct CallingConvention {
+    /// e.g. `amd64`, `ms`, `arm32`. Not part of `afcrj`, filled from `afc`.
+    pub name: String,
+    /// Register holding the return value.
+    pub ret: String,
+    /// Registers of the integer arguments, in order. Arguments past them go
+    /// on the stack.
+    pub args: Vec<String>,
+    #[serde(alias = "float_args")]
+    pub fargs: Vec<String>,
+    /// Register holding `this`, if any.
+    #[serde(rename = "self")]
+    pub self_reg: String,
+    /// Register holding the error code, if any.
+    pub error: String,
+}
+
+/// A parameter of a `Signature`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Param {
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// Empty for unnamed parameters.
+    pub name: String,
+}
+
+/// A C function prototype, as parsed by `parse_signature()` or listed by
+/// `afsj`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize))]
+#[serde(default)]
+pub struct Signature {
+    #[serde(rename = "return")]
+    pub ret: String,
+    pub name: String,
+    pub args: Vec<Param>,
+    /// Ends with `...`.
+    pub variadic: bool,
+}
+
+impl fmt::Display for Signature {
+    /// Prints the prototype in C, e.g. `int main(int argc, char **argv)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.ret)?;
+        if !self.ret.ends_with('*') {
+            write!(f, " ")?;
+        }
+        write!(f, "{}(", self.name)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg.ty)?;
+            if !arg.name.is_empty() {
+                if !arg.ty.ends_with('*') {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", arg.name)?;
+            }
+        }
+        match (self.variadic, self.args.is_empty()) {
+            (true, true) => write!(f, "...)"),
+            (true, false) => write!(f, ", ...)"),
+            (false, true) => write!(f, "void)"),
+            (false, false) => write!(f, ")"),
+        }
+    }
+}
+
+impl FromStr for Signature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Signature, Error> {
+        parse_signature(s)
+    }
+}
+
+/// Parses a C prototype such as `int main(int argc, char **argv);`.
+///
+/// Parameters without a name (`int f(int, char *)`) are accepted, function
+/// pointer parameters are kept whole as the type. Fails with
+/// `Error::UnexpectedOutput` on anything else.
+///
+/// ```
+/// let sig = r2pipe::parse_signature("char *strdup(const char *s)")?;
+/// assert_eq!(sig.ret, "char *");
+/// assert_eq!(sig.name, "strdup");
+/// assert_eq!(sig.args[0].ty, "const char *");
+/// assert_eq!(sig.to_string(), "char *strdup(const char *s)");
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+pub fn parse_signature(s: &str) -> Result<Signature, Error> {
+    let invalid = || Error::UnexpectedOutput(format!("Not a C prototype: {:?}", s));
+    let sig = s.trim().trim_end_matches(';').trim_end();
+    let open = sig.find('(').ok_or_else(invalid)?;
+    let params = sig[open + 1..].strip_suffix(')').ok_or_else(invalid)?;
+    let (ret, name) = split_declaration(&sig[..open]);
+    if ret.is_empty() || name.is_empty() {
+        return Err(invalid());
+    }
+    let mut res = Signature {
+        ret,
+        name,
+        ..Default::default()
+    };
+    let params = params.trim();
+    if params.is_empty() || params == "void" {
+        return Ok(res);
+    }
+    for param in split_params(params) {
+        let param = param.trim();
+        if param == "..." {
+            res.variadic = true;
+            continue;
+        }
+        if param.is_empty() || res.variadic {
+            return Err(invalid());
+        }
+        let (ty, name) = split_declaration(param);
+        if ty.is_empty() || param.contains('(') || C_TYPE_WORDS.contains(&name.as_str()) {
+            // A single word is a type, `(*cb)(int)` is kept whole.
+            res.args.push(Param {
+                ty: param.to_string(),
+                name: String::new(),
+            });
+        } else {
+            res.args.push(Param { ty, name });
+        }
+    }
+    Ok(res)
+}
+
+/// Words ending an unnamed parameter, e.g. `unsigned int`.
+const C_TYPE_WORDS: &[&str] = &[
+    "char", "short", "int", "long", "float", "double", "signed", "unsigned", "void", "const",
+];
+
+/// Splits `const char *name` into `const char *` and `name`.
+fn split_declaration(decl: &str) -> (String, String) {
+    let decl = decl.trim();
+    let start = decl
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map_or(0, |i| i + 1);
+    let (ty, name) = decl.split_at(start);
+    let ty = ty.trim_end();
+    let ty = if ty.ends_with('*') {
+        // `char*` and `char *` both read `char *`.
+        let stars = ty.len() - ty.trim_end_matches('*').len();
+        format!(
+            "{} {}",
+            ty.trim_end_matches('*').trim_end(),
+            &ty[ty.len() - stars..]
+        )
+    } else {
+        ty.to_string()
+    };
+    (ty, name.to_string())
+}
+
+/// Splits on the commas outside of parentheses.
+fn split_params(params: &str) -> Vec<&str> {
+    let mut res = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                res.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    res.push(&params[start..]);
+    res
+}
+
+macro_rules! value_conversions {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<Value> for $t {
+                type Error = Error;
+
+                fn try_from(json: Value) -> Result<$t, Error> {
+                    Ok(serde_json::from_value(json)?)
+                }
+            }
+
+            #[cfg(feature = "serde-serialize")]
+            impl From<$t> for Value {
+                fn from(model: $t) -> Value {
+                    // Only maps with non-string keys fail, the models have none.
+                    serde_json::to_value(model).unwrap_or(Value::Null)
+                }
+            }
+        )*
+    };
+}
+
+value_conversions!(
+    Addr,
+    FunctionInfo,
+    Instruction,
+    Hint,
+    Info,
+    CoreInfo,
+    BinInfo,
+    Section,
+    Breakpoint,
+    Frame,
+    BasicBlock,
+    TracePoint,
+    HeapChunk,
+    IoPlugin,
+    OpenFile,
+    BinObject,
+    SubBin,
+    Plugin,
+    IoMap,
+    Param,
+    Signature,
+    StringEntry,
+    Import,
+    Symbol,
+    FileHashes,
+    EntryPoint,
+    CallingConvention,
+    SwitchOp,
+    SwitchCase,
+    Resource,
+    LineInfo,
+    Flag,
+    Comment
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addr_from_numbers_and_strings() {
+        let addrs: Vec<Addr> =
+            serde_json::from_str(r#"[4096, "0x1000", "4096", "0X1000", -1]"#).unwrap();
+        assert_eq!(addrs[..4], [Addr(0x1000); 4]);
+        assert_eq!(addrs[4], Addr(u64::MAX));
+        assert!(serde_json::from_str::<Addr>(r#""main""#).is_err());
+        assert!(serde_json::from_str::<Addr>("1.5").is_err());
+        assert_eq!("0x4011d6".parse::<Addr>(), Ok(Addr(0x4011d6)));
+        assert_eq!(
+            "sym.main".parse::<Addr>().unwrap_err(),
+            "Invalid address: sym.main"
+        );
+        assert_eq!(Addr(0x4011d6).to_string(), "0x4011d6");
+        assert_eq!(format!("{:08x}", Addr(0x1f)), "0000001f");
+    }
+
+    #[test]
+    fn r2_numbers() {
+        assert_eq!(parse_r2_number("0x1F").unwrap(), 31);
+        assert_eq!(parse_r2_number("0XfF").unwrap(), 255);
+        assert_eq!(parse_r2_number("0b101").unwrap(), 5);
+        assert_eq!(parse_r2_number("0o17").unwrap(), 15);
+        assert_eq!(parse_r2_number("\t42\n").unwrap(), 42);
+        assert_eq!(parse_r2_number("0xffffffffffffffff").unwrap(), u64::MAX);
+        for bad in ["", "0x", "-1", "12abc", "0x1_0000_0000_0000_0000", "0b2"] {
+            assert!(parse_r2_number(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(parse_size("724").unwrap(), 724);
+        assert_eq!(parse_size("5.8K").unwrap(), 5939);
+        assert_eq!(parse_size("1.2MB").unwrap(), 1_258_291);
+        assert_eq!(parse_size("4 GiB").unwrap(), 4 << 30);
+        assert_eq!(parse_size("3k").unwrap(), 3072);
+        assert_eq!(parse_size("1T").unwrap(), 1 << 40);
+        assert_eq!(parse_size("12B").unwrap(), 12);
+        for bad in ["", "K", "5X", "1.2.3M", "MB 4"] {
+            assert!(parse_size(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn block_at_the_end_of_memory() {
+        let block = BasicBlock {
+            addr: Addr(u64::MAX - 1),
+            size: 4,
+            ..Default::default()
+        };
+        assert!(block.contains(Addr(u64::MAX)));
+        assert!(!block.contains(Addr(0)));
+        assert!(!block.contains(Addr(u64::MAX - 2)));
+    }
+
+    #[test]
+    fn parses_signatures() {
+        let sig = parse_signature("int main(int argc, char **argv);").unwrap();
+        assert_eq!(sig.ret, "int");
+        assert_eq!(sig.name, "main");
+        assert_eq!(sig.args.len(), 2);
+        assert_eq!(sig.args[1].ty, "char **");
+        assert_eq!(sig.args[1].name, "argv");
+        assert_eq!(sig.to_string(), "int main(int argc, char **argv)");
+        for bad in ["", "main", "int (int a)", "int f(int a"] {
+            match parse_signature(bad) {
+                Err(Error::UnexpectedOutput(_)) => {}
+                res => panic!("{:?}: {:?}", bad, res),
+            }
+        }
+    }
+}
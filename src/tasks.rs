@@ -0,0 +1,103 @@
+//! Running commands as r2 background tasks (`&`).
+//!
+//! A task runs in its own thread inside r2, so a long analysis can go on
+//! while the pipe answers other commands. r2 keeps the output of each task
+//! until it is deleted.
+
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+
+use serde_derive::Deserialize;
+use std::fmt;
+
+/// Identifier of a background task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+pub struct TaskId(pub u64);
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Where a background task is at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskState {
+    /// Queued, not started yet.
+    Before,
+    Running,
+    /// Waiting for its turn on the core lock.
+    Sleeping,
+    Done,
+    Other(String),
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TaskEntry {
+    pub(crate) id: TaskId,
+    #[serde(default)]
+    pub(crate) state: String,
+    #[serde(default)]
+    pub(crate) cmd: String,
+}
+
+impl R2Pipe {
+    /// Starts running `cmd` in the background (`& cmd`).
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// let task = r2.spawn_task("aaaa")?;
+    /// let entry = r2.cmdj("iej")?;
+    /// r2.wait_task(task)?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn spawn_task(&mut self, cmd: &str) -> Result<TaskId, Error> {
+        let before = self.tasks()?;
+        self.cmd_ok(&format!("& {}", cmd))?;
+        self.tasks()?
+            .into_iter()
+            .map(|t| t.id)
+            .filter(|id| !before.iter().any(|t| t.id == *id))
+            .max()
+            .ok_or_else(|| Error::Other(format!("r2 started no task for {}", cmd)))
+    }
+
+    /// The state of the task `id` (`&j`).
+    pub fn task_status(&mut self, id: TaskId) -> Result<TaskState, Error> {
+        let task = self
+            .tasks()?
+            .into_iter()
+            .find(|t| t.id == id)
+            .ok_or_else(|| Error::Other(format!("No task {}", id)))?;
+        Ok(match task.state.as_str() {
+            "before" => TaskState::Before,
+            "running" => TaskState::Running,
+            "sleeping" => TaskState::Sleeping,
+            "done" => TaskState::Done,
+            _ => TaskState::Other(task.state),
+        })
+    }
+
+    /// What the task `id` printed so far (`&=`).
+    pub fn task_output(&mut self, id: TaskId) -> Result<String, Error> {
+        self.cmd(&format!("&= {}", id))
+    }
+
+    /// Blocks until the task `id` is done (`&&`), and returns its output.
+    ///
+    /// Subject to `set_timeout()` like any command: raise it, or poll
+    /// `task_status()`, for tasks running longer.
+    pub fn wait_task(&mut self, id: TaskId) -> Result<String, Error> {
+        self.cmd_ok(&format!("&& {}", id))?;
+        self.task_output(id)
+    }
+
+    /// Deletes the task `id` and its output (`&-`).
+    pub fn delete_task(&mut self, id: TaskId) -> Result<(), Error> {
+        self.cmd_ok(&format!("&- {}", id))
+    }
+
+    pub(crate) fn tasks(&mut self) -> Result<Vec<TaskEntry>, Error> {
+        self.cmd_deserialize("&j")
+    }
+}
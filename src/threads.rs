@@ -0,0 +1,159 @@
+//! Spreading a list of commands over several sessions, like the python
+//! r2pipe's threads.
+
+use crate::error::Error;
+use crate::r2pipe::{R2Pipe, R2PipeSpawnOptions};
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Reply = Sender<(usize, Result<String, Error>)>;
+
+enum Job {
+    /// Run one command.
+    Cmd(usize, String, Reply),
+    /// Run commands from the shared queue until it is empty.
+    Drain(Arc<Mutex<VecDeque<(usize, String)>>>, Reply),
+}
+
+/// `count` sessions on the same file, each in its own thread.
+///
+/// `run()` hands the commands to whichever session is free, for analyses
+/// where every command is independent (one per function...). Each session
+/// has its own state, so preparatory commands such as `aa` must be run in
+/// every session with `run_all()`.
+///
+/// ```no_run
+/// use r2pipe::R2Threads;
+///
+/// let threads = R2Threads::spawn("/bin/ls", 4, None)?;
+/// threads.run_all("aa")?;
+/// for (cmd, res) in threads.run(&["pdf @ main", "pdf @ entry0"]) {
+///     println!("{}: {}", cmd, res?);
+/// }
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+pub struct R2Threads {
+    sessions: Vec<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl R2Threads {
+    /// Spawns `count` sessions on `path`, failing if any of them fails.
+    pub fn spawn(
+        path: &str,
+        count: usize,
+        opts: Option<R2PipeSpawnOptions>,
+    ) -> Result<R2Threads, Error> {
+        if count == 0 {
+            return Err(Error::Other(
+                "R2Threads needs at least one session".to_string(),
+            ));
+        }
+        let pipes = (0..count)
+            .map(|_| R2Pipe::spawn(path, opts.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut sessions = Vec::with_capacity(count);
+        let mut workers = Vec::with_capacity(count);
+        for r2 in pipes {
+            let (tx, rx) = mpsc::channel();
+            sessions.push(tx);
+            workers.push(thread::spawn(move || work(r2, rx)));
+        }
+        Ok(R2Threads { sessions, workers })
+    }
+
+    /// Runs every command of `cmds` on the first free session.
+    ///
+    /// Returns each command with its result, in the same order as `cmds`.
+    pub fn run(&self, cmds: &[&str]) -> Vec<(String, Result<String, Error>)> {
+        let queue: VecDeque<_> = cmds
+            .iter()
+            .enumerate()
+            .map(|(i, cmd)| (i, cmd.to_string()))
+            .collect();
+        let queue = Arc::new(Mutex::new(queue));
+        let (reply, results) = mpsc::channel();
+        for session in &self.sessions {
+            let _ = session.send(Job::Drain(Arc::clone(&queue), reply.clone()));
+        }
+        drop(reply);
+        collect(cmds, results)
+    }
+
+    /// Runs `cmd` once on every session, failing if it failed on any.
+    pub fn run_all(&self, cmd: &str) -> Result<(), Error> {
+        let (reply, results) = mpsc::channel();
+        for (index, session) in self.sessions.iter().enumerate() {
+            let _ = session.send(Job::Cmd(index, cmd.to_string(), reply.clone()));
+        }
+        drop(reply);
+        let cmds = vec![cmd; self.sessions.len()];
+        for (_, res) in collect(&cmds, results) {
+            res?;
+        }
+        Ok(())
+    }
+
+    /// Number of sessions.
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    /// Closes all the sessions and waits for their threads.
+    pub fn close(self) {}
+}
+
+impl Drop for R2Threads {
+    fn drop(&mut self) {
+        // Once its channel is gone, every worker closes its session.
+        self.sessions.clear();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(mut r2: R2Pipe, jobs: Receiver<Job>) {
+    for job in jobs {
+        match job {
+            Job::Cmd(index, cmd, reply) => {
+                let _ = reply.send((index, r2.cmd(&cmd)));
+            }
+            Job::Drain(queue, reply) => loop {
+                let next = queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+                match next {
+                    Some((index, cmd)) => {
+                        let _ = reply.send((index, r2.cmd(&cmd)));
+                    }
+                    None => break,
+                }
+            },
+        }
+    }
+    r2.close();
+}
+
+/// Puts the results back in the order of `cmds`.
+fn collect(
+    cmds: &[&str],
+    results: Receiver<(usize, Result<String, Error>)>,
+) -> Vec<(String, Result<String, Error>)> {
+    let mut ordered: Vec<Option<Result<String, Error>>> = (0..cmds.len()).map(|_| None).collect();
+    for (index, res) in results {
+        ordered[index] = Some(res);
+    }
+    cmds.iter()
+        .zip(ordered)
+        .map(|(cmd, res)| {
+            let res = res.unwrap_or_else(|| Err(Error::Other("Session is gone".to_string())));
+            (cmd.to_string(), res)
+        })
+        .collect()
+}
@@ -0,0 +1,116 @@
+//! Throttling the commands sent to a remote r2, see
+//! `R2Pipe::set_rate_limiter()`.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+    in_flight: usize,
+}
+
+struct Limits {
+    per_second: f64,
+    burst: f64,
+    max_in_flight: usize,
+}
+
+/// A token bucket limiting the commands per second, and optionally the
+/// commands running at once.
+///
+/// Clones share the same bucket, so the same limiter can be set on all the
+/// pipes talking to one r2 webserver:
+///
+/// ```no_run
+/// use r2pipe::{R2Pipe, RateLimiter};
+///
+/// let limiter = RateLimiter::new(20.0, 5).max_in_flight(2);
+/// let mut a = R2Pipe::http("localhost:9090")?;
+/// let mut b = R2Pipe::http("localhost:9090")?;
+/// a.set_rate_limiter(Some(limiter.clone()));
+/// b.set_rate_limiter(Some(limiter));
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: Arc<Limits>,
+    bucket: Arc<(Mutex<Bucket>, Condvar)>,
+}
+
+impl RateLimiter {
+    /// Allows `per_second` commands per second on average, and bursts of
+    /// up to `burst` commands.
+    pub fn new(per_second: f64, burst: u32) -> RateLimiter {
+        let per_second = per_second.max(0.001);
+        let burst = f64::from(burst.max(1));
+        RateLimiter {
+            limits: Arc::new(Limits {
+                per_second,
+                burst,
+                max_in_flight: usize::MAX,
+            }),
+            bucket: Arc::new((
+                Mutex::new(Bucket {
+                    tokens: burst,
+                    last: Instant::now(),
+                    in_flight: 0,
+                }),
+                Condvar::new(),
+            )),
+        }
+    }
+
+    /// Also allows at most `n` commands running at once.
+    pub fn max_in_flight(self, n: usize) -> RateLimiter {
+        RateLimiter {
+            limits: Arc::new(Limits {
+                max_in_flight: n.max(1),
+                ..*self.limits
+            }),
+            bucket: self.bucket,
+        }
+    }
+
+    /// Waits for a token and a free slot, which is released when the
+    /// returned guard is dropped.
+    pub(crate) fn acquire(&self) -> Permit<'_> {
+        let (ref lock, ref freed) = *self.bucket;
+        let mut bucket = lock.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            while bucket.in_flight >= self.limits.max_in_flight {
+                bucket = freed.wait(bucket).unwrap_or_else(|e| e.into_inner());
+            }
+            self.refill(&mut bucket);
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                bucket.in_flight += 1;
+                return Permit { limiter: self };
+            }
+            let wait = (1.0 - bucket.tokens) / self.limits.per_second;
+            drop(bucket);
+            thread::sleep(Duration::from_secs_f64(wait.min(1.0)));
+            bucket = lock.lock().unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn refill(&self, bucket: &mut MutexGuard<'_, Bucket>) {
+        let now = Instant::now();
+        let earned = now.duration_since(bucket.last).as_secs_f64() * self.limits.per_second;
+        bucket.tokens = (bucket.tokens + earned).min(self.limits.burst);
+        bucket.last = now;
+    }
+}
+
+pub(crate) struct Permit<'a> {
+    limiter: &'a RateLimiter,
+}
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        let (ref lock, ref freed) = *self.limiter.bucket;
+        lock.lock().unwrap_or_else(|e| e.into_inner()).in_flight -= 1;
+        freed.notify_one();
+    }
+}
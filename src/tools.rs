@@ -0,0 +1,170 @@
+//! Running r2's companion programs: rabin2, rax2 and rafind2.
+//!
+//! Each query is a single process reading the file, much lighter than a
+//! whole session for static information or a number conversion. Errors
+//! are the crate's, and `RetryPolicy` retries them as it does commands.
+
+use crate::error::Error;
+use crate::retry::{self, RetryPolicy};
+use crate::structs::{Addr, BinInfo, Import, Section, StringEntry};
+
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Listing<T> {
+    List(Vec<T>),
+    Wrapped(BTreeMap<String, Vec<T>>),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BinInfoReply {
+    info: BinInfo,
+}
+
+/// Locates and runs the companion programs.
+///
+/// They are looked up in the directory given to `dir()`, else next to
+/// the r2 of `R2PIPE_R2` when it is a path, else in `PATH`.
+///
+/// ```no_run
+/// use r2pipe::R2Tools;
+///
+/// let tools = R2Tools::new();
+/// let info = tools.bin_info("/bin/ls")?;
+/// println!("{} {}", info.arch, info.bits);
+/// assert_eq!(tools.rax2("0x10+1")?, "17");
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct R2Tools {
+    dir: Option<PathBuf>,
+    retry: Option<RetryPolicy>,
+}
+
+impl R2Tools {
+    pub fn new() -> R2Tools {
+        R2Tools::default()
+    }
+
+    /// Runs the programs installed in `dir`.
+    pub fn dir<P: AsRef<Path>>(mut self, dir: P) -> R2Tools {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Runs again the programs failing as `policy` retries.
+    pub fn retry(mut self, policy: RetryPolicy) -> R2Tools {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Runs `tool` with `args` and returns its standard output.
+    ///
+    /// Fails with `Error::ProcessExited` when it exits with an error.
+    pub fn run(&self, tool: &str, args: &[&str]) -> Result<String, Error> {
+        let exepath = self.locate(tool);
+        retry::run(self.retry.as_ref(), || {
+            let Output {
+                status,
+                stdout,
+                stderr,
+            } = Command::new(&exepath)
+                .args(args)
+                .output()
+                .map_err(|source| Error::Spawn {
+                    exepath: exepath.display().to_string(),
+                    source,
+                })?;
+            if !status.success() {
+                return Err(Error::ProcessExited {
+                    status: Some(status),
+                    stderr: String::from_utf8_lossy(&stderr).into_owned(),
+                });
+            }
+            String::from_utf8(stdout).map_err(Error::Utf8)
+        })
+    }
+
+    /// Runs rabin2 on `file` with `flags` and JSON output (`rabin2 -j`),
+    /// e.g. `-I` for the binary information.
+    pub fn rabin2<T: DeserializeOwned>(&self, file: &str, flags: &[&str]) -> Result<T, Error> {
+        let mut args = vec!["-j"];
+        args.extend_from_slice(flags);
+        args.push(file);
+        let out = self.run("rabin2", &args)?;
+        if out.trim().is_empty() {
+            return Err(Error::EmptyResponse(format!("rabin2 {}", args.join(" "))));
+        }
+        Ok(serde_json::from_str(&out)?)
+    }
+
+    /// What the `bin` object of `ij` holds (`rabin2 -I`).
+    pub fn bin_info(&self, file: &str) -> Result<BinInfo, Error> {
+        let reply: BinInfoReply = self.rabin2(file, &["-I"])?;
+        Ok(reply.info)
+    }
+
+    /// Imported symbols (`rabin2 -i`).
+    pub fn imports(&self, file: &str) -> Result<Vec<Import>, Error> {
+        self.listing(file, "-i")
+    }
+
+    /// Sections of the binary (`rabin2 -S`).
+    pub fn sections(&self, file: &str) -> Result<Vec<Section>, Error> {
+        self.listing(file, "-S")
+    }
+
+    /// Strings in the data sections (`rabin2 -z`).
+    pub fn strings(&self, file: &str) -> Result<Vec<StringEntry>, Error> {
+        self.listing(file, "-z")
+    }
+
+    /// Evaluates `expr` with rax2, e.g. `0x10+1` or `-b 0x41`, and returns
+    /// what it prints, trimmed.
+    pub fn rax2(&self, expr: &str) -> Result<String, Error> {
+        let args: Vec<&str> = expr.split_whitespace().collect();
+        Ok(self.run("rax2", &args)?.trim().to_string())
+    }
+
+    /// The offsets of `file` where the hex bytes `hex` are (`rafind2 -x`).
+    pub fn find_hex(&self, file: &str, hex: &str) -> Result<Vec<Addr>, Error> {
+        if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(Error::InvalidCommand(format!("Not hex bytes: {:?}", hex)));
+        }
+        let out = self.run("rafind2", &["-x", hex, file])?;
+        // `0x1234` per hit, sometimes followed by the matched bytes.
+        Ok(out
+            .lines()
+            .filter_map(|l| l.split_whitespace().next()?.parse().ok())
+            .collect())
+    }
+
+    fn listing<T: DeserializeOwned>(&self, file: &str, flag: &str) -> Result<Vec<T>, Error> {
+        Ok(match self.rabin2(file, &[flag]) {
+            // Nothing to list.
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => match res? {
+                Listing::List(list) => list,
+                Listing::Wrapped(map) => map.into_values().next().unwrap_or_default(),
+            },
+        })
+    }
+
+    fn locate(&self, tool: &str) -> PathBuf {
+        if let Some(ref dir) = self.dir {
+            return dir.join(tool);
+        }
+        let r2 = env::var("R2PIPE_R2").unwrap_or_default();
+        match Path::new(&r2).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() && dir.join(tool).exists() => dir.join(tool),
+            _ => PathBuf::from(tool),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! `tracing` spans and events for the commands and the life of the pipes.
+//!
+//! Everything here compiles to nothing without the `tracing` feature.
+
+use crate::error::Error;
+use crate::session::SessionId;
+
+use std::time::Duration;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Runs the command `f` inside a `r2pipe::cmd` span, then records the size
+/// of its output (as computed by `size`) and how long it took.
+///
+/// With `redact`, only the name of the command goes in the span, since
+/// arguments may hold paths or data the logs shouldn't see.
+#[cfg(feature = "tracing")]
+pub(crate) fn command<T, F, S>(
+    cmd: &str,
+    session: SessionId,
+    redact: bool,
+    f: F,
+    size: S,
+) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+    S: FnOnce(&T) -> usize,
+{
+    let shown = if redact { command_name(cmd) } else { cmd };
+    let span = tracing::debug_span!("r2pipe::cmd", cmd = shown, session = %session);
+    let _enter = span.enter();
+    let start = Instant::now();
+    let res = f();
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    match res {
+        Ok(ref res) => tracing::debug!(bytes = size(res), elapsed_us, "command done"),
+        Err(ref e) => tracing::warn!(error = %e, elapsed_us, "command failed"),
+    }
+    res
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn command<T, F, S>(
+    _cmd: &str,
+    _session: SessionId,
+    _redact: bool,
+    f: F,
+    _size: S,
+) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+    S: FnOnce(&T) -> usize,
+{
+    f()
+}
+
+/// Same as `command()` for a reply read as it comes, once it ended after
+/// `elapsed` with `bytes` of output, or failed.
+#[cfg(feature = "tracing")]
+pub(crate) fn streamed(
+    cmd: &str,
+    session: SessionId,
+    redact: bool,
+    elapsed: Duration,
+    res: Result<usize, &Error>,
+) {
+    let shown = if redact { command_name(cmd) } else { cmd };
+    let span = tracing::debug_span!("r2pipe::cmd", cmd = shown, session = %session);
+    let _enter = span.enter();
+    let elapsed_us = elapsed.as_micros() as u64;
+    match res {
+        Ok(bytes) => tracing::debug!(bytes, elapsed_us, "command done"),
+        Err(e) => tracing::warn!(error = %e, elapsed_us, "command failed"),
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn streamed(
+    _cmd: &str,
+    _session: SessionId,
+    _redact: bool,
+    _elapsed: Duration,
+    _res: Result<usize, &Error>,
+) {
+}
+
+/// Same as `command()` for `count` commands sent at once.
+#[cfg(feature = "tracing")]
+pub(crate) fn batch<F>(count: usize, session: SessionId, f: F) -> Result<Vec<String>, Error>
+where
+    F: FnOnce() -> Result<Vec<String>, Error>,
+{
+    let span = tracing::debug_span!("r2pipe::batch", count, session = %session);
+    let _enter = span.enter();
+    let start = Instant::now();
+    let res = f();
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    match res {
+        Ok(ref res) => {
+            let bytes: usize = res.iter().map(|r| r.len()).sum();
+            tracing::debug!(bytes, elapsed_us, "batch done");
+        }
+        Err(ref e) => tracing::warn!(error = %e, elapsed_us, "batch failed"),
+    }
+    res
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn batch<F>(_count: usize, _session: SessionId, f: F) -> Result<Vec<String>, Error>
+where
+    F: FnOnce() -> Result<Vec<String>, Error>,
+{
+    f()
+}
+
+/// An r2 process was spawned, or a connection opened, on `target`.
+#[cfg(feature = "tracing")]
+pub(crate) fn opened(kind: &str, target: &str, session: SessionId) {
+    tracing::info!(kind, target, session = %session, "r2pipe opened");
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn opened(_kind: &str, _target: &str, _session: SessionId) {}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn closed(kind: &str, session: SessionId) {
+    tracing::info!(kind, session = %session, "r2pipe closed");
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn closed(_kind: &str, _session: SessionId) {}
+
+/// The command without its arguments, temporary seek or pipes.
+#[cfg(feature = "tracing")]
+fn command_name(cmd: &str) -> &str {
+    let cmd = cmd.trim_start_matches('"');
+    cmd.split(|c: char| c.is_whitespace() || "@;|~>\"".contains(c))
+        .next()
+        .unwrap_or("")
+}
@@ -0,0 +1,107 @@
+//! Spawning r2 with the analysis of an earlier run, for pipelines
+//! analyzing the same binaries over and over.
+//!
+//! The first run analyzes the binary (`aaa`) and saves the result, the
+//! next ones load it back, which takes seconds where the analysis of a big
+//! binary takes minutes. The saved analysis is either an r2 project or a
+//! script of the commands recreating it.
+
+use crate::analysis::AnalysisLevel;
+use crate::error::Error;
+use crate::r2pipe::R2Pipe;
+
+use std::fs;
+use std::path::Path;
+use std::process;
+
+/// What the script cache saves of the analysis, replayed with `.`.
+const SCRIPT_CMDS: &[&str] = &["f*", "afl*", "ax*", "CC*"];
+
+impl R2Pipe {
+    /// Spawns r2 on `file` and opens the project `project` (`Po`), r2's
+    /// own format for saving sessions, kept in `dir.projects`.
+    ///
+    /// When there is no such project yet, the binary is analyzed and saved
+    /// as `project` (`Ps`) before returning.
+    ///
+    /// ```no_run
+    /// use r2pipe::R2Pipe;
+    ///
+    /// let mut r2 = R2Pipe::spawn_with_project("/bin/ls", "ls")?;
+    /// let functions = r2.function_metrics()?;
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn spawn_with_project(file: &str, project: &str) -> Result<R2Pipe, Error> {
+        if project.is_empty()
+            || !project
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+            || project.starts_with('.')
+        {
+            return Err(Error::InvalidCommand(format!(
+                "Invalid project name: {:?}",
+                project
+            )));
+        }
+        let mut r2 = R2Pipe::spawn(file, None)?;
+        let projects: Vec<String> = match r2.cmd_deserialize("Pj") {
+            Err(ref e) if e.is_empty_response() => Vec::new(),
+            res => res?,
+        };
+        if projects.iter().any(|p| p == project) {
+            r2.cmd_ok(&format!("Po {}", project))?;
+        } else {
+            r2.analyze(AnalysisLevel::Auto)?;
+            r2.cmd_ok(&format!("Ps {}", project))?;
+        }
+        Ok(r2)
+    }
+
+    /// Spawns r2 on `file` and runs the script saved for it in `cache_dir`,
+    /// recreating the flags, functions, references and comments of its
+    /// analysis.
+    ///
+    /// Scripts are named after the sha256 of the binary, so a rebuilt
+    /// binary is analyzed again rather than given a stale analysis. A
+    /// missing script is written after analyzing the binary (`aaa`).
+    pub fn spawn_with_script_cache<P: AsRef<Path>>(
+        file: &str,
+        cache_dir: P,
+    ) -> Result<R2Pipe, Error> {
+        let mut r2 = R2Pipe::spawn(file, None)?;
+        let sha256 = r2.file_hashes()?.sha256;
+        if sha256.is_empty() {
+            return Err(Error::UnexpectedOutput(format!(
+                "No sha256 for {:?} to look up its script",
+                file
+            )));
+        }
+        let script = cache_dir.as_ref().join(format!("{}.r2", sha256));
+        if script.is_file() {
+            let path = script.to_string_lossy();
+            if path.contains(['"', '\n', '\0']) {
+                return Err(Error::InvalidCommand(format!(
+                    "Unsupported script path: {:?}",
+                    path
+                )));
+            }
+            r2.cmd_ok(&format!("\". {}\"", path))?;
+            return Ok(r2);
+        }
+        r2.analyze(AnalysisLevel::Auto)?;
+        let mut lines = String::new();
+        for cmd in SCRIPT_CMDS {
+            lines.push_str(&r2.cmd(cmd)?);
+            if !lines.is_empty() && !lines.ends_with('\n') {
+                lines.push('\n');
+            }
+        }
+        // Pipelines running in parallel may analyze the same binary, write
+        // aside then rename so no one reads a partial script.
+        fs::create_dir_all(cache_dir.as_ref())?;
+        let partial = script.with_extension(format!("r2.{}", process::id()));
+        fs::write(&partial, lines)?;
+        fs::rename(&partial, &script)?;
+        Ok(r2)
+    }
+}
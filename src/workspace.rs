@@ -0,0 +1,162 @@
+//! Juggling sessions on many binaries at once.
+
+use crate::error::Error;
+use crate::r2pipe::{R2Pipe, R2PipeSpawnOptions};
+
+use std::collections::{HashMap, VecDeque};
+
+struct Member {
+    path: String,
+    pipe: Option<R2Pipe>,
+}
+
+/// Named sessions, one per binary, with at most `capacity` r2 processes
+/// running at once.
+///
+/// Opening one more session than the capacity closes the least recently
+/// used one. Its name stays known: `get()` spawns it again, though
+/// anything done in it before (analysis, flags...) is lost.
+///
+/// ```no_run
+/// use r2pipe::Workspace;
+///
+/// let mut ws = Workspace::new(8);
+/// ws.open("kernel", "fw/kernel.bin")?;
+/// ws.open("boot", "fw/boot.bin")?;
+/// let info = ws.get("kernel")?.cmdj("ij")?;
+/// ws.close_all();
+/// # Ok::<(), r2pipe::Error>(())
+/// ```
+pub struct Workspace {
+    capacity: usize,
+    opts: Option<R2PipeSpawnOptions>,
+    members: HashMap<String, Member>,
+    // Names of the running sessions, least recently used first.
+    order: VecDeque<String>,
+}
+
+impl Workspace {
+    pub fn new(capacity: usize) -> Workspace {
+        Workspace::with_options(capacity, None)
+    }
+
+    /// Spawns every session with `opts`.
+    pub fn with_options(capacity: usize, opts: Option<R2PipeSpawnOptions>) -> Workspace {
+        Workspace {
+            capacity: capacity.max(1),
+            opts,
+            members: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Opens `path` as `name`, replacing any session with that name.
+    ///
+    /// When r2 can't be spawned, `name` is left closed rather than known
+    /// with a path that can't be opened.
+    pub fn open(&mut self, name: &str, path: &str) -> Result<&mut R2Pipe, Error> {
+        self.close(name);
+        self.make_room();
+        let pipe = R2Pipe::spawn(path, self.opts.clone())?;
+        self.members.insert(
+            name.to_string(),
+            Member {
+                path: path.to_string(),
+                pipe: Some(pipe),
+            },
+        );
+        self.order.push_back(name.to_string());
+        self.get(name)
+    }
+
+    /// The session named `name`, spawned again if it was evicted.
+    pub fn get(&mut self, name: &str) -> Result<&mut R2Pipe, Error> {
+        let running = match self.members.get(name) {
+            Some(member) => member.pipe.is_some(),
+            None => return Err(Error::Other(format!("No session named {}", name))),
+        };
+        if running {
+            if let Some(pos) = self.order.iter().position(|n| n == name) {
+                if let Some(n) = self.order.remove(pos) {
+                    self.order.push_back(n);
+                }
+            }
+        } else {
+            self.make_room();
+            let path = self.members[name].path.clone();
+            let pipe = R2Pipe::spawn(&path, self.opts.clone())?;
+            if let Some(member) = self.members.get_mut(name) {
+                member.pipe = Some(pipe);
+            }
+            self.order.push_back(name.to_string());
+        }
+        match self.members.get_mut(name).and_then(|m| m.pipe.as_mut()) {
+            Some(pipe) => Ok(pipe),
+            None => Err(Error::Other(format!("No session named {}", name))),
+        }
+    }
+
+    /// Evicts the least recently used sessions until one more can run.
+    fn make_room(&mut self) {
+        while self.order.len() >= self.capacity {
+            match self.order.pop_front() {
+                Some(old) => self.evict(&old),
+                None => break,
+            }
+        }
+    }
+
+    /// Whether `name` is open and its r2 currently running.
+    pub fn is_running(&self, name: &str) -> bool {
+        self.members.get(name).is_some_and(|m| m.pipe.is_some())
+    }
+
+    /// Names of all the sessions, running or evicted.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.members.keys().map(|n| n.as_str())
+    }
+
+    /// Closes and forgets the session named `name`.
+    pub fn close(&mut self, name: &str) {
+        self.evict(name);
+        self.members.remove(name);
+    }
+
+    /// Closes and forgets all the sessions.
+    pub fn close_all(&mut self) {
+        for member in self.members.values_mut() {
+            if let Some(mut pipe) = member.pipe.take() {
+                pipe.close();
+            }
+        }
+        self.members.clear();
+        self.order.clear();
+    }
+
+    fn evict(&mut self, name: &str) {
+        if let Some(mut pipe) = self.members.get_mut(name).and_then(|m| m.pipe.take()) {
+            pipe.close();
+        }
+        self.order.retain(|n| n != name);
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        self.close_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_open_leaves_no_session() {
+        let mut ws = Workspace::new(2);
+        assert!(ws.open("missing", "/nonexistent/r2pipe/target").is_err());
+        assert_eq!(ws.names().count(), 0);
+        assert!(!ws.is_running("missing"));
+        assert!(ws.get("missing").is_err());
+    }
+}
@@ -0,0 +1,79 @@
+//! Scanning with YARA rules through the r2yara plugin (`yr`).
+//!
+//! r2yara flags every match in the `yara` flagspace, so the matches are
+//! read back from the flags (`fj`) rather than from the scan output.
+
+use crate::error::Error;
+use crate::r2pipe::{file_arg, R2Pipe};
+use crate::structs::{Addr, Flag};
+
+/// A string of a rule found in the scanned file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YaraMatch {
+    pub rule: String,
+    pub addr: Addr,
+    /// Length of the matched bytes.
+    pub size: u64,
+}
+
+impl R2Pipe {
+    /// Whether the r2yara plugin is loaded.
+    pub fn has_yara(&mut self) -> Result<bool, Error> {
+        // r2 only complains on stderr about unknown commands.
+        Ok(!self.cmd("yr?")?.trim().is_empty())
+    }
+
+    /// Scans the file with the rules in `rules_path` (`yr`, `yrs`), in
+    /// place of any rules loaded before, and returns all the matches.
+    ///
+    /// Fails with `Error::Unsupported` when r2yara isn't installed.
+    ///
+    /// ```no_run
+    /// # let mut r2 = r2pipe::R2Pipe::spawn("/bin/ls", None).unwrap();
+    /// for m in r2.yara_scan("rules/packers.yar")? {
+    ///     println!("{} at {}", m.rule, m.addr);
+    /// }
+    /// # Ok::<(), r2pipe::Error>(())
+    /// ```
+    pub fn yara_scan(&mut self, rules_path: &str) -> Result<Vec<YaraMatch>, Error> {
+        file_arg(rules_path)?;
+        if !self.has_yara()? {
+            return Err(Error::Unsupported(
+                "r2yara isn't installed, see `r2pm -ci r2yara`".to_string(),
+            ));
+        }
+        self.cmd_ok("yr-")?;
+        self.cmd_ok(&format!("yr {}", rules_path))?;
+        self.cmd_ok("yrs")?;
+        self.yara_list_matches()
+    }
+
+    /// The matches flagged by previous scans, sorted by address.
+    pub fn yara_list_matches(&mut self) -> Result<Vec<YaraMatch>, Error> {
+        let flags: Vec<Flag> = match self.cmd_deserialize("fj") {
+            Err(ref e) if e.is_empty_response() => return Ok(Vec::new()),
+            res => res?,
+        };
+        let mut matches: Vec<YaraMatch> = flags
+            .into_iter()
+            .filter_map(|f| {
+                Some(YaraMatch {
+                    rule: rule_name(&f.name)?.to_string(),
+                    addr: f.offset,
+                    size: f.size,
+                })
+            })
+            .collect();
+        matches.sort_by_key(|m| m.addr);
+        Ok(matches)
+    }
+}
+
+/// The rule of a flag named `yara.<rule>_<n>`, `n` numbering the matches.
+fn rule_name(flag: &str) -> Option<&str> {
+    let rule = flag.strip_prefix("yara.")?;
+    Some(match rule.rsplit_once('_') {
+        Some((name, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => rule,
+    })
+}